@@ -0,0 +1,12 @@
+//! Decodes the images of structurally valid ICO files with arbitrary image data.
+
+#![no_main]
+
+use ico_builder::reader::IcoFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|file: IcoFile| {
+    for entry in file.entries() {
+        let _ = entry.decode();
+    }
+});
@@ -0,0 +1,14 @@
+//! Parses arbitrary bytes as an ICO file, and writes valid files again.
+
+#![no_main]
+
+use ico_builder::reader::IcoFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(file) = IcoFile::parse(data) {
+        let bytes = file.to_bytes();
+        let reparsed = IcoFile::parse(&bytes).expect("written files can be parsed");
+        assert_eq!(reparsed.entries().len(), file.entries().len());
+    }
+});
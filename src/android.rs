@@ -0,0 +1,220 @@
+//! Launcher icons for Android apps.
+
+use crate::export::{center_on_canvas, write_png};
+use crate::transform::{Background, SuperellipseMask, Transform};
+use crate::{IcoBuilder, Result};
+use image::Rgba;
+use std::path::{Path, PathBuf};
+
+/// Screen densities as `(qualifier, scale factor in percent of mdpi)`.
+const DENSITIES: &[(&str, u32)] = &[
+    ("mdpi", 100),
+    ("hdpi", 150),
+    ("xhdpi", 200),
+    ("xxhdpi", 300),
+    ("xxxhdpi", 400),
+];
+
+/// The size of legacy launcher icons in dp.
+const LEGACY_SIZE: u32 = 48;
+
+/// The size of adaptive icon layers in dp. The launcher masks the
+/// layers to the inner 72dp, of which a circle of 66dp is always visible.
+const LAYER_SIZE: u32 = 108;
+const VISIBLE_SIZE: u32 = 72;
+const SAFE_ZONE_SIZE: u32 = 66;
+
+/// Exports Android launcher icons from the sources of an [`IcoBuilder`] into a `res` directory:
+/// * `mipmap-*/ic_launcher.png` and `mipmap-*/ic_launcher_round.png` for Android 7.1 and older
+/// * `mipmap-*/ic_launcher_foreground.png` with the artwork inside the safe zone
+/// * `values/ic_launcher_background.xml` with the background color
+/// * `mipmap-anydpi-v26/ic_launcher.xml` and `mipmap-anydpi-v26/ic_launcher_round.xml`
+///   describing the adaptive icon
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::android::AndroidExport;
+/// # use ico_builder::Rgba;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-512x512.png");
+/// AndroidExport::default()
+///     .background(Rgba([0x3d, 0xdc, 0x84, 0xff]))
+///     .build(&icon, "app/src/main/res")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AndroidExport {
+    name: String,
+    background: Rgba<u8>,
+}
+
+impl Default for AndroidExport {
+    fn default() -> Self {
+        AndroidExport {
+            name: "ic_launcher".to_owned(),
+            background: Rgba([0xff, 0xff, 0xff, 0xff]),
+        }
+    }
+}
+
+impl AndroidExport {
+    /// Customizes the resource name of the icon, which is referenced
+    /// as `@mipmap/<name>` in `AndroidManifest.xml`. Defaults to `ic_launcher`.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut AndroidExport {
+        self.name = name.into();
+        self
+    }
+
+    /// Customizes the color of the background layer. Defaults to white.
+    pub fn background(&mut self, background: Rgba<u8>) -> &mut AndroidExport {
+        self.background = background;
+        self
+    }
+
+    /// Writes all icons to `res_dir` and returns the paths of the written files.
+    pub fn build(&self, icon: &IcoBuilder, res_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let res_dir = res_dir.as_ref();
+        let name = &self.name;
+        let mut written = Vec::new();
+
+        let legacy_sizes: Vec<_> = DENSITIES
+            .iter()
+            .map(|&(_, scale)| LEGACY_SIZE * scale / 100)
+            .collect();
+        let layer_sizes: Vec<_> = DENSITIES
+            .iter()
+            .map(|&(_, scale)| LAYER_SIZE * scale / 100)
+            .collect();
+        // Legacy icons show the same part of the artwork as the visible area of adaptive icons.
+        let legacy_artworks = icon.render(&artwork_sizes(&legacy_sizes, VISIBLE_SIZE))?;
+        let layer_artworks = icon.render(&artwork_sizes(&layer_sizes, LAYER_SIZE))?;
+
+        let background = Background::solid(self.background);
+        for (i, &(density, _)) in DENSITIES.iter().enumerate() {
+            let dir = res_dir.join(format!("mipmap-{density}"));
+            icon.create_dir_all(&dir)?;
+
+            let mut legacy =
+                center_on_canvas(&legacy_artworks[i], legacy_sizes[i], legacy_sizes[i]);
+            background.apply(&mut legacy);
+            let mut round = legacy.clone();
+            SuperellipseMask::default().apply(&mut legacy);
+            SuperellipseMask::new(2.0).apply(&mut round);
+            written.push(write_png(icon, &dir, &format!("{name}.png"), &legacy)?);
+            written.push(write_png(icon, &dir, &format!("{name}_round.png"), &round)?);
+
+            let foreground = center_on_canvas(&layer_artworks[i], layer_sizes[i], layer_sizes[i]);
+            written.push(write_png(
+                icon,
+                &dir,
+                &format!("{name}_foreground.png"),
+                &foreground,
+            )?);
+        }
+
+        let values_dir = res_dir.join("values");
+        icon.create_dir_all(&values_dir)?;
+        let background_path = values_dir.join(format!("{name}_background.xml"));
+        icon.write_file(&background_path, self.background_xml().as_bytes())?;
+        written.push(background_path);
+
+        let adaptive_dir = res_dir.join("mipmap-anydpi-v26");
+        icon.create_dir_all(&adaptive_dir)?;
+        for file_name in [format!("{name}.xml"), format!("{name}_round.xml")] {
+            let path = adaptive_dir.join(file_name);
+            icon.write_file(&path, self.adaptive_icon_xml().as_bytes())?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    fn background_xml(&self) -> String {
+        let Rgba([r, g, b, _]) = self.background;
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <resources>\n    \
+               <color name=\"{name}_background\">#{r:02X}{g:02X}{b:02X}</color>\n\
+             </resources>\n",
+            name = self.name
+        )
+    }
+
+    fn adaptive_icon_xml(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <adaptive-icon xmlns:android=\"http://schemas.android.com/apk/res/android\">\n    \
+               <background android:drawable=\"@color/{name}_background\"/>\n    \
+               <foreground android:drawable=\"@mipmap/{name}_foreground\"/>\n\
+             </adaptive-icon>\n",
+            name = self.name
+        )
+    }
+}
+
+/// The sizes at which the artwork fills the safe zone of images that show `shown_size` dp of a layer.
+fn artwork_sizes(image_sizes: &[u32], shown_size: u32) -> Vec<u32> {
+    image_sizes
+        .iter()
+        .map(|&size| size * SAFE_ZONE_SIZE / shown_size)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::RgbaImage;
+    use std::sync::Arc;
+
+    #[test]
+    fn artwork_fills_the_safe_zone() {
+        assert_eq!(artwork_sizes(&[108, 432], LAYER_SIZE), [66, 264]);
+        assert_eq!(artwork_sizes(&[48, 192], VISIBLE_SIZE), [44, 176]);
+    }
+
+    #[test]
+    fn writes_legacy_and_adaptive_icons() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .allow_upscaling(true)
+            .add_source_image(RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255])));
+        let written = AndroidExport::default()
+            .name("ic_app")
+            .background(Rgba([0x3d, 0xdc, 0x84, 0xff]))
+            .build(&icon, "res")
+            .unwrap();
+        assert_eq!(written.len(), 3 * DENSITIES.len() + 3);
+
+        let load = |path: &str| {
+            image::load_from_memory(&fs.read(Path::new(path)).unwrap())
+                .unwrap()
+                .into_rgba8()
+        };
+        let legacy = load("res/mipmap-xhdpi/ic_app.png");
+        assert_eq!(legacy.dimensions(), (96, 96));
+        assert_eq!(legacy.get_pixel(0, 0)[3], 0);
+        assert_eq!(*legacy.get_pixel(48, 48), Rgba([255, 0, 0, 255]));
+        // The artwork leaves the background visible around the safe zone.
+        assert_eq!(*legacy.get_pixel(48, 1), Rgba([0x3d, 0xdc, 0x84, 0xff]));
+
+        let foreground = load("res/mipmap-xhdpi/ic_app_foreground.png");
+        assert_eq!(foreground.dimensions(), (216, 216));
+        assert_eq!(foreground.get_pixel(108, 20)[3], 0);
+        assert_eq!(*foreground.get_pixel(108, 108), Rgba([255, 0, 0, 255]));
+
+        let background = fs
+            .read(Path::new("res/values/ic_app_background.xml"))
+            .unwrap();
+        assert!(String::from_utf8(background)
+            .unwrap()
+            .contains("<color name=\"ic_app_background\">#3DDC84</color>"));
+        let adaptive = fs
+            .read(Path::new("res/mipmap-anydpi-v26/ic_app_round.xml"))
+            .unwrap();
+        let adaptive = String::from_utf8(adaptive).unwrap();
+        assert!(adaptive.contains("@color/ic_app_background"));
+        assert!(adaptive.contains("@mipmap/ic_app_foreground"));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::android::AndroidExport;
+use crate::ios::IosExport;
+use crate::linux::LinuxExport;
+use crate::macos::MacosExport;
+use crate::msix::MsixExport;
+use crate::web::WebExport;
+use crate::{BuildReport, IcoBuilder, Result};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// One declaration of an app icon for all platforms: the sources, sizes and transformations
+/// of an [`IcoBuilder`] plus the platforms to export to. Each platform is written to a
+/// subdirectory named after it, e.g. `windows-ico/app-icon.ico` and `web/favicon.ico`.
+///
+/// Like [`Artifacts`](crate::Artifacts), the sources are decoded only once and the platforms
+/// are exported concurrently.
+///
+/// ```no_run
+/// # use ico_builder::{AppIconSpec, IcoBuilder, Platform};
+/// # use ico_builder::web::WebExport;
+/// let report = AppIconSpec::new(IcoBuilder::from_sources(["app-icon-1024x1024.png"]))
+///     .platform(Platform::WindowsIco)
+///     .platform(Platform::Icns(Default::default()))
+///     .platform(Platform::Web(WebExport::default().hashed_file_names(true).clone()))
+///     .platform(Platform::Linux(Default::default()))
+///     .build("dist/icons")?;
+/// for diagnostic in report.ico.iter().flat_map(|ico| &ico.diagnostics) {
+///     println!("{diagnostic}");
+/// }
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AppIconSpec {
+    icon: IcoBuilder,
+    name: String,
+    platforms: Vec<Platform>,
+}
+
+/// A platform that an [`AppIconSpec`] exports to, with the configuration of its exporter.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Platform {
+    /// An ICO file built with the configuration of the [`IcoBuilder`].
+    WindowsIco,
+    Icns(MacosExport),
+    Web(WebExport),
+    Linux(LinuxExport),
+    Msix(MsixExport),
+    Android(AndroidExport),
+    Ios(IosExport),
+}
+
+/// Information about a successful [`AppIconSpec`] build.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct AppIconReport {
+    /// The written files of each platform, in the order the platforms were added.
+    pub platforms: Vec<(&'static str, Vec<PathBuf>)>,
+    /// The report of the ICO file, if [`Platform::WindowsIco`] was exported.
+    pub ico: Option<BuildReport>,
+}
+
+impl AppIconSpec {
+    /// Creates a spec for the sources and configuration of `icon`, without any platforms.
+    pub fn new(icon: IcoBuilder) -> AppIconSpec {
+        AppIconSpec {
+            icon,
+            name: "app-icon".to_owned(),
+            platforms: Vec::new(),
+        }
+    }
+
+    /// Customizes the file name of the ICO file without its extension. Defaults to `app-icon`.
+    /// The names of the other platforms are configured on their exporters.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut AppIconSpec {
+        self.name = name.into();
+        self
+    }
+
+    /// Adds a platform to export to.
+    pub fn platform(&mut self, platform: Platform) -> &mut AppIconSpec {
+        self.platforms.push(platform);
+        self
+    }
+
+    /// Exports all platforms to subdirectories of `output_dir`. Fails if any platform fails;
+    /// with [`IcoBuilder::collect_all_errors`] enabled, the errors of all platforms are reported.
+    pub fn build(&self, output_dir: impl AsRef<Path>) -> Result<AppIconReport> {
+        let output_dir = output_dir.as_ref();
+        let icon = self.icon.with_decoded_sources()?;
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .platforms
+                .iter()
+                .map(|platform| {
+                    let dir = output_dir.join(platform.name());
+                    let icon = &icon;
+                    scope.spawn(move || self.export(icon, platform, &dir))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+
+        let mut report = AppIconReport::default();
+        for (platform, (written, ico)) in self
+            .platforms
+            .iter()
+            .zip(icon.collect(results.into_iter())?)
+        {
+            report.platforms.push((platform.name(), written));
+            report.ico = report.ico.or(ico);
+        }
+        Ok(report)
+    }
+
+    fn export(
+        &self,
+        icon: &IcoBuilder,
+        platform: &Platform,
+        dir: &Path,
+    ) -> Result<(Vec<PathBuf>, Option<BuildReport>)> {
+        icon.create_dir_all(dir)?;
+        let written = match platform {
+            Platform::WindowsIco => {
+                let path = dir.join(format!("{}.ico", self.name));
+                let report = icon.build_file(&path)?;
+                return Ok((vec![path], Some(report)));
+            }
+            Platform::Icns(export) => export.build(icon, dir)?,
+            Platform::Web(export) => export.build(icon, dir)?,
+            Platform::Linux(export) => export.build(icon, dir)?,
+            Platform::Msix(export) => export.build(icon, dir)?,
+            Platform::Android(export) => export.build(icon, dir)?,
+            Platform::Ios(export) => export.build(icon, dir)?,
+        };
+        Ok((written, None))
+    }
+}
+
+impl Platform {
+    /// The name of the platform, e.g. `windows-ico`, which is also the name of its subdirectory.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::WindowsIco => "windows-ico",
+            Platform::Icns(_) => "icns",
+            Platform::Web(_) => "web",
+            Platform::Linux(_) => "linux",
+            Platform::Msix(_) => "msix",
+            Platform::Android(_) => "android",
+            Platform::Ios(_) => "ios",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use crate::reader::IcoFile;
+    use image::{Rgba, RgbaImage};
+    use std::sync::Arc;
+
+    #[test]
+    fn exports_each_platform_to_its_directory() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .sizes(&[16, 32])
+            .add_source_image(RgbaImage::from_pixel(512, 512, Rgba([0, 120, 215, 255])));
+        let report = AppIconSpec::new(icon)
+            .name("tool")
+            .platform(Platform::Linux(Default::default()))
+            .platform(Platform::WindowsIco)
+            .build("dist")
+            .unwrap();
+
+        let names: Vec<_> = report.platforms.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["linux", "windows-ico"]);
+        assert!(report.platforms[0]
+            .1
+            .iter()
+            .all(|path| path.starts_with("dist/linux")));
+        let ico_path = Path::new("dist").join("windows-ico").join("tool.ico");
+        assert_eq!(report.platforms[1].1, std::slice::from_ref(&ico_path));
+        assert_eq!(report.ico.unwrap().frames.len(), 2);
+        let ico = IcoFile::parse(&fs.read(&ico_path).unwrap()).unwrap();
+        assert_eq!(ico.entries().len(), 2);
+    }
+
+    #[test]
+    fn reports_the_errors_of_all_platforms() {
+        let mut icon = IcoBuilder::default();
+        icon.fs(MemoryFs::default())
+            .collect_all_errors(true)
+            .add_source_image(RgbaImage::new(16, 16));
+        let error = AppIconSpec::new(icon)
+            .platform(Platform::Linux(Default::default()))
+            .platform(Platform::Icns(Default::default()))
+            .build("dist")
+            .unwrap_err();
+        assert!(
+            matches!(&error, crate::Error::Multiple(errors) if errors.len() == 2),
+            "{error:?}"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{IcoBuilder, Result};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+type Job<'a> = Box<dyn FnOnce(&IcoBuilder) -> Result<Vec<PathBuf>> + Send + 'a>;
+
+/// Builds several artifacts from the sources of an [`IcoBuilder`] concurrently.
+/// The sources are decoded only once and shared between all artifacts,
+/// and each artifact is built on its own thread.
+///
+/// ```no_run
+/// # use ico_builder::{Artifacts, IcoBuilder};
+/// # use ico_builder::ios::IosExport;
+/// # use ico_builder::web::WebExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_files(["app-icon-32x32.png", "app-icon-1024x1024.png"]);
+/// let written = Artifacts::default()
+///     .add(|icon| Ok(vec![icon.build_file_cargo("app-icon.ico")?]))
+///     .add(|icon| WebExport::default().build(icon, "public"))
+///     .add(|icon| IosExport::default().build(icon, "App/Assets.xcassets"))
+///     .build(&icon)?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Default)]
+pub struct Artifacts<'a> {
+    jobs: Vec<Job<'a>>,
+    manifest: Option<PathBuf>,
+}
+
+impl<'a> Artifacts<'a> {
+    /// Adds an artifact, built by a function that receives the shared builder
+    /// and returns the paths of the written files.
+    pub fn add(
+        &mut self,
+        job: impl FnOnce(&IcoBuilder) -> Result<Vec<PathBuf>> + Send + 'a,
+    ) -> &mut Artifacts<'a> {
+        self.jobs.push(Box::new(job));
+        self
+    }
+
+    /// Writes a [manifest](IcoBuilder::write_manifest) of all written files to `manifest_path`
+    /// after building. The manifest is included in the returned paths.
+    pub fn manifest(&mut self, manifest_path: impl AsRef<Path>) -> &mut Artifacts<'a> {
+        self.manifest = Some(manifest_path.as_ref().to_owned());
+        self
+    }
+
+    /// Builds all artifacts and returns the paths of the written files, in the order the
+    /// artifacts were added. Fails if any artifact fails; with
+    /// [`IcoBuilder::collect_all_errors`] enabled, the errors of all artifacts are reported.
+    ///
+    /// The artifacts are consumed, so the same `Artifacts` can't be built twice.
+    pub fn build(&mut self, icon: &IcoBuilder) -> Result<Vec<PathBuf>> {
+        let icon = icon.with_decoded_sources()?;
+        let jobs = std::mem::take(&mut self.jobs);
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = jobs
+                .into_iter()
+                .map(|job| scope.spawn(|| job(&icon)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+        let mut written = icon.collect(results.into_iter())?.concat();
+        if let Some(manifest_path) = &self.manifest {
+            written.push(icon.write_manifest(&written, manifest_path)?);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use crate::Error;
+    use image::RgbaImage;
+    use std::sync::Arc;
+
+    fn icon(fs: &Arc<MemoryFs>) -> IcoBuilder {
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .sizes(&[16, 32])
+            .add_source_image(RgbaImage::new(32, 32));
+        icon
+    }
+
+    #[test]
+    fn paths_are_returned_in_order_with_the_manifest_last() {
+        let fs = Arc::new(MemoryFs::default());
+        let written = Artifacts::default()
+            .add(|icon| {
+                icon.build_file("out/a.ico")?;
+                Ok(vec![PathBuf::from("out/a.ico")])
+            })
+            .add(|icon| {
+                icon.clone().sizes(&[16]).build_file("out/b.ico")?;
+                Ok(vec![PathBuf::from("out/b.ico")])
+            })
+            .manifest("out/manifest.json")
+            .build(&icon(&fs))
+            .unwrap();
+        assert_eq!(
+            written,
+            ["out/a.ico", "out/b.ico", "out/manifest.json"].map(PathBuf::from)
+        );
+        let manifest = String::from_utf8(fs.read(Path::new("out/manifest.json")).unwrap()).unwrap();
+        assert!(manifest.contains("\"path\": \"a.ico\""));
+        assert!(manifest.contains("\"path\": \"b.ico\""));
+    }
+
+    #[test]
+    fn errors_of_all_artifacts_can_be_collected() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = icon(&fs);
+        let failing = |size| move |_: &IcoBuilder| Err(Error::MissingIconSize(size));
+
+        let mut artifacts = Artifacts::default();
+        artifacts
+            .add(failing(48))
+            .add(|_| Ok(Vec::new()))
+            .add(failing(64));
+        assert!(matches!(
+            artifacts.build(&icon),
+            Err(Error::MissingIconSize(48))
+        ));
+
+        icon.collect_all_errors(true);
+        let mut artifacts = Artifacts::default();
+        artifacts.add(failing(48)).add(failing(64));
+        match artifacts.build(&icon) {
+            Err(Error::Multiple(errors)) => assert!(matches!(
+                errors[..],
+                [Error::MissingIconSize(48), Error::MissingIconSize(64)]
+            )),
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{BuildReport, Error, IcoBuilder, Result};
+use image::ImageFormat;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Converts a directory of source images into one ICO file per source, or per group of sources,
+/// for converting large icon sets like file-type or toolbar icons at once.
+/// All icons share the sizes, transformations and other configuration of a template [`IcoBuilder`].
+///
+/// ```no_run
+/// # use ico_builder::{BatchBuilder, IcoBuilder};
+/// // toolbar/save-16x16.png and toolbar/save-256x256.png become icons/save.ico
+/// let report = BatchBuilder::new(IcoBuilder::default())
+///     .group_by_name(true)
+///     .build("toolbar", "icons")?;
+/// for (path, report) in &report.icons {
+///     println!("{}: {} frames", path.display(), report.frames.len());
+/// }
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct BatchBuilder {
+    template: IcoBuilder,
+    group_by_name: bool,
+}
+
+/// Information about a successful batch build.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct BatchReport {
+    /// The written ICO files and their reports, ordered by path.
+    pub icons: Vec<(PathBuf, BuildReport)>,
+}
+
+impl BatchBuilder {
+    /// Creates a batch builder that configures every icon like `template`.
+    /// Sources of the template are added to every icon.
+    pub fn new(template: IcoBuilder) -> BatchBuilder {
+        BatchBuilder {
+            template,
+            group_by_name: false,
+        }
+    }
+
+    /// Groups sources whose file names only differ in a size suffix into one icon,
+    /// e.g. `save-16x16.png`, `save_32.png` and `save.png` into `save.ico`.
+    /// Defaults to `false`, which creates an icon per source.
+    pub fn group_by_name(&mut self, group_by_name: bool) -> &mut BatchBuilder {
+        self.group_by_name = group_by_name;
+        self
+    }
+
+    /// Builds an icon for each source (or group) of images in `input_dir` and writes it
+    /// to `output_dir`. Files that aren't images in a known format are ignored.
+    ///
+    /// Stops at the first icon that fails, unless [`IcoBuilder::collect_all_errors`]
+    /// is enabled on the template.
+    pub fn build(
+        &self,
+        input_dir: impl AsRef<Path>,
+        output_dir: impl AsRef<Path>,
+    ) -> Result<BatchReport> {
+        let output_dir = output_dir.as_ref();
+        let mut groups = BTreeMap::<String, Vec<PathBuf>>::new();
+        for path in self.template.fs.read_dir(input_dir.as_ref())? {
+            let Some(stem) = source_stem(&path) else {
+                continue;
+            };
+            let name = if self.group_by_name {
+                strip_size_suffix(stem)
+            } else {
+                stem
+            };
+            groups.entry(name.to_owned()).or_default().push(path);
+        }
+
+        self.template.create_dir_all(output_dir)?;
+        let icons = self
+            .template
+            .collect(groups.into_iter().map(|(name, mut sources)| {
+                sources.sort();
+                let path = output_dir.join(format!("{name}.ico"));
+                match self
+                    .template
+                    .clone()
+                    .add_source_files(sources)
+                    .build_file(&path)
+                {
+                    Ok(report) => Ok((path, report)),
+                    Err(error) => Err(Error::BatchIcon {
+                        path,
+                        error: Box::new(error),
+                    }),
+                }
+            }))?;
+        Ok(BatchReport { icons })
+    }
+}
+
+/// The file stem of a source image, or `None` if the file is not an image in a supported format.
+/// Existing ICO files are skipped, since they might be outputs of a previous batch.
+fn source_stem(path: &Path) -> Option<&str> {
+    let format = ImageFormat::from_path(path).ok()?;
+    (format != ImageFormat::Ico && format.reading_enabled()).then_some(path.file_stem()?.to_str()?)
+}
+
+/// Strips a size suffix like `-16x16`, `_32` or ` 48` from a file stem.
+fn strip_size_suffix(stem: &str) -> &str {
+    let without_size = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let without_size = match without_size.strip_suffix('x') {
+        Some(rest) if rest.ends_with(|c: char| c.is_ascii_digit()) => {
+            rest.trim_end_matches(|c: char| c.is_ascii_digit())
+        }
+        _ => without_size,
+    };
+    if without_size.len() == stem.len() {
+        return stem;
+    }
+    match without_size.strip_suffix(['-', '_', ' ']) {
+        Some(name) if !name.is_empty() => name,
+        _ => stem,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_png;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::RgbaImage;
+    use std::sync::Arc;
+
+    #[test]
+    fn strips_size_suffixes() {
+        for (stem, name) in [
+            ("save-16x16", "save"),
+            ("save_32", "save"),
+            ("save 48", "save"),
+            ("save", "save"),
+            ("save-x16", "save-x16"),
+            ("web2", "web2"),
+            ("-16", "-16"),
+            ("16x16", "16x16"),
+        ] {
+            assert_eq!(strip_size_suffix(stem), name, "{stem}");
+        }
+    }
+
+    #[test]
+    fn skips_files_that_are_not_sources() {
+        assert_eq!(
+            source_stem(Path::new("icons/save-16x16.png")),
+            Some("save-16x16")
+        );
+        assert_eq!(source_stem(Path::new("icons/save.ico")), None);
+        assert_eq!(source_stem(Path::new("icons/readme.md")), None);
+    }
+
+    #[test]
+    fn builds_an_icon_per_group() {
+        let fs = Arc::new(MemoryFs::default());
+        for (name, size) in [("save-16x16", 16), ("save-32x32", 32), ("open", 32)] {
+            let png = encode_png(&RgbaImage::new(size, size)).unwrap();
+            fs.write(&Path::new("toolbar").join(format!("{name}.png")), &png)
+                .unwrap();
+        }
+        fs.write(Path::new("toolbar/notes.txt"), b"").unwrap();
+        let mut template = IcoBuilder::default();
+        template
+            .fs(fs.clone())
+            .sizes(&[16, 32])
+            .allow_upscaling(true);
+
+        let report = BatchBuilder::new(template.clone())
+            .group_by_name(true)
+            .build("toolbar", "icons")
+            .unwrap();
+        let icons: Vec<_> = report
+            .icons
+            .iter()
+            .map(|(path, report)| (path.clone(), report.frames.len()))
+            .collect();
+        assert_eq!(
+            icons,
+            [
+                (Path::new("icons").join("open.ico"), 2),
+                (Path::new("icons").join("save.ico"), 2)
+            ]
+        );
+
+        let report = BatchBuilder::new(template)
+            .build("toolbar", "icons")
+            .unwrap();
+        assert_eq!(report.icons.len(), 3);
+    }
+}
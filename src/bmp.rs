@@ -0,0 +1,421 @@
+//! Encoding frames as BMPs, the format of ICO entries before Windows Vista,
+//! and decoding BMP sources with alpha.
+
+use crate::frame::bit_count;
+use crate::png::PNG_SIGNATURE;
+use crate::reader::{u16_at, u32_at, IcoEntry};
+use crate::{BuildReport, Frame, Result};
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeSet;
+
+/// The largest frame that Windows XP displays.
+pub(crate) const XP_MAX_SIZE: u32 = 48;
+
+/// The size of the `BITMAPINFOHEADER` that BMPs in ICO files start with.
+const HEADER_SIZE: u32 = 40;
+/// Uncompressed pixels.
+const BI_RGB: u32 = 0;
+/// Pixels with less alpha are transparent in BMPs without an alpha channel.
+const ALPHA_THRESHOLD: u8 = 128;
+/// The 16 colors of the standard Windows palette.
+const WINDOWS_16_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x80, 0x00, 0x00],
+    [0x00, 0x80, 0x00],
+    [0x80, 0x80, 0x00],
+    [0x00, 0x00, 0x80],
+    [0x80, 0x00, 0x80],
+    [0x00, 0x80, 0x80],
+    [0xc0, 0xc0, 0xc0],
+    [0x80, 0x80, 0x80],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0xff, 0xff, 0x00],
+    [0x00, 0x00, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+/// Encodes a frame as a BMP without file header, followed by the transparency mask, as stored in ICO files.
+/// Supported bit counts are 32 (with alpha), 24, 8, 4 and 1. Indexed frames use the exact colors
+/// of the artwork if there are few enough, and a standard palette otherwise.
+///
+/// ## Panics
+/// Panics on other bit counts.
+pub(crate) fn encode_bmp(frame: &RgbaImage, bit_count: u16) -> Vec<u8> {
+    assert!(
+        matches!(bit_count, 1 | 4 | 8 | 24 | 32),
+        "BMPs with {bit_count} bits per pixel are not supported"
+    );
+    let (width, height) = frame.dimensions();
+    let palette = match bit_count {
+        1 | 4 | 8 => palette(frame, 1 << bit_count),
+        _ => Vec::new(),
+    };
+    let color_row_size = row_size(width, bit_count);
+    let mask_row_size = row_size(width, 1);
+
+    let mut bmp = Vec::new();
+    bmp.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    bmp.extend_from_slice(&width.to_le_bytes());
+    // The height includes the mask.
+    bmp.extend_from_slice(&(height * 2).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&bit_count.to_le_bytes());
+    // No compression.
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    let image_size = (color_row_size + mask_row_size) * height as usize;
+    bmp.extend_from_slice(&(image_size as u32).to_le_bytes());
+    // No resolution.
+    bmp.extend_from_slice(&[0; 8]);
+    bmp.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    for &[r, g, b] in &palette {
+        bmp.extend_from_slice(&[b, g, r, 0]);
+    }
+
+    // Rows are stored bottom-up.
+    for y in (0..height).rev() {
+        let mut row = vec![0; color_row_size];
+        for x in 0..width {
+            let pixel = *frame.get_pixel(x, y);
+            let x = x as usize;
+            match bit_count {
+                32 => {
+                    row[x * 4..x * 4 + 4].copy_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]])
+                }
+                24 if is_opaque(pixel) => {
+                    row[x * 3..x * 3 + 3].copy_from_slice(&[pixel[2], pixel[1], pixel[0]])
+                }
+                24 => {}
+                _ => {
+                    // Transparent pixels must be black, since the mask is combined with XOR.
+                    let index = if is_opaque(pixel) {
+                        nearest(&palette, pixel)
+                    } else {
+                        nearest(&palette, Rgba([0, 0, 0, 0xff]))
+                    };
+                    let pixels_per_byte = 8 / bit_count as usize;
+                    let shift = 8 - bit_count as usize * (x % pixels_per_byte + 1);
+                    row[x / pixels_per_byte] |= (index as u8) << shift;
+                }
+            }
+        }
+        bmp.extend_from_slice(&row);
+    }
+    for y in (0..height).rev() {
+        let mut row = vec![0; mask_row_size];
+        for x in 0..width {
+            let pixel = *frame.get_pixel(x, y);
+            let transparent = match bit_count {
+                32 => pixel[3] == 0,
+                _ => !is_opaque(pixel),
+            };
+            if transparent {
+                row[x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        bmp.extend_from_slice(&row);
+    }
+    bmp
+}
+
+/// Decodes a 32-bit BMP file whose fourth byte per pixel is alpha, as written by many Windows tools.
+/// The format declares that byte as unused, so other decoders produce opaque images.
+/// Returns `None` for other BMPs and for BMPs whose fourth bytes are all `0`, which really are opaque.
+pub(crate) fn decode_with_alpha(data: &[u8]) -> Option<RgbaImage> {
+    if !data.starts_with(b"BM") || data.len() < 14 + HEADER_SIZE as usize {
+        return None;
+    }
+    let pixels_offset = u32_at(data, 10) as usize;
+    let header_size = u32_at(data, 14);
+    let width = i32::from_le_bytes(data[18..22].try_into().ok()?);
+    let height = i32::from_le_bytes(data[22..26].try_into().ok()?);
+    let bit_count = u16_at(data, 28);
+    let compression = u32_at(data, 30);
+    if header_size < HEADER_SIZE || bit_count != 32 || compression != BI_RGB || width <= 0 {
+        return None;
+    }
+    // Rows are stored bottom-up, unless the height is negative.
+    let bottom_up = height > 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    let size = (width as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(4)?;
+    let pixels = data.get(pixels_offset..pixels_offset.checked_add(size)?)?;
+    if pixels.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        return None;
+    }
+    Some(RgbaImage::from_fn(width, height, |x, y| {
+        let row = if bottom_up { height - 1 - y } else { y };
+        let offset = (row as usize * width as usize + x as usize) * 4;
+        let [b, g, r, a] = pixels[offset..offset + 4]
+            .try_into()
+            .expect("pixels have 4 bytes");
+        Rgba([r, g, b, a])
+    }))
+}
+
+/// Re-encodes the frames as 32-bit BMPs and appends an 8-bit copy of each.
+/// Frames that already are BMPs are kept as they are.
+pub(crate) fn xp_compatible_frames(
+    frames: Vec<Frame>,
+    mut report: BuildReport,
+) -> Result<(Vec<Frame>, BuildReport)> {
+    let images = frames
+        .iter()
+        .map(|frame| {
+            let bit_count = bit_count(&frame.data).unwrap_or(32);
+            IcoEntry::new(frame.size, frame.size, 0, 1, bit_count, &frame.data).decode()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut converted = Vec::with_capacity(frames.len() * 2);
+    for (frame, image) in frames.into_iter().zip(&images) {
+        let data = if frame.data.starts_with(PNG_SIGNATURE) {
+            encode_bmp(image, 32)
+        } else {
+            frame.data
+        };
+        converted.push(Frame {
+            size: frame.size,
+            data,
+        });
+    }
+    for image in &images {
+        converted.push(Frame {
+            size: image.width(),
+            data: encode_bmp(image, 8),
+        });
+    }
+    report.frames.extend_from_within(..);
+    Ok((converted, report))
+}
+
+/// The size of a frame encoded with [`encode_bmp`] if its palette has the largest possible size.
+pub(crate) fn encoded_size(size: u32, bit_count: u16) -> usize {
+    let palette_size = match bit_count {
+        1 | 4 | 8 => 4 << bit_count,
+        _ => 0,
+    };
+    let image_size = (row_size(size, bit_count) + row_size(size, 1)) * size as usize;
+    HEADER_SIZE as usize + palette_size + image_size
+}
+
+/// Rows are padded to a multiple of 4 bytes.
+fn row_size(width: u32, bit_count: u16) -> usize {
+    (width as usize * usize::from(bit_count)).div_ceil(32) * 4
+}
+
+fn is_opaque(pixel: Rgba<u8>) -> bool {
+    pixel[3] >= ALPHA_THRESHOLD
+}
+
+/// The colors of the opaque pixels if there are at most `size` of them including black,
+/// which transparent pixels need. Otherwise a standard palette of `size` colors.
+fn palette(frame: &RgbaImage, size: usize) -> Vec<[u8; 3]> {
+    let mut colors = BTreeSet::from([[0, 0, 0]]);
+    for &pixel in frame.pixels().filter(|&&pixel| is_opaque(pixel)) {
+        colors.insert([pixel[0], pixel[1], pixel[2]]);
+        if colors.len() > size {
+            return standard_palette(size);
+        }
+    }
+    colors.into_iter().collect()
+}
+
+/// Black and white, the 16 Windows colors, or a 6×6×6 color cube followed by grays.
+fn standard_palette(size: usize) -> Vec<[u8; 3]> {
+    match size {
+        2 => vec![[0, 0, 0], [0xff, 0xff, 0xff]],
+        16 => WINDOWS_16_COLORS.to_vec(),
+        _ => {
+            let levels = [0x00, 0x33, 0x66, 0x99, 0xcc, 0xff];
+            let mut palette = Vec::with_capacity(size);
+            for r in levels {
+                for g in levels {
+                    for b in levels {
+                        palette.push([r, g, b]);
+                    }
+                }
+            }
+            palette.extend((1..=40).map(|i| {
+                let gray = (i * 255 / 41) as u8;
+                [gray, gray, gray]
+            }));
+            palette
+        }
+    }
+}
+
+/// The index of the palette color closest to `pixel`.
+fn nearest(palette: &[[u8; 3]], pixel: Rgba<u8>) -> usize {
+    let distance = |color: &[u8; 3]| -> u32 {
+        (0..3)
+            .map(|i| u32::from(color[i].abs_diff(pixel[i])).pow(2))
+            .sum()
+    };
+    (0..palette.len())
+        .min_by_key(|&index| distance(&palette[index]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 20×20 artwork with 3 opaque colors, translucent and transparent pixels.
+    /// The width needs padding in every bit count.
+    fn artwork() -> RgbaImage {
+        RgbaImage::from_fn(20, 20, |x, y| match (x / 5 + y / 5) % 5 {
+            0 => Rgba([255, 0, 0, 255]),
+            1 => Rgba([0, 0, 255, 255]),
+            2 => Rgba([255, 255, 255, 200]),
+            3 => Rgba([9, 9, 9, 100]),
+            _ => Rgba([0, 255, 0, 0]),
+        })
+    }
+
+    fn decode(data: &[u8], bit_count: u16) -> RgbaImage {
+        IcoEntry::new(20, 20, 0, 1, bit_count, data)
+            .decode()
+            .unwrap()
+    }
+
+    /// Whether the AND mask marks the pixel as transparent.
+    fn masked(data: &[u8], bit_count: u16, x: u32, y: u32) -> bool {
+        let palette_size = u32_at(data, 32) as usize * 4;
+        let mask = HEADER_SIZE as usize + palette_size + row_size(20, bit_count) * 20;
+        let row = mask + (19 - y as usize) * row_size(20, 1);
+        data[row + x as usize / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    fn bit_count_of(data: &[u8]) -> u16 {
+        u16_at(data, 14)
+    }
+
+    #[test]
+    fn encodes_32_bit_bmps_with_alpha() {
+        let frame = artwork();
+        let data = encode_bmp(&frame, 32);
+        assert_eq!(data.len(), encoded_size(20, 32));
+        let decoded = decode(&data, 32);
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            assert_eq!(decoded.get_pixel(x, y)[3], pixel[3]);
+            if pixel[3] > 0 {
+                assert_eq!(decoded.get_pixel(x, y), pixel);
+            }
+            assert_eq!(masked(&data, 32, x, y), pixel[3] == 0);
+        }
+    }
+
+    #[test]
+    fn encodes_bmps_with_a_mask() {
+        let frame = artwork();
+        for bit_count in [1, 4, 8, 24] {
+            let data = encode_bmp(&frame, bit_count);
+            assert_eq!(bit_count_of(&data), bit_count);
+            let decoded = decode(&data, bit_count);
+            for (x, y, &pixel) in frame.enumerate_pixels() {
+                let decoded = *decoded.get_pixel(x, y);
+                let expected = match (is_opaque(pixel), bit_count) {
+                    (false, _) => Rgba([0, 0, 0, 0]),
+                    // Only black and white fit into 1 bit, so the colors are approximated.
+                    (true, 1) => {
+                        let [r, g, b] = if pixel == Rgba([255, 255, 255, 200]) {
+                            [255; 3]
+                        } else {
+                            [0; 3]
+                        };
+                        Rgba([r, g, b, 255])
+                    }
+                    (true, _) => Rgba([pixel[0], pixel[1], pixel[2], 255]),
+                };
+                assert_eq!(decoded, expected, "{bit_count} bits at {x}, {y}");
+                assert_eq!(decoded[3], if is_opaque(pixel) { 255 } else { 0 });
+                assert_eq!(masked(&data, bit_count, x, y), !is_opaque(pixel));
+            }
+        }
+    }
+
+    #[test]
+    fn uses_standard_palettes_for_many_colors() {
+        let frame = RgbaImage::from_fn(20, 20, |x, y| Rgba([x as u8 * 12, y as u8 * 12, 0, 255]));
+        for bit_count in [1, 4, 8] {
+            let data = encode_bmp(&frame, bit_count);
+            assert_eq!(data.len(), encoded_size(20, bit_count));
+            let decoded = decode(&data, bit_count);
+            let palette = standard_palette(1 << bit_count);
+            for (&decoded, &pixel) in decoded.pixels().zip(frame.pixels()) {
+                let nearest = palette[nearest(&palette, pixel)];
+                assert_eq!(decoded, Rgba([nearest[0], nearest[1], nearest[2], 255]));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "BMPs with 16 bits per pixel are not supported"]
+    fn rejects_other_bit_counts() {
+        encode_bmp(&artwork(), 16);
+    }
+
+    /// A 32-bit BMP file with the given pixels as BGRA, bottom-up for positive heights.
+    fn bmp_file(width: i32, height: i32, bit_count: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut file = b"BM".to_vec();
+        file.extend_from_slice(&(54 + pixels.len() as u32).to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&54u32.to_le_bytes());
+        file.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+        file.extend_from_slice(&width.to_le_bytes());
+        file.extend_from_slice(&height.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&bit_count.to_le_bytes());
+        file.extend_from_slice(&[0; 24]);
+        file.extend_from_slice(pixels);
+        file
+    }
+
+    #[test]
+    fn decodes_bmp_sources_with_alpha() {
+        let pixels = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        let top_row = [Rgba([11, 10, 9, 12]), Rgba([15, 14, 13, 16])];
+        let bottom_row = [Rgba([3, 2, 1, 4]), Rgba([7, 6, 5, 8])];
+
+        let image = decode_with_alpha(&bmp_file(2, 2, 32, &pixels)).unwrap();
+        let rows = [top_row, bottom_row];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(*pixel, rows[y as usize][x as usize]);
+        }
+        let image = decode_with_alpha(&bmp_file(2, -2, 32, &pixels)).unwrap();
+        let rows = [bottom_row, top_row];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(*pixel, rows[y as usize][x as usize]);
+        }
+    }
+
+    #[test]
+    fn ignores_other_bmp_sources() {
+        let opaque = [1, 2, 3, 0, 5, 6, 7, 0];
+        assert_eq!(decode_with_alpha(&bmp_file(2, 1, 32, &opaque)), None);
+        assert_eq!(decode_with_alpha(&bmp_file(2, 1, 24, &[1; 8])), None);
+        assert_eq!(decode_with_alpha(&bmp_file(0, 1, 32, &[1; 8])), None);
+        assert_eq!(decode_with_alpha(&bmp_file(2, 2, 32, &[1; 8])), None);
+        assert_eq!(
+            decode_with_alpha(&bmp_file(i32::MAX, i32::MIN, 32, &[1; 8])),
+            None
+        );
+        assert_eq!(decode_with_alpha(b"BM"), None);
+    }
+
+    #[test]
+    fn appends_8_bit_copies_for_xp() {
+        let frame = Frame {
+            size: 20,
+            data: crate::encode_png(&artwork()).unwrap(),
+        };
+        let (frames, _) = xp_compatible_frames(vec![frame], BuildReport::default()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].data, encode_bmp(&artwork(), 32));
+        assert_eq!(frames[1].data, encode_bmp(&artwork(), 8));
+    }
+}
@@ -0,0 +1,85 @@
+//! An on-disk cache for rendered frames.
+
+use crate::hash::Fnv1a;
+use crate::transform::Transform;
+use crate::FilterType;
+use image::DynamicImage;
+use std::sync::{Arc, OnceLock};
+
+/// The hashes of the decoded sources, computed once for all frames rendered from each source.
+pub(crate) struct SourceHashes(Vec<OnceLock<u64>>);
+
+impl SourceHashes {
+    pub(crate) fn new(count: usize) -> SourceHashes {
+        SourceHashes((0..count).map(|_| OnceLock::new()).collect())
+    }
+
+    /// The hash of `source`, which is the decoded source at `index`.
+    pub(crate) fn get(&self, index: usize, source: &DynamicImage) -> u64 {
+        *self.0[index].get_or_init(|| {
+            Fnv1a::default()
+                .write(&source.width().to_le_bytes())
+                .write(&source.height().to_le_bytes())
+                .write(format!("{:?}", source.color()).as_bytes())
+                .write(source.as_bytes())
+                .finish()
+        })
+    }
+}
+
+/// The cache key of a frame rendered from the source with the hash `source_hash`.
+/// Transforms are identified by their [`Debug`](std::fmt::Debug) representation, and
+/// `resizer` identifies the GPU that resizes the frames, if any.
+pub(crate) fn frame_key(
+    source_hash: u64,
+    size: u32,
+    filter_type: FilterType,
+    transforms: &[Arc<dyn Transform>],
+    resizer: Option<&str>,
+) -> u64 {
+    Fnv1a::default()
+        .write(env!("CARGO_PKG_VERSION").as_bytes())
+        .write(&source_hash.to_le_bytes())
+        .write(&size.to_le_bytes())
+        .write(format!("{filter_type:?}").as_bytes())
+        .write(format!("{transforms:?}").as_bytes())
+        .write(format!("{resizer:?}").as_bytes())
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Opacity;
+    use image::RgbaImage;
+
+    #[test]
+    fn hashes_each_source_once() {
+        let hashes = SourceHashes::new(2);
+        let source = DynamicImage::from(RgbaImage::new(4, 4));
+        let other = DynamicImage::from(RgbaImage::from_pixel(4, 4, [1, 2, 3, 4].into()));
+        assert_ne!(hashes.get(0, &source), hashes.get(1, &other));
+        // The hash is not computed again for the same index.
+        assert_eq!(hashes.get(1, &source), hashes.get(1, &other));
+        assert_ne!(
+            SourceHashes::new(1).get(0, &source),
+            SourceHashes::new(1).get(0, &source.to_rgb8().into())
+        );
+    }
+
+    #[test]
+    fn keys_depend_on_all_parameters() {
+        let transforms: Vec<Arc<dyn Transform>> = vec![Arc::new(Opacity::new(0.5))];
+        let key = frame_key(1, 16, FilterType::Lanczos3, &[], None);
+        assert_eq!(key, frame_key(1, 16, FilterType::Lanczos3, &[], None));
+        for other in [
+            frame_key(2, 16, FilterType::Lanczos3, &[], None),
+            frame_key(1, 32, FilterType::Lanczos3, &[], None),
+            frame_key(1, 16, FilterType::Auto, &[], None),
+            frame_key(1, 16, FilterType::Lanczos3, &transforms, None),
+            frame_key(1, 16, FilterType::Lanczos3, &[], Some("GPU")),
+        ] {
+            assert_ne!(key, other);
+        }
+    }
+}
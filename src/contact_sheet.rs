@@ -0,0 +1,97 @@
+//! A single image showing all frames side by side, for design reviews.
+
+use crate::font;
+use image::imageops::overlay;
+use image::{Rgba, RgbaImage};
+
+/// The space around and between frames.
+const PADDING: u32 = 16;
+/// The scale of the size labels.
+const LABEL_SCALE: u32 = 2;
+const LABEL_COLOR: Rgba<u8> = Rgba([0x40, 0x40, 0x40, 0xff]);
+/// The size of the checkerboard cells that make transparency visible.
+const CELL_SIZE: u32 = 8;
+const CELL_COLORS: [Rgba<u8>; 2] = [
+    Rgba([0xff, 0xff, 0xff, 0xff]),
+    Rgba([0xe0, 0xe0, 0xe0, 0xff]),
+];
+
+/// Places the frames next to each other on a checkerboard, each labelled with its size below it.
+pub(crate) fn render(frames: &[RgbaImage]) -> RgbaImage {
+    let labels: Vec<_> = frames
+        .iter()
+        .map(|frame| format!("{}x{}", frame.width(), frame.height()))
+        .collect();
+    let column_widths: Vec<_> = frames
+        .iter()
+        .zip(&labels)
+        .map(|(frame, label)| frame.width().max(font::text_width(label, LABEL_SCALE)))
+        .collect();
+    let frame_height = frames.iter().map(RgbaImage::height).max().unwrap_or(0);
+    let width = column_widths
+        .iter()
+        .map(|width| width + PADDING)
+        .sum::<u32>()
+        + PADDING;
+    let height = PADDING + frame_height + PADDING / 2 + font::text_height(LABEL_SCALE) + PADDING;
+
+    let mut sheet = checkerboard(width, height);
+    let mut x = PADDING;
+    for ((frame, label), column_width) in frames.iter().zip(&labels).zip(column_widths) {
+        // Frames are aligned at the bottom, so that the labels line up.
+        let frame_x = x + (column_width - frame.width()) / 2;
+        let frame_y = PADDING + frame_height - frame.height();
+        overlay(&mut sheet, frame, frame_x.into(), frame_y.into());
+
+        let label_x = x + (column_width - font::text_width(label, LABEL_SCALE)) / 2;
+        let label_y = PADDING + frame_height + PADDING / 2;
+        font::draw_text(
+            &mut sheet,
+            label,
+            (label_x.into(), label_y.into()),
+            LABEL_SCALE,
+            LABEL_COLOR,
+        );
+        x += column_width + PADDING;
+    }
+    sheet
+}
+
+/// A checkerboard that makes transparency of frames placed on it visible.
+pub(crate) fn checkerboard(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        CELL_COLORS[((x / CELL_SIZE + y / CELL_SIZE) % 2) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_frames_side_by_side() {
+        let red = Rgba([255, 0, 0, 255]);
+        let frames = [
+            RgbaImage::from_pixel(16, 16, red),
+            RgbaImage::from_pixel(48, 48, red),
+        ];
+        let sheet = render(&frames);
+        // The `16x16` label is wider than its frame.
+        let label_width = font::text_width("16x16", LABEL_SCALE);
+        assert_eq!(
+            sheet.width(),
+            PADDING + label_width + PADDING + 48 + PADDING
+        );
+        assert_eq!(
+            sheet.height(),
+            PADDING + 48 + PADDING / 2 + font::text_height(LABEL_SCALE) + PADDING
+        );
+        // Frames are aligned at the bottom.
+        let small_x = PADDING + (label_width - 16) / 2;
+        assert_eq!(*sheet.get_pixel(small_x, PADDING + 32), red);
+        assert_ne!(*sheet.get_pixel(small_x, PADDING + 31), red);
+        assert_eq!(*sheet.get_pixel(sheet.width() - PADDING - 1, PADDING), red);
+        assert_eq!(*sheet.get_pixel(0, 0), CELL_COLORS[0]);
+        assert_eq!(*sheet.get_pixel(CELL_SIZE, 0), CELL_COLORS[1]);
+    }
+}
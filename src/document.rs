@@ -0,0 +1,158 @@
+//! Generators for file-type (document) icons, as shown by Explorer for associated files.
+
+use crate::{IcoBuilder, IconSizes};
+use image::imageops::{overlay, resize, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::sync::Arc;
+
+/// A document icon: a page with a folded corner and an optional format badge in the lower left,
+/// like the icons of associated file types in Explorer.
+///
+/// The page leaves a margin on both sides so that document icons line up with
+/// the system's own, and the badge overlaps the left edge of the page.
+///
+/// ```no_run
+/// # use ico_builder::document::DocumentIcon;
+/// DocumentIcon::default()
+///     .badge(image::open("app-logo.png")?)
+///     .builder()
+///     .build_file("document.ico")?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct DocumentIcon {
+    page: Rgba<u8>,
+    outline: Rgba<u8>,
+    badge: Option<Arc<RgbaImage>>,
+}
+
+impl Default for DocumentIcon {
+    fn default() -> Self {
+        DocumentIcon {
+            page: Rgba([0xff, 0xff, 0xff, 0xff]),
+            outline: Rgba([0x80, 0x80, 0x80, 0xff]),
+            badge: None,
+        }
+    }
+}
+
+impl DocumentIcon {
+    /// Customizes the color of the page. Defaults to white.
+    pub fn page_color(&mut self, page: Rgba<u8>) -> &mut DocumentIcon {
+        self.page = page;
+        self
+    }
+
+    /// Customizes the color of the outline of the page. Defaults to gray.
+    pub fn outline_color(&mut self, outline: Rgba<u8>) -> &mut DocumentIcon {
+        self.outline = outline;
+        self
+    }
+
+    /// Shows a badge for the format, e.g. the logo of the app that opens it,
+    /// in the lower left half of the icon. The badge should be square.
+    pub fn badge(&mut self, badge: impl Into<DynamicImage>) -> &mut DocumentIcon {
+        self.badge = Some(Arc::new(badge.into().into_rgba8()));
+        self
+    }
+
+    /// Renders the document icon at the given size.
+    pub fn render(&self, size: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(size, size);
+        let page_width = size * 3 / 4;
+        let page_height = size * 15 / 16;
+        let left = (size - page_width) / 2;
+        let top = (size - page_height) / 2;
+        let fold = page_width / 4;
+        let line = (size / 32).max(1);
+
+        for y in 0..page_height {
+            for x in 0..page_width {
+                // The folded corner cuts off the top right of the page.
+                let corner_x = x + fold;
+                if corner_x >= page_width + y {
+                    continue;
+                }
+                let is_edge = x < line
+                    || y < line
+                    || x >= page_width - line
+                    || y >= page_height - line
+                    || corner_x + line >= page_width + y
+                    || (x >= page_width - fold && (fold - line..fold).contains(&y))
+                    || (y < fold && (page_width - fold..page_width - fold + line).contains(&x));
+                let color = if is_edge { self.outline } else { self.page };
+                image.put_pixel(left + x, top + y, color);
+            }
+        }
+
+        if let Some(badge) = &self.badge {
+            let badge_size = size / 2;
+            if badge_size > 0 {
+                let badge = resize(&**badge, badge_size, badge_size, FilterType::Lanczos3);
+                overlay(&mut image, &badge, 0, i64::from(size - badge_size));
+            }
+        }
+        image
+    }
+
+    /// Creates a builder with the [`IconSizes::DOCUMENT`] sizes and the document icon rendered
+    /// at each of these sizes as sources. Further transformations can be added to the builder.
+    pub fn builder(&self) -> IcoBuilder {
+        let mut builder = IcoBuilder::default();
+        builder.sizes(IconSizes::DOCUMENT);
+        for &size in IconSizes::DOCUMENT.iter() {
+            builder.add_source_image(self.render(size));
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameOrigin;
+
+    #[test]
+    fn renders_a_page_with_a_folded_corner() {
+        let page = Rgba([0xff, 0xff, 0xf0, 0xff]);
+        let outline = Rgba([0x20, 0x20, 0x20, 0xff]);
+        let image = DocumentIcon::default()
+            .page_color(page)
+            .outline_color(outline)
+            .render(32);
+        // The 24×30 page starts at (4, 1), and the fold cuts off 6px of its top right.
+        assert_eq!(image.get_pixel(0, 16)[3], 0);
+        assert_eq!(image.get_pixel(27, 1)[3], 0);
+        assert_eq!(*image.get_pixel(4, 16), outline);
+        assert_eq!(*image.get_pixel(14, 16), page);
+        assert_eq!(*image.get_pixel(27, 30), outline);
+    }
+
+    #[test]
+    fn badges_cover_the_lower_left() {
+        let red = Rgba([0xff, 0, 0, 0xff]);
+        let image = DocumentIcon::default()
+            .badge(RgbaImage::from_pixel(8, 8, red))
+            .render(32);
+        assert_eq!(*image.get_pixel(2, 28), red);
+        assert_eq!(*image.get_pixel(14, 20), red);
+        assert_ne!(*image.get_pixel(20, 20), red);
+        assert_ne!(*image.get_pixel(8, 14), red);
+    }
+
+    #[test]
+    fn builders_use_a_source_per_size() {
+        let (_, report) = DocumentIcon::default().builder().encode_ico().unwrap();
+        let sizes: Vec<_> = report.frames.iter().map(|frame| frame.size).collect();
+        assert_eq!(
+            sizes,
+            IconSizes::DOCUMENT.iter().copied().collect::<Vec<_>>()
+        );
+        for frame in &report.frames {
+            assert!(matches!(
+                frame.origin,
+                FrameOrigin::Resized { source_size, .. } if source_size == frame.size
+            ));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{Error, IconSizes, Result};
+use std::env;
+use std::path::PathBuf;
+
+const SIZES: &str = "ICO_BUILDER_SIZES";
+const OUTPUT: &str = "ICO_BUILDER_OUTPUT";
+const ALLOW_UPSCALING: &str = "ICO_BUILDER_ALLOW_UPSCALING";
+pub(crate) const PROFILE: &str = "ICO_BUILDER_PROFILE";
+
+/// All environment variables that can override the configuration.
+pub(crate) const VARIABLES: &[&str] = &[SIZES, OUTPUT, ALLOW_UPSCALING, PROFILE];
+
+/// Configuration overrides from environment variables. See [`IcoBuilder::apply_env_overrides`](crate::IcoBuilder::apply_env_overrides).
+#[derive(Debug, Default)]
+pub(crate) struct EnvOverrides {
+    pub(crate) sizes: Option<IconSizes>,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) allow_upscaling: Option<bool>,
+}
+
+impl EnvOverrides {
+    pub(crate) fn from_env() -> Result<EnvOverrides> {
+        Ok(EnvOverrides {
+            sizes: var(SIZES).map(|value| parse_sizes(&value)).transpose()?,
+            output: env::var_os(OUTPUT)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from),
+            allow_upscaling: var(ALLOW_UPSCALING)
+                .map(|value| parse_bool(&value))
+                .transpose()?,
+        })
+    }
+}
+
+/// The profile selected with `ICO_BUILDER_PROFILE`. See [`Profiles::selected`](crate::Profiles::selected).
+pub(crate) fn profile() -> Option<String> {
+    var(PROFILE)
+}
+
+/// The Cargo profile, `debug` or `release`, from the `PROFILE` variable of build scripts.
+pub(crate) fn cargo_profile() -> Option<String> {
+    var("PROFILE")
+}
+
+/// Whether Cargo builds with the `release` profile.
+pub(crate) fn is_cargo_release() -> bool {
+    cargo_profile().as_deref() == Some("release")
+}
+
+/// Empty variables are treated as unset.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn parse_sizes(value: &str) -> Result<IconSizes> {
+    value
+        .split(',')
+        .map(|size| match size.trim().parse() {
+            Ok(size) if size > 0 => Ok(size),
+            _ => Err(invalid(
+                SIZES,
+                value,
+                "expected comma-separated sizes, e.g. `16,32`",
+            )),
+        })
+        .collect()
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(true),
+        "0" | "false" | "no" => Ok(false),
+        _ => Err(invalid(
+            ALLOW_UPSCALING,
+            value,
+            "expected `true` or `false`",
+        )),
+    }
+}
+
+fn invalid(name: &str, value: &str, reason: &str) -> Error {
+    Error::InvalidEnvVar {
+        name: name.to_owned(),
+        value: value.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(&*parse_sizes(" 32, 16 ,32").unwrap(), &[16, 32]);
+        for value in ["16,", "0", "16;32", "-16"] {
+            assert!(
+                matches!(
+                    parse_sizes(value),
+                    Err(Error::InvalidEnvVar { name, value: invalid, .. })
+                        if name == SIZES && invalid == value
+                ),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_booleans() {
+        for value in ["1", "true", " YES "] {
+            assert!(parse_bool(value).unwrap(), "{value}");
+        }
+        for value in ["0", "False", "no"] {
+            assert!(!parse_bool(value).unwrap(), "{value}");
+        }
+        assert!(matches!(
+            parse_bool("maybe"),
+            Err(Error::InvalidEnvVar { name, .. }) if name == ALLOW_UPSCALING
+        ));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::resource::ResourceId;
+use crate::FeatureFormat;
 use core::fmt;
-use std::path::PathBuf;
+use image::ImageFormat;
+use std::path::{Path, PathBuf};
 use std::{error, io};
 
 #[derive(Debug)]
@@ -7,12 +10,52 @@ use std::{error, io};
 pub enum Error {
     Image(image::ImageError),
     Io(io::Error),
+    BatchIcon {
+        path: PathBuf,
+        error: Box<Error>,
+    },
+    DuplicateResourceId(ResourceId),
+    #[cfg(feature = "gpu")]
+    Gpu(String),
+    InvalidEncodedFrame {
+        size: u32,
+    },
+    InvalidEnvVar {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    InvalidIco {
+        reason: String,
+    },
+    InvalidLargeFrame {
+        size: u32,
+    },
+    InvalidLut {
+        line: usize,
+        reason: String,
+    },
     MissingIconSize(u32),
+    Multiple(Vec<Error>),
     NonSquareImage {
         path: PathBuf,
         width: u32,
         height: u32,
     },
+    NonUtf8Path(PathBuf),
+    OutdatedIcon {
+        path: PathBuf,
+        reason: String,
+    },
+    UnknownProfile(String),
+    UnsupportedFeatureFormat {
+        path: PathBuf,
+        format: FeatureFormat,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+        format: ImageFormat,
+    },
 }
 
 impl error::Error for Error {
@@ -20,8 +63,23 @@ impl error::Error for Error {
         match self {
             Error::Image(e) => e.source(),
             Error::Io(e) => e.source(),
+            Error::BatchIcon { error, .. } => Some(error),
+            Error::DuplicateResourceId(..) => None,
+            #[cfg(feature = "gpu")]
+            Error::Gpu(..) => None,
+            Error::InvalidEncodedFrame { .. } => None,
+            Error::InvalidEnvVar { .. } => None,
+            Error::InvalidIco { .. } => None,
+            Error::InvalidLargeFrame { .. } => None,
+            Error::InvalidLut { .. } => None,
             Error::MissingIconSize(..) => None,
+            Error::Multiple(..) => None,
             Error::NonSquareImage { .. } => None,
+            Error::NonUtf8Path(..) => None,
+            Error::OutdatedIcon { .. } => None,
+            Error::UnknownProfile(..) => None,
+            Error::UnsupportedFeatureFormat { .. } => None,
+            Error::UnsupportedFormat { .. } => None,
         }
     }
 }
@@ -30,7 +88,49 @@ impl fmt::Display for Error {
         match self {
             Error::Image(e) => e.fmt(f),
             Error::Io(e) => e.fmt(f),
+            // The error is only returned as the source, so that it isn't reported twice.
+            Error::BatchIcon { path, .. } => write!(f, "Failed to build {p}", p = path.display()),
+            Error::DuplicateResourceId(ResourceId::Ordinal(id)) => {
+                write!(f, "More than one icon has the resource ID {id}")
+            }
+            Error::DuplicateResourceId(ResourceId::Name(name)) => write!(
+                f,
+                "More than one icon has the resource name {name}, ignoring case"
+            ),
+            #[cfg(feature = "gpu")]
+            Error::Gpu(reason) => write!(f, "GPU resizing failed: {reason}"),
+            Error::InvalidEncodedFrame { size } => write!(
+                f,
+                "The encoded {size}px frame is not a PNG or BMP between 1px and 256px"
+            ),
+            Error::InvalidEnvVar {
+                name,
+                value,
+                reason,
+            } => write!(f, "Invalid value `{value}` of {name}: {reason}"),
+            Error::InvalidIco { reason } => write!(f, "Invalid ICO file: {reason}"),
+            Error::InvalidLargeFrame { size } => {
+                write!(f, "The {size}px frame must be a 32-bit RGBA PNG")
+            }
+            Error::InvalidLut { line: 0, reason } => write!(f, "Invalid LUT: {reason}"),
+            Error::InvalidLut { line, reason } => {
+                write!(f, "Invalid LUT on line {line}: {reason}")
+            }
             Error::MissingIconSize(size) => write!(f, "No icon in the sources is >= {size}px"),
+            Error::Multiple(errors) => {
+                write!(f, "{} errors occurred:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n* {error}")?;
+                }
+                Ok(())
+            }
+            Error::NonSquareImage {
+                path,
+                width,
+                height,
+            } if path.as_os_str().is_empty() => {
+                write!(f, "In-memory image ({width} × {height}) is not a square")
+            }
             Error::NonSquareImage {
                 path,
                 width,
@@ -40,18 +140,171 @@ impl fmt::Display for Error {
                 "Image {p} ({width} × {height}) is not a square",
                 p = path.display()
             ),
+            Error::NonUtf8Path(path) => write!(
+                f,
+                "Path {p} is not valid UTF-8\nHint: Cargo can only track files with UTF-8 paths",
+                p = path.display()
+            ),
+            Error::OutdatedIcon { path, reason } => write!(
+                f,
+                "{p} is out of date: {reason}\nHint: Rebuild it from its sources",
+                p = path.display()
+            ),
+            Error::UnknownProfile(name) => write!(f, "There is no profile named `{name}`"),
+            Error::UnsupportedFeatureFormat { path, format } => {
+                write_unsupported_format(f, path, format, Format::Feature(*format))
+            }
+            Error::UnsupportedFormat { path, format } => write_unsupported_format(
+                f,
+                path,
+                &format_args!("{format:?}"),
+                Format::Image(*format),
+            ),
         }
     }
 }
 
+/// Writes that the format of a source at `path`, which is empty for in-memory sources,
+/// is not enabled, with a hint on how to enable it.
+fn write_unsupported_format(
+    f: &mut fmt::Formatter,
+    path: &Path,
+    name: &dyn fmt::Display,
+    format: Format,
+) -> fmt::Result {
+    if path.as_os_str().is_empty() {
+        write!(
+            f,
+            "In-memory image is in the {name} format, which is not enabled"
+        )?;
+    } else {
+        write!(
+            f,
+            "Image {p} is in the {name} format, which is not enabled",
+            p = path.display()
+        )?;
+    }
+    match format_feature(format) {
+        FormatFeature::IcoBuilder(feature) => write!(
+            f,
+            "\nHint: Enable the `{feature}` feature of the `ico-builder` crate"
+        ),
+        FormatFeature::Image(feature) => write!(
+            f,
+            "\nHint: Enable the `{feature}` feature of the `image` crate"
+        ),
+        FormatFeature::Unknown => Ok(()),
+    }
+}
+
+enum Format {
+    Image(ImageFormat),
+    Feature(FeatureFormat),
+}
+
+enum FormatFeature {
+    IcoBuilder(&'static str),
+    Image(&'static str),
+    Unknown,
+}
+
+/// The Cargo feature that enables decoding of the format.
+/// Formats that this crate has no feature for can be enabled on the `image` crate directly.
+fn format_feature(format: Format) -> FormatFeature {
+    match format {
+        Format::Image(ImageFormat::Jpeg) => FormatFeature::IcoBuilder("jpeg"),
+        Format::Image(ImageFormat::Gif) => FormatFeature::IcoBuilder("gif"),
+        Format::Image(ImageFormat::Tiff) => FormatFeature::IcoBuilder("tiff"),
+        Format::Image(ImageFormat::Qoi) => FormatFeature::IcoBuilder("qoi"),
+        Format::Feature(FeatureFormat::Heif) => FormatFeature::IcoBuilder("heif"),
+        Format::Feature(FeatureFormat::JpegXl) => FormatFeature::IcoBuilder("jxl"),
+        Format::Image(ImageFormat::WebP) => FormatFeature::Image("webp"),
+        Format::Image(ImageFormat::Pnm) => FormatFeature::Image("pnm"),
+        Format::Image(ImageFormat::Tga) => FormatFeature::Image("tga"),
+        Format::Image(ImageFormat::Dds) => FormatFeature::Image("dds"),
+        Format::Image(ImageFormat::Hdr) => FormatFeature::Image("hdr"),
+        Format::Image(ImageFormat::OpenExr) => FormatFeature::Image("exr"),
+        Format::Image(ImageFormat::Farbfeld) => FormatFeature::Image("ff"),
+        Format::Image(ImageFormat::Avif) => FormatFeature::Image("avif"),
+        _ => FormatFeature::Unknown,
+    }
+}
+
 impl From<image::ImageError> for Error {
     fn from(source: image::ImageError) -> Self {
         Error::Image(source)
     }
 }
 
+#[cfg(feature = "camino")]
+impl From<camino::FromPathBufError> for Error {
+    fn from(source: camino::FromPathBufError) -> Self {
+        Error::NonUtf8Path(source.into_path_buf())
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(source: io::Error) -> Self {
         Error::Io(source)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_at_features_of_unsupported_formats() {
+        let error = Error::UnsupportedFormat {
+            path: "icon.webp".into(),
+            format: ImageFormat::WebP,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Image icon.webp is in the WebP format, which is not enabled\n\
+             Hint: Enable the `webp` feature of the `image` crate"
+        );
+        let error = Error::UnsupportedFormat {
+            path: PathBuf::new(),
+            format: ImageFormat::Jpeg,
+        };
+        assert_eq!(
+            error.to_string(),
+            "In-memory image is in the Jpeg format, which is not enabled\n\
+             Hint: Enable the `jpeg` feature of the `ico-builder` crate"
+        );
+        let error = Error::UnsupportedFeatureFormat {
+            path: "icon.heic".into(),
+            format: FeatureFormat::Heif,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Image icon.heic is in the HEIF format, which is not enabled\n\
+             Hint: Enable the `heif` feature of the `ico-builder` crate"
+        );
+        let error = Error::UnsupportedFeatureFormat {
+            path: PathBuf::new(),
+            format: FeatureFormat::JpegXl,
+        };
+        assert_eq!(
+            error.to_string(),
+            "In-memory image is in the JPEG XL format, which is not enabled\n\
+             Hint: Enable the `jxl` feature of the `ico-builder` crate"
+        );
+    }
+
+    #[test]
+    fn batch_errors_have_their_cause_as_source() {
+        use std::error::Error as _;
+
+        let error = Error::BatchIcon {
+            path: "icons/app.ico".into(),
+            error: Box::new(Error::MissingIconSize(256)),
+        };
+        assert_eq!(error.to_string(), "Failed to build icons/app.ico");
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "No icon in the sources is >= 256px"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::frame::encode_png;
+use crate::png::encode_indexed_png;
+use crate::{bmp, Result};
+use image::imageops::{resize, FilterType};
+use image::DynamicImage;
+
+/// The size of the ICO header and of each directory entry.
+const ICO_HEADER_SIZE: u64 = 6;
+const ICO_ENTRY_SIZE: u64 = 16;
+/// The size of a PNG without pixels: the signature, the `IHDR` and `IEND` chunks,
+/// and the header of an `IDAT` chunk.
+const PNG_OVERHEAD: f64 = 57.0;
+/// The largest size that is encoded to estimate the size of a PNG frame.
+const SAMPLE_SIZE: u32 = 64;
+
+/// The predicted size of an ICO file. See [`IcoBuilder::estimate_output_size`](crate::IcoBuilder::estimate_output_size).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct SizeEstimate {
+    /// The frames in the order of the output.
+    pub frames: Vec<FrameEstimate>,
+    /// The predicted size of the whole ICO file in bytes, including its header and directory.
+    pub total_bytes: u64,
+}
+
+/// The predicted size of a single frame.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct FrameEstimate {
+    pub size: u32,
+    /// The predicted size of the encoded frame in bytes.
+    pub bytes: u64,
+}
+
+impl SizeEstimate {
+    pub(crate) fn new(frames: Vec<FrameEstimate>) -> SizeEstimate {
+        let total_bytes = ICO_HEADER_SIZE
+            + frames
+                .iter()
+                .map(|frame| ICO_ENTRY_SIZE + frame.bytes)
+                .sum::<u64>();
+        SizeEstimate {
+            frames,
+            total_bytes,
+        }
+    }
+}
+
+impl FrameEstimate {
+    /// The size of a frame that is already encoded.
+    pub(crate) fn encoded(size: u32, data: &[u8]) -> FrameEstimate {
+        FrameEstimate {
+            size,
+            bytes: data.len() as u64,
+        }
+    }
+
+    /// Estimates the size of a PNG frame resized from `source` with `filter`. Frames up to
+    /// [`SAMPLE_SIZE`] are resized and encoded. Larger frames are extrapolated from the compressed sizes at [`SAMPLE_SIZE`]
+    /// and half of it: the size of detailed artwork grows with the area of the frame,
+    /// while the size of flat artwork mostly grows with the length of its edges.
+    pub(crate) fn png(
+        source: &DynamicImage,
+        size: u32,
+        filter: FilterType,
+        indexed: bool,
+    ) -> Result<FrameEstimate> {
+        let encoded_size = |sample_size| -> Result<f64> {
+            let sample = resize(source, sample_size, sample_size, filter);
+            let encoded = if indexed {
+                encode_indexed_png(&sample)?
+            } else {
+                encode_png(&sample)?
+            };
+            Ok((encoded.len() as f64 - PNG_OVERHEAD).max(0.0))
+        };
+        if size <= SAMPLE_SIZE {
+            return Ok(FrameEstimate::with_pixel_bytes(size, encoded_size(size)?));
+        }
+        // Fits `area * size² + edges * size` to both samples.
+        let (small, large) = (f64::from(SAMPLE_SIZE / 2), f64::from(SAMPLE_SIZE));
+        let (small_bytes, large_bytes) =
+            (encoded_size(SAMPLE_SIZE / 2)?, encoded_size(SAMPLE_SIZE)?);
+        let area = ((large_bytes / large - small_bytes / small) / (large - small)).max(0.0);
+        let edges = (large_bytes / large - area * large).max(0.0);
+        let size_f = f64::from(size);
+        Ok(FrameEstimate::with_pixel_bytes(
+            size,
+            area * size_f * size_f + edges * size_f,
+        ))
+    }
+
+    fn with_pixel_bytes(size: u32, pixel_bytes: f64) -> FrameEstimate {
+        FrameEstimate {
+            size,
+            bytes: (PNG_OVERHEAD + pixel_bytes).round() as u64,
+        }
+    }
+
+    /// The size of an uncompressed BMP frame with the given bit count, including its mask
+    /// and the largest possible palette.
+    pub(crate) fn bmp(size: u32, bit_count: u16) -> FrameEstimate {
+        FrameEstimate {
+            size,
+            bytes: bmp::encoded_size(size, bit_count) as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn totals_include_the_header_and_directory() {
+        let estimate = SizeEstimate::new(vec![
+            FrameEstimate::encoded(16, &[0; 100]),
+            FrameEstimate::bmp(32, 32),
+        ]);
+        let bmp_size = bmp::encode_bmp(&RgbaImage::new(32, 32), 32).len() as u64;
+        assert_eq!(estimate.frames[1].bytes, bmp_size);
+        assert_eq!(estimate.total_bytes, 6 + 2 * 16 + 100 + bmp_size);
+    }
+
+    #[test]
+    fn large_frames_are_extrapolated() {
+        let source = DynamicImage::from(RgbaImage::from_fn(512, 512, |x, y| {
+            Rgba([(x / 2) as u8, (y / 2) as u8, ((x + y) / 4) as u8, 255])
+        }));
+        let estimate = FrameEstimate::png(&source, 256, FilterType::Lanczos3, false).unwrap();
+        let actual = encode_png(&resize(&source, 256, 256, FilterType::Lanczos3)).unwrap();
+        let error = estimate.bytes.abs_diff(actual.len() as u64) as f64;
+        assert!(
+            error < 0.25 * actual.len() as f64,
+            "{estimate:?} vs {}",
+            actual.len()
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Helpers shared by the exporters for platform-specific icon assets.
+
+use crate::{encode_png, IcoBuilder, Result};
+use image::imageops::overlay;
+use image::{Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+
+/// High contrast themes as `(qualifier, silhouette color)`.
+/// Black themes need light artwork and vice versa.
+pub(crate) const CONTRAST_VARIANTS: &[(&str, Rgba<u8>)] = &[
+    ("contrast-black", Rgba([0xff, 0xff, 0xff, 0xff])),
+    ("contrast-white", Rgba([0x00, 0x00, 0x00, 0xff])),
+];
+
+/// Places the artwork in the center of a transparent canvas.
+pub(crate) fn center_on_canvas(artwork: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::new(width, height);
+    let x = (width - artwork.width()) / 2;
+    let y = (height - artwork.height()) / 2;
+    overlay(&mut canvas, artwork, x.into(), y.into());
+    canvas
+}
+
+/// Writes the image as a PNG file using the filesystem of the [`IcoBuilder`].
+pub(crate) fn write_png(
+    icon: &IcoBuilder,
+    output_dir: &Path,
+    name: &str,
+    image: &RgbaImage,
+) -> Result<PathBuf> {
+    let path = output_dir.join(name);
+    icon.write_file(&path, &encode_png(image)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artwork_is_centered() {
+        let artwork = RgbaImage::from_pixel(2, 2, Rgba([0xff, 0, 0, 0xff]));
+        let canvas = center_on_canvas(&artwork, 6, 5);
+        assert_eq!(canvas.dimensions(), (6, 5));
+        let opaque: Vec<_> = canvas
+            .enumerate_pixels()
+            .filter(|(.., pixel)| pixel[3] > 0)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(opaque, [(2, 1), (3, 1), (2, 2), (3, 2)]);
+    }
+}
@@ -0,0 +1,161 @@
+//! Serving favicons from an [axum](https://docs.rs/axum) web app.
+//! Requires the `axum` feature.
+
+use crate::hash::Fnv1a;
+use crate::{encode_png, IcoBuilder, Result};
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+/// How long browsers may cache the favicons without revalidating, in seconds.
+const MAX_AGE: u32 = 24 * 60 * 60;
+
+/// Favicons generated in memory, served as `/favicon.ico` and
+/// `/favicon-{size}x{size}.png` for each of the [sizes](IcoBuilder::sizes) of the builder.
+///
+/// The responses have the correct content type, a `Cache-Control` header and an `ETag`,
+/// so that browsers can revalidate them cheaply.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::favicon::Favicons;
+/// # use axum::Router;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-256x256.png");
+/// let app: Router = Router::new()
+///     // ... routes of the app
+///     .merge(Favicons::new(&icon)?.router());
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Favicons {
+    files: Vec<File>,
+}
+
+#[derive(Debug, Clone)]
+struct File {
+    path: String,
+    content_type: &'static str,
+    contents: Bytes,
+    etag: HeaderValue,
+}
+
+impl Favicons {
+    /// Builds the favicons from the sources of an [`IcoBuilder`]. This is usually done once at startup.
+    pub fn new(icon: &IcoBuilder) -> Result<Favicons> {
+        let (ico, _) = icon.encode_ico()?;
+        let mut files = vec![File::new("/favicon.ico".to_owned(), "image/x-icon", ico)];
+        for frame in icon.render(&icon.sizes)? {
+            let size = frame.width();
+            files.push(File::new(
+                format!("/favicon-{size}x{size}.png"),
+                "image/png",
+                encode_png(&frame)?,
+            ));
+        }
+        Ok(Favicons { files })
+    }
+
+    /// The paths of the served files.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|file| file.path.as_str())
+    }
+
+    /// A router that serves the favicons. It can be merged into the router of the app.
+    pub fn router<S: Clone + Send + Sync + 'static>(&self) -> Router<S> {
+        self.files.iter().fold(Router::new(), |router, file| {
+            let file = file.clone();
+            router.route(
+                &file.path.clone(),
+                get(move |headers: HeaderMap| async move { file.respond(&headers) }),
+            )
+        })
+    }
+}
+
+impl File {
+    fn new(path: String, content_type: &'static str, contents: Vec<u8>) -> File {
+        let hash = Fnv1a::default().write(&contents).finish();
+        File {
+            path,
+            content_type,
+            contents: contents.into(),
+            etag: HeaderValue::from_str(&format!("\"{hash:016x}\""))
+                .expect("hex is a valid header"),
+        }
+    }
+
+    fn respond(&self, headers: &HeaderMap) -> Response {
+        let cache_control = HeaderValue::from_str(&format!("public, max-age={MAX_AGE}"))
+            .expect("the cache control is a valid header");
+        if headers.get(header::IF_NONE_MATCH) == Some(&self.etag) {
+            return (
+                StatusCode::NOT_MODIFIED,
+                [
+                    (header::ETAG, self.etag.clone()),
+                    (header::CACHE_CONTROL, cache_control),
+                ],
+            )
+                .into_response();
+        }
+        (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(self.content_type),
+                ),
+                (header::ETAG, self.etag.clone()),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+            self.contents.clone(),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn serves_the_ico_and_a_png_per_size() {
+        let mut icon = IcoBuilder::default();
+        icon.sizes(&[16, 32])
+            .add_source_image(RgbaImage::new(32, 32));
+        let favicons = Favicons::new(&icon).unwrap();
+        assert_eq!(
+            favicons.paths().collect::<Vec<_>>(),
+            ["/favicon.ico", "/favicon-16x16.png", "/favicon-32x32.png"]
+        );
+        let (ico, png) = (&favicons.files[0], &favicons.files[1]);
+        assert_eq!(ico.content_type, "image/x-icon");
+        assert_eq!(png.content_type, "image/png");
+        assert_eq!(image::load_from_memory(&png.contents).unwrap().width(), 16);
+        assert_ne!(ico.etag, png.etag);
+    }
+
+    #[test]
+    fn matching_etags_are_not_modified() {
+        let file = File::new("/favicon.ico".to_owned(), "image/x-icon", b"icon".to_vec());
+        let response = file.respond(&HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/x-icon");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=86400"
+        );
+        assert_eq!(response.headers()[header::ETAG], file.etag);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, file.etag.clone());
+        let response = file.respond(&headers);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().get(header::CONTENT_TYPE).is_none());
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"stale\""));
+        assert_eq!(file.respond(&headers).status(), StatusCode::OK);
+    }
+}
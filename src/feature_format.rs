@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// The major brands of HEIF files with HEVC-coded images.
+const HEIF_BRANDS: [&[u8; 4]; 8] = [
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1",
+];
+/// The signature of bare JPEG XL codestreams.
+const JXL_CODESTREAM_SIGNATURE: &[u8] = &[0xff, 0x0a];
+/// The signature of JPEG XL files in the ISOBMFF-based container format.
+const JXL_CONTAINER_SIGNATURE: &[u8] = &[
+    0x00, 0x00, 0x00, 0x0c, b'J', b'X', b'L', b' ', 0x0d, 0x0a, 0x87, 0x0a,
+];
+
+/// A source format that is decoded by this crate instead of the [`image`] crate,
+/// if its feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FeatureFormat {
+    /// HEIF with HEVC-coded images, e.g. `.heic` files, decoded with the `heif` feature.
+    Heif,
+    /// JPEG XL, decoded with the `jxl` feature.
+    JpegXl,
+}
+
+impl FeatureFormat {
+    /// Recognizes the format by the signature at the start of `data`,
+    /// regardless of whether its feature is enabled.
+    pub(crate) fn from_signature(data: &[u8]) -> Option<FeatureFormat> {
+        let is_heif = data.get(4..8) == Some(b"ftyp")
+            && data
+                .get(8..12)
+                .is_some_and(|brand| HEIF_BRANDS.iter().any(|known| known[..] == *brand));
+        if is_heif {
+            Some(FeatureFormat::Heif)
+        } else if data.starts_with(JXL_CODESTREAM_SIGNATURE)
+            || data.starts_with(JXL_CONTAINER_SIGNATURE)
+        {
+            Some(FeatureFormat::JpegXl)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for FeatureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureFormat::Heif => write!(f, "HEIF"),
+            FeatureFormat::JpegXl => write!(f, "JPEG XL"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_signatures() {
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        assert_eq!(
+            FeatureFormat::from_signature(heic),
+            Some(FeatureFormat::Heif)
+        );
+        assert_eq!(FeatureFormat::from_signature(b"\0\0\0\x18ftypavif"), None);
+        assert_eq!(
+            FeatureFormat::from_signature(&[0xff, 0x0a, 0xfa]),
+            Some(FeatureFormat::JpegXl)
+        );
+        assert_eq!(
+            FeatureFormat::from_signature(JXL_CONTAINER_SIGNATURE),
+            Some(FeatureFormat::JpegXl)
+        );
+        assert_eq!(FeatureFormat::from_signature(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(FeatureFormat::from_signature(b"\0\0\0\x18ftyp"), None);
+        assert_eq!(FeatureFormat::from_signature(&[]), None);
+    }
+}
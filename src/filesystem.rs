@@ -0,0 +1,187 @@
+//! Abstraction over the filesystem used for reading sources and writing outputs.
+//!
+//! By default, the real filesystem is used. A custom [`Fs`] allows driving the builder
+//! from tests, WASM hosts, or tools with virtual filesystems without touching the disk.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fmt, fs, io};
+
+/// A filesystem that sources are read from and outputs are written to.
+pub trait Fs: fmt::Debug + Send + Sync {
+    /// Reads the entire contents of a file.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Writes a file, replacing it if it already exists.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Creates a directory and all of its missing parents.
+    /// Does nothing by default, for filesystems where directories are implicit.
+    fn create_dir_all(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Lists the paths of the files in a directory, in no particular order.
+    /// Fails with [`io::ErrorKind::Unsupported`] by default.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Listing {} is not supported", path.display()),
+        ))
+    }
+}
+
+/// Allows sharing a filesystem with the builder, e.g. to inspect
+/// the outputs of a [`MemoryFs`] after building.
+impl<F: Fs + ?Sized> Fs for Arc<F> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        (**self).read_dir(path)
+    }
+}
+
+/// The real filesystem, using [`std::fs`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl Fs for StdFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// An in-memory filesystem. Directories are implicit.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::filesystem::{Fs, MemoryFs};
+/// # use std::path::Path;
+/// # use std::sync::Arc;
+/// let fs = Arc::new(MemoryFs::default());
+/// fs.write(Path::new("app-icon-256x256.png"), &std::fs::read("app-icon-256x256.png")?)?;
+/// IcoBuilder::default()
+///     .fs(fs.clone())
+///     .add_source_file("app-icon-256x256.png")
+///     .build_file("app-icon.ico")?;
+/// let icon = fs.read(Path::new("app-icon.ico"))?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFs {
+    /// The paths of all files, in no particular order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files().keys().cloned().collect()
+    }
+
+    fn files(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Vec<u8>>> {
+        // A panic while holding the lock can't leave the map in an inconsistent state.
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Fs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files().get(path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            )
+        })
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.files().insert(path.to_owned(), contents.to_owned());
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .files()
+            .keys()
+            .filter(|file| file.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_fs_lists_direct_children() {
+        let fs = MemoryFs::default();
+        fs.write(Path::new("icons/a.png"), b"a").unwrap();
+        fs.write(Path::new("icons/b.png"), b"b").unwrap();
+        fs.write(Path::new("icons/nested/c.png"), b"c").unwrap();
+        fs.write(Path::new("icons/a.png"), b"replaced").unwrap();
+
+        assert_eq!(fs.read(Path::new("icons/a.png")).unwrap(), b"replaced");
+        assert_eq!(
+            fs.read(Path::new("icons/c.png")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        let mut children = fs.read_dir(Path::new("icons")).unwrap();
+        children.sort();
+        assert_eq!(
+            children,
+            [PathBuf::from("icons/a.png"), PathBuf::from("icons/b.png")]
+        );
+        assert_eq!(fs.paths().len(), 3);
+    }
+
+    #[test]
+    fn listing_is_unsupported_by_default() {
+        #[derive(Debug)]
+        struct WriteOnly;
+        impl Fs for WriteOnly {
+            fn read(&self, _path: &Path) -> io::Result<Vec<u8>> {
+                unreachable!()
+            }
+            fn write(&self, _path: &Path, _contents: &[u8]) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let fs = Arc::new(WriteOnly);
+        assert!(fs.create_dir_all(Path::new("icons")).is_ok());
+        assert_eq!(
+            fs.read_dir(Path::new("icons")).unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! A tiny bitmap font for rendering labels and badges.
+
+use image::{Rgba, RgbaImage};
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// The rows of a glyph, from top to bottom. The three lowest bits of each row
+/// are the pixels, with the most significant bit being the leftmost pixel.
+/// Digits, upper case letters and a few symbols are supported.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        'x' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
+
+/// The width of the rendered text in pixels.
+pub(crate) fn text_width(text: &str, scale: u32) -> u32 {
+    let count = text.chars().count() as u32;
+    (count * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// The height of the rendered text in pixels.
+pub(crate) fn text_height(scale: u32) -> u32 {
+    GLYPH_HEIGHT * scale
+}
+
+/// Draws `text` with its top left corner at `(x, y)`.
+/// Pixels outside of the image are skipped.
+pub(crate) fn draw_text(
+    image: &mut RgbaImage,
+    text: &str,
+    (x, y): (i64, i64),
+    scale: u32,
+    color: Rgba<u8>,
+) {
+    let scale = i64::from(scale);
+    for (index, c) in text.chars().enumerate() {
+        let glyph_x = x + index as i64 * i64::from(GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let pixel_x = glyph_x + i64::from(column) * scale;
+                let pixel_y = y + row as i64 * scale;
+                fill_rect(image, (pixel_x, pixel_y), scale, color);
+            }
+        }
+    }
+}
+
+fn fill_rect(image: &mut RgbaImage, (x, y): (i64, i64), size: i64, color: Rgba<u8>) {
+    for py in y..y + size {
+        for px in x..x + size {
+            if let (Ok(px), Ok(py)) = (u32::try_from(px), u32::try_from(py)) {
+                if let Some(pixel) = image.get_pixel_mut_checked(px, py) {
+                    *pixel = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([0xff, 0xff, 0xff, 0xff]);
+
+    /// The rows of `image`, with `#` for drawn pixels.
+    fn rows(image: &RgbaImage) -> Vec<String> {
+        image
+            .rows()
+            .map(|row| {
+                row.map(|pixel| if *pixel == WHITE { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_text() {
+        assert_eq!(text_width("", 2), 0);
+        assert_eq!(text_width("A", 1), 3);
+        assert_eq!(text_width("AB", 2), 14);
+        assert_eq!(text_height(3), 15);
+    }
+
+    #[test]
+    fn draws_glyphs_with_spacing() {
+        let mut image = RgbaImage::new(7, 5);
+        draw_text(&mut image, "1L", (0, 0), 1, WHITE);
+        assert_eq!(
+            rows(&image),
+            [".#..#..", "##..#..", ".#..#..", ".#..#..", "###.###"]
+        );
+    }
+
+    #[test]
+    fn clips_scaled_glyphs() {
+        let mut image = RgbaImage::new(4, 4);
+        draw_text(&mut image, "-", (-2, -2), 2, WHITE);
+        assert_eq!(rows(&image), ["....", "....", "####", "####"]);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::png::{has_chunk, insert_chunks, PNG_SIGNATURE};
+use crate::report::json_string;
+use crate::{BuildReport, DecodedSource, Diagnostic, Error, Result, Source};
+use image::codecs::ico::IcoFrame;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, ImageFormat, RgbaImage};
+use std::env;
+use std::io::Cursor;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A square frame of an ICO file, encoded as a PNG or BMP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub(crate) size: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Frame {
+    /// The width and height of the frame in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The encoded image data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the frame and returns the encoded image data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Validates pre-encoded frame data, which must be a PNG or a BMP without file header
+    /// (as stored in ICO files).
+    pub(crate) fn from_encoded(data: &[u8], width: u32, height: u32) -> Result<Frame> {
+        if width != height {
+            return Err(Error::NonSquareImage {
+                path: Default::default(),
+                width,
+                height,
+            });
+        }
+        if !(1..=256).contains(&width) || bit_count(data).is_none() {
+            return Err(Error::InvalidEncodedFrame { size: width });
+        }
+        Ok(Frame {
+            size: width,
+            data: data.to_owned(),
+        })
+    }
+
+    /// Converts the frame for use with the [`image`] crate's [`IcoEncoder`](image::codecs::ico::IcoEncoder).
+    pub fn to_ico_frame(&self) -> Result<IcoFrame<'static>> {
+        create_ico_frame(self)
+    }
+}
+
+/// Color space information embedded in PNG frames, so that color-managed consumers
+/// render the icon like other assets of the brand.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub enum PngColorSpace {
+    /// No color space information. Most consumers assume sRGB.
+    #[default]
+    Unspecified,
+    /// An `sRGB` chunk with perceptual rendering intent and,
+    /// for decoders that don't support it, the matching `gAMA` chunk.
+    Srgb,
+    /// A `gAMA` chunk with the given encoding gamma, e.g. `1.0 / 2.2`.
+    Gamma(f32),
+}
+
+impl PngColorSpace {
+    /// Adds the chunks to a PNG frame, unless it already has color space information.
+    pub(crate) fn apply(self, frame: Frame) -> Frame {
+        /// The gamma of sRGB, scaled by 100000 as stored in `gAMA` chunks.
+        const SRGB_GAMMA: u32 = 45455;
+        const PERCEPTUAL_INTENT: u8 = 0;
+
+        let has_color_space = [b"sRGB", b"gAMA", b"iCCP"]
+            .iter()
+            .any(|chunk_type| has_chunk(&frame.data, chunk_type));
+        if has_color_space {
+            return frame;
+        }
+        let gamma = |gamma: u32| gamma.to_be_bytes();
+        let data = match self {
+            PngColorSpace::Unspecified => return frame,
+            PngColorSpace::Srgb => insert_chunks(
+                frame.data,
+                &[
+                    (b"sRGB", &[PERCEPTUAL_INTENT]),
+                    (b"gAMA", &gamma(SRGB_GAMMA)),
+                ],
+            ),
+            PngColorSpace::Gamma(value) => insert_chunks(
+                frame.data,
+                &[(b"gAMA", &gamma((value * 100_000.0).round() as u32))],
+            ),
+        };
+        Frame {
+            size: frame.size,
+            data,
+        }
+    }
+}
+
+/// A provenance record embedded in the largest PNG frame as an `iTXt` chunk, so that shipped icons
+/// can be traced back to the exact sources they were built from. The record is JSON with the
+/// version of ico-builder and the path of each source with a hash of the data it was decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Provenance {
+    /// No provenance record.
+    #[default]
+    Omitted,
+    /// A record without the build time, so that builds stay reproducible.
+    Sources,
+    /// A record with the build time in seconds since the Unix epoch,
+    /// taken from `SOURCE_DATE_EPOCH` if it is set.
+    SourcesWithBuildTime,
+}
+
+impl Provenance {
+    /// The keyword of the `iTXt` chunk.
+    const KEYWORD: &'static str = "ico-builder provenance";
+
+    /// Embeds the record into the largest PNG frame, with the hashes of the decoded sources.
+    pub(crate) fn apply(self, sources: &[Source], decoded: &[DecodedSource], frames: &mut [Frame]) {
+        if self == Provenance::Omitted {
+            return;
+        }
+        let Some(frame) = frames
+            .iter_mut()
+            .filter(|frame| frame.data.starts_with(PNG_SIGNATURE))
+            .max_by_key(|frame| frame.size)
+        else {
+            return;
+        };
+
+        let sources: Vec<_> = sources
+            .iter()
+            .zip(decoded)
+            .map(|(source, decoded)| {
+                let path = match source.as_file() {
+                    Some(path) => json_string(&path.to_string_lossy()),
+                    None => "null".to_owned(),
+                };
+                format!(
+                    "{{\"path\": {path}, \"hash\": \"fnv1a64:{:016x}\"}}",
+                    decoded.hash
+                )
+            })
+            .collect();
+        let mut record = format!(
+            "{{\"tool\": \"ico-builder {}\", \"sources\": [{}]",
+            env!("CARGO_PKG_VERSION"),
+            sources.join(", ")
+        );
+        if self == Provenance::SourcesWithBuildTime {
+            let build_time = match env::var("SOURCE_DATE_EPOCH") {
+                Ok(epoch) => epoch.trim().parse().ok(),
+                Err(_) => None,
+            }
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs())
+            });
+            record.push_str(&format!(", \"build_time\": {build_time}"));
+        }
+        record.push('}');
+
+        // Uncompressed, without language tag and translated keyword.
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(Self::KEYWORD.as_bytes());
+        chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+        chunk.extend_from_slice(record.as_bytes());
+        frame.data = insert_chunks(std::mem::take(&mut frame.data), &[(b"iTXt", &chunk)]);
+    }
+}
+
+/// Windows Explorer only renders 256px entries correctly if they are 32-bit RGBA PNGs.
+/// Other PNGs are converted, anything else is rejected.
+pub(crate) fn ensure_rgba_png(frame: Frame, report: &mut BuildReport) -> Result<Frame> {
+    if frame.size < 256 {
+        return Ok(frame);
+    }
+    match png_color_type(&frame.data) {
+        Some(PNG_RGBA8) => Ok(frame),
+        Some(_) => {
+            let converted = image::load_from_memory_with_format(&frame.data, ImageFormat::Png)?;
+            report
+                .diagnostics
+                .push(Diagnostic::ConvertedLargeFrame { size: frame.size });
+            Ok(Frame {
+                size: frame.size,
+                data: encode_png(&converted.to_rgba8())?,
+            })
+        }
+        None => Err(Error::InvalidLargeFrame { size: frame.size }),
+    }
+}
+
+/// Bit depth 8 and color type 6 (RGBA) as stored in the IHDR chunk.
+const PNG_RGBA8: (u8, u8) = (8, 6);
+
+/// The bit depth and color type of a PNG, or `None` if the data is not a PNG.
+fn png_color_type(data: &[u8]) -> Option<(u8, u8)> {
+    // The IHDR chunk always comes first: length, type, width, height, bit depth, color type.
+    let ihdr = data.strip_prefix(PNG_SIGNATURE)?.get(..18)?;
+    (&ihdr[4..8] == b"IHDR").then_some((ihdr[16], ihdr[17]))
+}
+
+/// A BMP in an ICO file starts with a `BITMAPINFOHEADER`, which starts with its own size.
+const BITMAPINFOHEADER_SIZE: u32 = 40;
+
+/// The bits per pixel of a PNG or BMP frame.
+pub(crate) fn bit_count(data: &[u8]) -> Option<u16> {
+    if let Some((bit_depth, color_type)) = png_color_type(data) {
+        let channels = match color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            6 => 4,
+            _ => return None,
+        };
+        Some(u16::from(bit_depth) * channels)
+    } else {
+        let header_size = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+        let bit_count = u16::from_le_bytes(data.get(14..16)?.try_into().ok()?);
+        (header_size == BITMAPINFOHEADER_SIZE).then_some(bit_count)
+    }
+}
+
+pub(crate) fn create_ico_frames(frames: &[Frame]) -> Result<Vec<IcoFrame<'static>>> {
+    frames.iter().map(create_ico_frame).collect()
+}
+
+fn create_ico_frame(frame: &Frame) -> Result<IcoFrame<'static>> {
+    // The encoder derives the bit count of the directory entry from the color type.
+    let color_type = match bit_count(&frame.data) {
+        Some(1) => ExtendedColorType::L1,
+        Some(2) => ExtendedColorType::L2,
+        Some(4) => ExtendedColorType::L4,
+        Some(8) => ExtendedColorType::L8,
+        Some(24) => ExtendedColorType::Rgb8,
+        _ => ExtendedColorType::Rgba8,
+    };
+    Ok(IcoFrame::with_encoded(
+        frame.data.clone(),
+        frame.size,
+        frame.size,
+        color_type,
+    )?)
+}
+
+pub(crate) fn encode_png(frame: &RgbaImage) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    PngEncoder::new(Cursor::new(&mut encoded)).write_image(
+        frame.as_raw(),
+        frame.width(),
+        frame.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn png_frame(size: u32) -> Frame {
+        Frame {
+            size,
+            data: encode_png(&RgbaImage::from_pixel(size, size, Rgba([9, 8, 7, 255]))).unwrap(),
+        }
+    }
+
+    /// A grayscale PNG, whose color type is not RGBA.
+    fn grayscale_png(size: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        let pixels = vec![0x80; (size * size) as usize];
+        PngEncoder::new(&mut data)
+            .write_image(&pixels, size, size, ExtendedColorType::L8)
+            .unwrap();
+        data
+    }
+
+    /// The chunk types of a PNG, in order.
+    fn chunk_types(data: &[u8]) -> Vec<[u8; 4]> {
+        let mut rest = data.strip_prefix(PNG_SIGNATURE).unwrap();
+        let mut types = Vec::new();
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            types.push(rest[4..8].try_into().unwrap());
+            rest = &rest[length + 12..];
+        }
+        types
+    }
+
+    #[test]
+    fn embeds_srgb_and_gamma_chunks() {
+        let frame = PngColorSpace::Srgb.apply(png_frame(16));
+        assert_eq!(
+            chunk_types(&frame.data)[..3],
+            [*b"IHDR", *b"sRGB", *b"gAMA"]
+        );
+        let decoded = image::load_from_memory(&frame.data).unwrap().to_rgba8();
+        assert_eq!(decoded, RgbaImage::from_pixel(16, 16, Rgba([9, 8, 7, 255])));
+
+        // Frames that already have color space information are left as they are.
+        assert_eq!(PngColorSpace::Gamma(0.5).apply(frame.clone()), frame);
+    }
+
+    #[test]
+    fn embeds_gamma_chunks() {
+        let frame = PngColorSpace::Gamma(1.0 / 2.2).apply(png_frame(16));
+        assert_eq!(chunk_types(&frame.data)[..2], [*b"IHDR", *b"gAMA"]);
+        let gamma = frame
+            .data
+            .windows(4)
+            .position(|window| window == b"gAMA")
+            .unwrap();
+        assert_eq!(&frame.data[gamma + 4..gamma + 8], &45455u32.to_be_bytes());
+
+        assert_eq!(
+            PngColorSpace::Unspecified.apply(png_frame(16)),
+            png_frame(16)
+        );
+        let bmp = Frame {
+            size: 16,
+            data: crate::bmp::encode_bmp(&RgbaImage::new(16, 16), 32),
+        };
+        assert_eq!(PngColorSpace::Srgb.apply(bmp.clone()), bmp);
+    }
+
+    #[test]
+    fn reads_bit_counts() {
+        assert_eq!(bit_count(&png_frame(16).data), Some(32));
+        assert_eq!(bit_count(&grayscale_png(16)), Some(8));
+        for bits in [1, 4, 8, 24, 32] {
+            let bmp = crate::bmp::encode_bmp(&RgbaImage::new(16, 16), bits);
+            assert_eq!(bit_count(&bmp), Some(bits));
+        }
+        assert_eq!(bit_count(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn converts_large_frames_to_rgba_pngs() {
+        let mut report = BuildReport::default();
+        let grayscale = Frame {
+            size: 256,
+            data: grayscale_png(256),
+        };
+        assert_ne!(png_color_type(&grayscale.data), Some(PNG_RGBA8));
+        let converted = ensure_rgba_png(grayscale, &mut report).unwrap();
+        assert_eq!(png_color_type(&converted.data), Some(PNG_RGBA8));
+        assert_eq!(
+            report.diagnostics,
+            [Diagnostic::ConvertedLargeFrame { size: 256 }]
+        );
+
+        let bmp = Frame {
+            size: 256,
+            data: crate::bmp::encode_bmp(&RgbaImage::new(256, 256), 32),
+        };
+        assert!(matches!(
+            ensure_rgba_png(bmp, &mut report),
+            Err(Error::InvalidLargeFrame { size: 256 })
+        ));
+    }
+}
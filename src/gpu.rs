@@ -0,0 +1,306 @@
+//! Resizing frames on the GPU with wgpu, for batch builds of hundreds of icons or of very large
+//! sources, where downscaling on the GPU and reading the frames back is faster than filtering
+//! on the CPU.
+
+use crate::{Error, Result};
+use image::imageops::{resize, FilterType};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use std::fmt;
+use std::sync::{mpsc, Arc};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferDescriptor, BufferUsages,
+    CommandEncoderDescriptor, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor,
+    Device, DeviceDescriptor, Instance, MapMode, PipelineLayoutDescriptor, PollType,
+    PowerPreference, Queue, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages,
+};
+
+/// The width and height of the workgroups of the shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// A GPU device that resizes the frames of [`IcoBuilder`](crate::IcoBuilder)s,
+/// see [`IcoBuilder::gpu_resizer`](crate::IcoBuilder::gpu_resizer).
+/// The device is shared between clones, so that all icons of a batch are resized on it
+/// without initializing it again.
+///
+/// The frames match those resized on the CPU up to rounding, with every [`FilterType`].
+/// Sources that exceed the buffer limits of the device are resized on the CPU.
+///
+/// ```no_run
+/// # use ico_builder::{BatchBuilder, IcoBuilder};
+/// # use ico_builder::gpu::GpuResizer;
+/// let mut template = IcoBuilder::default();
+/// template.gpu_resizer(&GpuResizer::new()?);
+/// BatchBuilder::new(template).build("toolbar", "icons")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct GpuResizer {
+    context: Arc<Context>,
+}
+
+struct Context {
+    adapter_name: String,
+    device: Device,
+    queue: Queue,
+    layout: BindGroupLayout,
+    vertical: ComputePipeline,
+    horizontal: ComputePipeline,
+}
+
+impl GpuResizer {
+    /// Initializes the most powerful GPU of the system.
+    /// Fails with [`Error::Gpu`] if there is no GPU that supports compute shaders.
+    pub fn new() -> Result<GpuResizer> {
+        let instance = Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .map_err(|error| Error::Gpu(error.to_string()))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&DeviceDescriptor {
+            label: Some("ico-builder"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .map_err(|error| Error::Gpu(error.to_string()))?;
+
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    ..storage(0, true)
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[Some(&layout)],
+            ..Default::default()
+        });
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("resize"),
+            source: ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let (vertical, horizontal) = (pipeline("vertical"), pipeline("horizontal"));
+
+        Ok(GpuResizer {
+            context: Arc::new(Context {
+                adapter_name: adapter.get_info().name,
+                device,
+                queue,
+                layout,
+                vertical,
+                horizontal,
+            }),
+        })
+    }
+
+    /// The name of the GPU, which identifies its frames in the [cache](crate::IcoBuilder::cache_dir).
+    pub(crate) fn adapter_name(&self) -> &str {
+        &self.context.adapter_name
+    }
+
+    /// Resizes `image` like [`image::imageops::resize`].
+    pub fn resize(
+        &self,
+        image: &DynamicImage,
+        width: u32,
+        height: u32,
+        filter: FilterType,
+    ) -> Result<RgbaImage> {
+        let (src_width, src_height) = image.dimensions();
+        if (width, height) == (src_width, src_height) {
+            return Ok(image.to_rgba8());
+        }
+        let Context {
+            device,
+            queue,
+            layout,
+            vertical,
+            horizontal,
+            ..
+        } = &*self.context;
+        let source_size = 4 * u64::from(src_width) * u64::from(src_height);
+        let columns_size = 16 * u64::from(src_width) * u64::from(height);
+        let output_size = 4 * u64::from(width) * u64::from(height);
+        let limits = device.limits();
+        let max_size = limits
+            .max_storage_buffer_binding_size
+            .min(limits.max_buffer_size);
+        let max_workgroups = u64::from(limits.max_compute_workgroups_per_dimension);
+        let max_workgroups = max_workgroups * u64::from(WORKGROUP_SIZE);
+        if source_size.max(columns_size) > max_size
+            || u64::from(src_width.max(width).max(height)) > max_workgroups
+        {
+            return Ok(resize(image, width, height, filter));
+        }
+
+        let filter_index: u32 = match filter {
+            FilterType::Nearest => 0,
+            FilterType::Triangle => 1,
+            FilterType::CatmullRom => 2,
+            FilterType::Gaussian => 3,
+            FilterType::Lanczos3 => 4,
+        };
+        let params: Vec<u8> = [src_width, src_height, width, height, filter_index, 0, 0, 0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("params"),
+            contents: &params,
+            usage: BufferUsages::UNIFORM,
+        });
+        let source = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("source"),
+            contents: image.to_rgba8().as_raw(),
+            usage: BufferUsages::STORAGE,
+        });
+        let buffer = |label, size, usage| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let columns = buffer("columns", columns_size, BufferUsages::STORAGE);
+        let output = buffer(
+            "output",
+            output_size,
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        );
+        let readback = buffer(
+            "readback",
+            output_size,
+            BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        );
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[&params, &source, &columns, &output]
+                .iter()
+                .enumerate()
+                .map(|(binding, buffer)| BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.set_pipeline(vertical);
+            pass.dispatch_workgroups(
+                src_width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+            pass.set_pipeline(horizontal);
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, output_size);
+        let submission_index = queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = mpsc::channel();
+        readback.map_async(MapMode::Read, .., move |result| {
+            let _ = sender.send(result);
+        });
+        device
+            .poll(PollType::Wait {
+                submission_index: Some(submission_index),
+                timeout: None,
+            })
+            .map_err(|error| Error::Gpu(error.to_string()))?;
+        receiver
+            .recv()
+            .map_err(|error| Error::Gpu(error.to_string()))?
+            .map_err(|error| Error::Gpu(error.to_string()))?;
+        let pixels = readback
+            .get_mapped_range(..)
+            .map_err(|error| Error::Gpu(error.to_string()))?
+            .to_vec();
+        Ok(RgbaImage::from_raw(width, height, pixels).expect("the output has the frame size"))
+    }
+}
+
+impl fmt::Debug for GpuResizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GpuResizer")
+            .field("adapter", &self.context.adapter_name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_match_the_cpu_up_to_rounding() {
+        // Machines without a GPU adapter, not even a software one, skip this test.
+        let Ok(resizer) = GpuResizer::new() else {
+            return;
+        };
+        let source: DynamicImage = RgbaImage::from_fn(64, 64, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 4) as u8, (x ^ y) as u8 * 4, 0xff])
+        })
+        .into();
+        for filter in [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::Lanczos3,
+        ] {
+            let gpu = resizer.resize(&source, 24, 24, filter).unwrap();
+            let cpu = resize(&source, 24, 24, filter);
+            for (gpu, cpu) in gpu.pixels().zip(cpu.pixels()) {
+                for (gpu, cpu) in gpu.0.iter().zip(cpu.0) {
+                    assert!(gpu.abs_diff(cpu) <= 1, "{filter:?}: {gpu} vs. {cpu}");
+                }
+            }
+        }
+        assert_eq!(
+            resizer
+                .resize(&source, 64, 64, FilterType::Nearest)
+                .unwrap(),
+            source.to_rgba8()
+        );
+    }
+}
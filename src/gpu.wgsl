@@ -0,0 +1,145 @@
+// Separable resampling with the filters of `image::imageops::resize`, which it matches
+// up to rounding: a vertical pass into an `f32` buffer, then a horizontal pass into RGBA8.
+
+struct Params {
+    src_size: vec2<u32>,
+    dst_size: vec2<u32>,
+    filter_type: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> source: array<u32>;
+@group(0) @binding(2) var<storage, read_write> columns: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> output: array<u32>;
+
+const PI: f32 = 3.14159265358979;
+
+fn sinc(t: f32) -> f32 {
+    if t == 0.0 {
+        return 1.0;
+    }
+    let a = t * PI;
+    return sin(a) / a;
+}
+
+fn bc_cubic_spline(x: f32, b: f32, c: f32) -> f32 {
+    let a = abs(x);
+    var k = 0.0;
+    if a < 1.0 {
+        k = (12.0 - 9.0 * b - 6.0 * c) * a * a * a + (-18.0 + 12.0 * b + 6.0 * c) * a * a + (6.0 - 2.0 * b);
+    } else if a < 2.0 {
+        k = (-b - 6.0 * c) * a * a * a + (6.0 * b + 30.0 * c) * a * a + (-12.0 * b - 48.0 * c) * a + (8.0 * b + 24.0 * c);
+    }
+    return k / 6.0;
+}
+
+fn kernel(x: f32) -> f32 {
+    switch params.filter_type {
+        // Nearest
+        case 0u: {
+            return 1.0;
+        }
+        // Triangle
+        case 1u: {
+            return max(1.0 - abs(x), 0.0);
+        }
+        // CatmullRom
+        case 2u: {
+            return bc_cubic_spline(x, 0.0, 0.5);
+        }
+        // Gaussian with a standard deviation of 0.5
+        case 3u: {
+            return exp(-x * x / 0.5) / (sqrt(2.0 * PI) * 0.5);
+        }
+        // Lanczos3
+        default: {
+            if abs(x) < 3.0 {
+                return sinc(x) * sinc(x / 3.0);
+            }
+            return 0.0;
+        }
+    }
+}
+
+fn support() -> f32 {
+    switch params.filter_type {
+        case 0u: {
+            return 0.0;
+        }
+        case 1u: {
+            return 1.0;
+        }
+        case 2u: {
+            return 2.0;
+        }
+        default: {
+            return 3.0;
+        }
+    }
+}
+
+// The range of source pixels that contribute to an output pixel, and their filter positions.
+struct Window {
+    left: u32,
+    right: u32,
+    center: f32,
+    scale: f32,
+}
+
+fn window(out: u32, src_len: u32, dst_len: u32) -> Window {
+    let ratio = f32(src_len) / f32(dst_len);
+    let scale = max(ratio, 1.0);
+    let src_support = support() * scale;
+    let center = (f32(out) + 0.5) * ratio;
+    let left = u32(clamp(i32(floor(center - src_support)), 0, i32(src_len) - 1));
+    let right = u32(clamp(i32(ceil(center + src_support)), i32(left) + 1, i32(src_len)));
+    return Window(left, right, center - 0.5, scale);
+}
+
+fn unpack(pixel: u32) -> vec4<f32> {
+    return vec4<f32>(
+        f32(pixel & 0xffu),
+        f32((pixel >> 8u) & 0xffu),
+        f32((pixel >> 16u) & 0xffu),
+        f32(pixel >> 24u),
+    );
+}
+
+fn pack(color: vec4<f32>) -> u32 {
+    let c = vec4<u32>(floor(clamp(color, vec4(0.0), vec4(255.0)) + 0.5));
+    return c.r | (c.g << 8u) | (c.b << 16u) | (c.a << 24u);
+}
+
+@compute @workgroup_size(8, 8)
+fn vertical(@builtin(global_invocation_id) id: vec3<u32>) {
+    let width = params.src_size.x;
+    if id.x >= width || id.y >= params.dst_size.y {
+        return;
+    }
+    let w = window(id.y, params.src_size.y, params.dst_size.y);
+    var sum = vec4(0.0);
+    var weights = 0.0;
+    for (var y = w.left; y < w.right; y++) {
+        let weight = kernel((f32(y) - w.center) / w.scale);
+        sum += unpack(source[y * width + id.x]) * weight;
+        weights += weight;
+    }
+    columns[id.y * width + id.x] = sum / weights;
+}
+
+@compute @workgroup_size(8, 8)
+fn horizontal(@builtin(global_invocation_id) id: vec3<u32>) {
+    let width = params.src_size.x;
+    if id.x >= params.dst_size.x || id.y >= params.dst_size.y {
+        return;
+    }
+    let w = window(id.x, width, params.dst_size.x);
+    var sum = vec4(0.0);
+    var weights = 0.0;
+    for (var x = w.left; x < w.right; x++) {
+        let weight = kernel((f32(x) - w.center) / w.scale);
+        sum += columns[id.y * width + x] * weight;
+        weights += weight;
+    }
+    output[id.y * params.dst_size.x + id.x] = pack(sum / weights);
+}
@@ -0,0 +1,37 @@
+//! Decoding HEIF sources, e.g. `.heic` files exported by macOS and iOS design tools, with libheif.
+
+use crate::{FeatureFormat, Result};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, RgbaImage};
+use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+
+/// Decodes the primary image of a HEIF file, applying its rotation and cropping.
+/// Returns `None` if `data` is not a HEIF file.
+pub(crate) fn decode(data: &[u8]) -> Option<Result<RgbaImage>> {
+    if FeatureFormat::from_signature(data) != Some(FeatureFormat::Heif) {
+        return None;
+    }
+    Some(decode_primary_image(data).map_err(|error| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("HEIF".into()),
+            error,
+        ))
+        .into()
+    }))
+}
+
+fn decode_primary_image(data: &[u8]) -> std::result::Result<RgbaImage, HeifError> {
+    let context = HeifContext::read_from_bytes(data)?;
+    let handle = context.primary_image_handle()?;
+    let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .expect("RGBA images have an interleaved plane");
+    let row_size = plane.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_size * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_size]);
+    }
+    Ok(RgbaImage::from_raw(plane.width, plane.height, pixels).expect("rows have the plane width"))
+}
@@ -0,0 +1,53 @@
+//! Callbacks that run at fixed points of the pipeline.
+
+use image::RgbaImage;
+use std::fmt;
+use std::sync::Arc;
+
+type Hook = dyn Fn(&mut RgbaImage, u32) + Send + Sync;
+
+/// The hooks registered for one point of the pipeline, in the order they were added.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<Arc<Hook>>);
+
+impl Hooks {
+    pub(crate) fn push(&mut self, hook: impl Fn(&mut RgbaImage, u32) + Send + Sync + 'static) {
+        self.0.push(Arc::new(hook));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn run(&self, frame: &mut RgbaImage) {
+        let size = frame.width();
+        for hook in &self.0 {
+            hook(frame, size);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hooks", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn hooks_run_in_order_with_the_frame_size() {
+        let mut hooks = Hooks::default();
+        assert!(hooks.is_empty());
+        hooks.push(|frame, size| frame.put_pixel(0, 0, Rgba([size as u8, 0, 0, 0xff])));
+        hooks.push(|frame, _| frame.get_pixel_mut(0, 0)[1] = frame.get_pixel(0, 0)[0] * 2);
+        assert_eq!(format!("{hooks:?}"), "2 hooks");
+
+        let mut frame = RgbaImage::new(24, 24);
+        hooks.run(&mut frame);
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([24, 48, 0, 0xff]));
+    }
+}
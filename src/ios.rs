@@ -0,0 +1,181 @@
+//! App icons for iOS and iPadOS apps.
+
+use crate::export::write_png;
+use crate::transform::{Background, Transform};
+use crate::{IcoBuilder, Result};
+use image::Rgba;
+use std::path::{Path, PathBuf};
+
+/// App icons as `(idiom, size in points, scale, size in pixels)`.
+const ICONS: &[(&str, &str, u32, u32)] = &[
+    ("iphone", "20", 2, 40),
+    ("iphone", "20", 3, 60),
+    ("iphone", "29", 2, 58),
+    ("iphone", "29", 3, 87),
+    ("iphone", "40", 2, 80),
+    ("iphone", "40", 3, 120),
+    ("iphone", "60", 2, 120),
+    ("iphone", "60", 3, 180),
+    ("ipad", "20", 1, 20),
+    ("ipad", "20", 2, 40),
+    ("ipad", "29", 1, 29),
+    ("ipad", "29", 2, 58),
+    ("ipad", "40", 1, 40),
+    ("ipad", "40", 2, 80),
+    ("ipad", "76", 1, 76),
+    ("ipad", "76", 2, 152),
+    ("ipad", "83.5", 2, 167),
+    ("ios-marketing", "1024", 1, 1024),
+];
+
+/// Exports an `AppIcon.appiconset` for an asset catalog from the sources of an [`IcoBuilder`],
+/// with all iPhone, iPad and App Store sizes and a `Contents.json` describing them.
+///
+/// App icons must not be transparent, so the artwork is placed on an opaque
+/// [background](IosExport::background).
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::ios::IosExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-1024x1024.png");
+/// IosExport::default().build(&icon, "App/Assets.xcassets")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct IosExport {
+    name: String,
+    background: Rgba<u8>,
+}
+
+impl Default for IosExport {
+    fn default() -> Self {
+        IosExport {
+            name: "AppIcon".to_owned(),
+            background: Rgba([0xff, 0xff, 0xff, 0xff]),
+        }
+    }
+}
+
+impl IosExport {
+    /// Customizes the name of the icon set, which is referenced in the
+    /// build settings of the Xcode project. Defaults to `AppIcon`.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut IosExport {
+        self.name = name.into();
+        self
+    }
+
+    /// Customizes the color behind transparent parts of the artwork. The alpha of the
+    /// color is ignored. Defaults to white.
+    pub fn background(&mut self, background: Rgba<u8>) -> &mut IosExport {
+        self.background = Rgba([background[0], background[1], background[2], 0xff]);
+        self
+    }
+
+    /// Writes the icon set into the asset catalog at `xcassets_dir`
+    /// and returns the paths of the written files.
+    pub fn build(&self, icon: &IcoBuilder, xcassets_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dir = xcassets_dir
+            .as_ref()
+            .join(format!("{}.appiconset", self.name));
+        icon.create_dir_all(&dir)?;
+        let mut written = Vec::new();
+
+        let background = Background::solid(self.background);
+        let sizes: Vec<_> = ICONS.iter().map(|&(.., size)| size).collect();
+        for (mut frame, &(_, points, scale, _)) in icon.render(&sizes)?.into_iter().zip(ICONS) {
+            let name = file_name(points, scale);
+            // Both iPhone and iPad use some of the files.
+            if written.contains(&dir.join(&name)) {
+                continue;
+            }
+            background.apply(&mut frame);
+            written.push(write_png(icon, &dir, &name, &frame)?);
+        }
+
+        let contents_path = dir.join("Contents.json");
+        icon.write_file(&contents_path, contents_json().as_bytes())?;
+        written.push(contents_path);
+
+        Ok(written)
+    }
+}
+
+fn file_name(points: &str, scale: u32) -> String {
+    format!("Icon-{points}@{scale}x.png")
+}
+
+fn contents_json() -> String {
+    let images: Vec<_> = ICONS
+        .iter()
+        .map(|&(idiom, points, scale, _)| {
+            format!(
+                "    {{\n      \
+                   \"filename\" : \"{file_name}\",\n      \
+                   \"idiom\" : \"{idiom}\",\n      \
+                   \"scale\" : \"{scale}x\",\n      \
+                   \"size\" : \"{points}x{points}\"\n    \
+                 }}",
+                file_name = file_name(points, scale)
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \
+           \"images\" : [\n{images}\n  ],\n  \
+           \"info\" : {{\n    \
+             \"author\" : \"ico-builder\",\n    \
+             \"version\" : 1\n  \
+           }}\n\
+         }}\n",
+        images = images.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::RgbaImage;
+    use std::sync::Arc;
+
+    #[test]
+    fn writes_the_files_of_contents_json() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .allow_upscaling(true)
+            .add_source_image(RgbaImage::new(64, 64));
+        let written = IosExport::default()
+            .background(Rgba([10, 20, 30, 0]))
+            .build(&icon, "Assets.xcassets")
+            .unwrap();
+        let dir = Path::new("Assets.xcassets").join("AppIcon.appiconset");
+        assert_eq!(written.last(), Some(&dir.join("Contents.json")));
+        // Shared files are written once.
+        let mut names: Vec<_> = ICONS
+            .iter()
+            .map(|&(_, points, scale, _)| file_name(points, scale))
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(written.len(), names.len() + 1);
+        assert!(names.len() < ICONS.len());
+
+        let contents = String::from_utf8(fs.read(&dir.join("Contents.json")).unwrap()).unwrap();
+        for &(_, points, scale, size) in ICONS {
+            let name = file_name(points, scale);
+            assert!(
+                contents.contains(&format!("\"filename\" : \"{name}\"")),
+                "{name}"
+            );
+            let frame = image::load_from_memory(&fs.read(&dir.join(&name)).unwrap())
+                .unwrap()
+                .into_rgba8();
+            assert_eq!(frame.width(), size, "{name}");
+            assert!(frame
+                .pixels()
+                .all(|pixel| *pixel == Rgba([10, 20, 30, 255])));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Decoding JPEG XL sources, e.g. `.jxl` masters exported by modern asset pipelines, with jxl-oxide.
+
+use crate::{FeatureFormat, Result};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageBuffer, ImageError};
+use jxl_oxide::{JxlImage, PixelFormat};
+
+/// Decodes the first frame of a JPEG XL file with 16 bits per channel, applying its orientation.
+/// Returns `None` if `data` is not a JPEG XL file.
+pub(crate) fn decode(data: &[u8]) -> Option<Result<DynamicImage>> {
+    if FeatureFormat::from_signature(data) != Some(FeatureFormat::JpegXl) {
+        return None;
+    }
+    Some(decode_first_frame(data).map_err(|error| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("JPEG XL".into()),
+            error,
+        ))
+        .into()
+    }))
+}
+
+fn decode_first_frame(
+    data: &[u8],
+) -> std::result::Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    let image = JxlImage::read_with_defaults(data)?;
+    let pixel_format = image.pixel_format();
+    if pixel_format.has_black() {
+        return Err("CMYK images are not supported".into());
+    }
+    let render = image.render_frame(0)?;
+    let mut stream = render.stream();
+    let (width, height) = (stream.width(), stream.height());
+    let mut pixels = vec![0u16; width as usize * height as usize * stream.channels() as usize];
+    stream.write_to_buffer(&mut pixels);
+
+    let image = match pixel_format {
+        PixelFormat::Gray => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma16)
+        }
+        PixelFormat::Graya => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA16)
+        }
+        PixelFormat::Rgb => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb16)
+        }
+        _ => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba16),
+    };
+    Ok(image.expect("the stream has the channels of the pixel format"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn only_jpeg_xl_files_are_decoded() {
+        assert!(decode(b"\x89PNG\r\n\x1a\n").is_none());
+        let error = decode(&[0xff, 0x0a, 0x00]).unwrap().unwrap_err();
+        assert!(matches!(error, Error::Image(ImageError::Decoding(_))));
+        assert!(error.to_string().contains("JPEG XL"), "{error}");
+    }
+}
@@ -31,45 +31,199 @@
 #[doc = include_str!("../readme.md")]
 mod test_readme {}
 
-use image::codecs::ico::{IcoEncoder, IcoFrame};
-use image::codecs::png::PngEncoder;
-use image::imageops::resize;
-use image::io::Reader as ImageReader;
-use image::{DynamicImage, ExtendedColorType, ImageEncoder};
-use std::borrow::Cow;
+use env_overrides::EnvOverrides;
+use filesystem::{Fs, StdFs};
+use hash::Fnv1a;
+use hooks::Hooks;
+use image::codecs::ico::IcoEncoder;
+use image::error::{ImageFormatHint, UnsupportedErrorKind};
+use image::imageops::{self, resize};
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader, RgbaImage};
+use png::strip_metadata_chunks;
+use reader::{IcoEntry, IcoFile};
 use std::ffi::OsStr;
-use std::fs::OpenOptions;
-use std::io::Cursor;
-use std::ops::Deref;
+use std::io::{Cursor, Write};
+#[cfg(feature = "watch")]
+use std::ops::ControlFlow;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use std::{env, iter};
+use std::sync::Arc;
+use std::{env, iter, panic, thread};
+use transform::Transform;
 
+mod app_icon;
+mod artifacts;
+mod batch;
+mod bmp;
+mod cache;
+mod contact_sheet;
+mod env_overrides;
 mod error;
+mod estimate;
+mod export;
+mod feature_format;
+mod filter;
+mod font;
+mod frame;
+mod hash;
+#[cfg(feature = "heif")]
+mod heif;
+mod hooks;
+#[cfg(feature = "jxl")]
+mod jxl;
+mod locales;
+mod manifest;
+mod png;
+mod profiles;
+mod quality;
+mod report;
+mod sizes;
+mod verify;
+#[cfg(feature = "watch")]
+mod watch;
+pub use app_icon::*;
+pub use artifacts::*;
+pub use batch::*;
 pub use error::*;
+pub use estimate::*;
+pub use feature_format::*;
+pub use filter::*;
+pub use frame::*;
+pub use locales::*;
+pub use profiles::*;
+pub use report::*;
+pub use sizes::*;
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub use image::imageops::FilterType;
+pub use image::{Rgb, Rgba};
+
+pub mod android;
+pub mod document;
+#[cfg(feature = "axum")]
+pub mod favicon;
+pub mod filesystem;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod ios;
+pub mod linux;
+pub mod macos;
+pub mod modernize;
+pub mod msix;
+pub mod overlay;
+pub mod placeholder;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod reader;
+pub mod regression;
+pub mod repair;
+pub mod resource;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transform;
+pub mod tray;
+pub mod web;
 
 /// Builds an ICO file from individual files.
 /// For each size, the closest source image is scaled down to the appropriate size.
-#[derive(Debug)]
+///
+/// The builder is `Send + Sync` and cheap to clone, since images, transforms and hooks
+/// are shared between clones. A configured builder can be shared across threads,
+/// e.g. by a server that builds icons for each tenant; see [`IcoBuilder::decode_sources`].
+#[derive(Debug, Clone)]
 pub struct IcoBuilder {
     sizes: IconSizes,
-    source_files: Vec<PathBuf>,
+    sources: Vec<Source>,
     filter_type: FilterType,
+    #[cfg(feature = "gpu")]
+    gpu_resizer: Option<gpu::GpuResizer>,
+    transforms: Vec<Arc<dyn Transform>>,
+    fs: Arc<dyn Fs>,
+    drop_duplicate_frames: bool,
+    quality_report: bool,
+    collect_all_errors: bool,
+    best_effort: bool,
+    cache_dir: Option<PathBuf>,
+    entry_order: EntryOrder,
+    entry_overrides: Vec<EntryOverrides>,
+    max_downscale_ratio: f32,
+    allow_upscaling: bool,
+    selection_strategy: SelectionStrategy,
+    verify_tolerance: u8,
+    xp_compatible: bool,
+    png_file_name: String,
+    strip_png_metadata: bool,
+    png_color_space: PngColorSpace,
+    provenance: Provenance,
+    indexed_png: bool,
+    color_vision_previews: bool,
+    links_metadata_key: Option<String>,
+    after_resize: Hooks,
+    before_encode: Hooks,
+    encoded_frames: Vec<(Arc<[u8]>, u32, u32)>,
+    /// The decoded sources, shared between the builds of [`Artifacts`].
+    /// Reset whenever the sources could change.
+    decoded_sources: Option<Arc<[DecodedSource]>>,
 }
 
 impl Default for IcoBuilder {
     fn default() -> Self {
         IcoBuilder {
             sizes: Default::default(),
-            source_files: Default::default(),
+            sources: Default::default(),
             filter_type: FilterType::Lanczos3,
+            #[cfg(feature = "gpu")]
+            gpu_resizer: None,
+            transforms: Default::default(),
+            fs: Arc::new(StdFs),
+            drop_duplicate_frames: false,
+            quality_report: false,
+            collect_all_errors: false,
+            best_effort: false,
+            cache_dir: None,
+            entry_order: EntryOrder::default(),
+            entry_overrides: Vec::new(),
+            max_downscale_ratio: 10.0,
+            allow_upscaling: false,
+            selection_strategy: SelectionStrategy::default(),
+            verify_tolerance: 4,
+            xp_compatible: false,
+            png_file_name: "{name}-{size}x{size}.png".to_owned(),
+            strip_png_metadata: true,
+            png_color_space: PngColorSpace::default(),
+            provenance: Provenance::default(),
+            indexed_png: false,
+            color_vision_previews: false,
+            links_metadata_key: None,
+            after_resize: Hooks::default(),
+            before_encode: Hooks::default(),
+            encoded_frames: Default::default(),
+            decoded_sources: None,
         }
     }
 }
 
 impl IcoBuilder {
+    /// Creates a builder with the given source files. See [`IcoBuilder::add_source_file`].
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// IcoBuilder::from_sources(["app-icon-32x32.png", "app-icon-256x256.png"])
+    ///     .build_file("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn from_sources(source_files: impl IntoIterator<Item = impl AsRef<Path>>) -> IcoBuilder {
+        let mut builder = IcoBuilder::default();
+        builder.add_source_files(source_files);
+        builder
+    }
+
+    /// Creates a builder with the given sizes. See [`IcoBuilder::sizes`].
+    pub fn with_sizes(sizes: impl Into<IconSizes>) -> IcoBuilder {
+        let mut builder = IcoBuilder::default();
+        builder.sizes(sizes);
+        builder
+    }
+
     /// Customizes the sizes included in the ICO file. Defaults to [`IconSizes::MINIMAL`].
     pub fn sizes(&mut self, sizes: impl Into<IconSizes>) -> &mut IcoBuilder {
         self.sizes = sizes.into();
@@ -97,153 +251,1733 @@ impl IcoBuilder {
         &mut self,
         source_files: impl IntoIterator<Item = impl AsRef<Path>>,
     ) -> &mut IcoBuilder {
-        self.source_files
-            .extend(source_files.into_iter().map(|f| f.as_ref().to_owned()));
+        self.sources.extend(
+            source_files
+                .into_iter()
+                .map(|f| Source::File(f.as_ref().to_owned())),
+        );
+        self.decoded_sources = None;
+        self
+    }
+
+    /// Adds an in-memory source image. The image is assumed to be a square.
+    pub fn add_source_image(&mut self, source_image: impl Into<DynamicImage>) -> &mut IcoBuilder {
+        self.sources
+            .push(Source::Image(Arc::new(source_image.into())));
+        self.decoded_sources = None;
+        self
+    }
+
+    /// Adds a source from an encoded image in memory, e.g. an upload.
+    /// The format is detected from the contents, and the image is decoded when building.
+    pub fn add_source_bytes(&mut self, source_bytes: impl Into<Vec<u8>>) -> &mut IcoBuilder {
+        self.sources
+            .push(Source::Encoded(source_bytes.into().into()));
+        self.decoded_sources = None;
+        self
+    }
+
+    /// Adds the images in a directory embedded with [`include_dir::include_dir!`] as sources,
+    /// ordered by path, so that ICO files can be built at runtime without extracting the
+    /// embedded files to disk. Files that aren't images in a known format, and ICO files, are ignored.
+    /// Subdirectories are not included. Requires the `include_dir` feature.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use include_dir::Dir;
+    /// // With `static ICONS: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/icons");`
+    /// fn build_icon(icons: &Dir<'static>) -> ico_builder::Result<()> {
+    ///     IcoBuilder::default()
+    ///         .add_source_dir(icons)
+    ///         .build_file("app-icon.ico")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "include_dir")]
+    pub fn add_source_dir(&mut self, dir: &include_dir::Dir<'_>) -> &mut IcoBuilder {
+        let mut files: Vec<_> = dir
+            .files()
+            .filter(|file| {
+                ImageFormat::from_path(file.path())
+                    .is_ok_and(|format| format != ImageFormat::Ico && format.reading_enabled())
+            })
+            .collect();
+        files.sort_by_key(|file| file.path());
+        for file in files {
+            self.add_source_bytes(file.contents());
+        }
+        self
+    }
+
+    /// Reads a source from an async reader, like an upload streamed by a web server,
+    /// without blocking the thread while waiting for data. See [`IcoBuilder::add_source_bytes`].
+    /// Requires the `async` feature.
+    ///
+    /// Sources aren't fetched from URLs, since that would tie the crate to an HTTP client.
+    /// Stream the body of a response as an [`AsyncRead`](tokio::io::AsyncRead) instead,
+    /// e.g. with `tokio_util::io::StreamReader`.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use tokio::io::AsyncRead;
+    /// async fn icon_from_upload(upload: impl AsyncRead + Unpin) -> ico_builder::Result<()> {
+    ///     IcoBuilder::default()
+    ///         .add_source_reader(upload)
+    ///         .await?
+    ///         .build_file("upload.ico")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn add_source_reader(
+        &mut self,
+        mut reader: impl tokio::io::AsyncRead + Unpin,
+    ) -> Result<&mut IcoBuilder> {
+        use tokio::io::AsyncReadExt as _;
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).await?;
+        Ok(self.add_source_bytes(contents))
+    }
+
+    /// Adds a pre-encoded PNG or BMP frame, which is placed into the output as is
+    /// instead of a generated frame of the same size. This is useful for hand-optimized small sizes.
+    /// BMP data must not include the file header, as is usual for ICO files.
+    pub fn add_encoded_frame(
+        &mut self,
+        data: impl Into<Vec<u8>>,
+        width: u32,
+        height: u32,
+    ) -> &mut IcoBuilder {
+        self.encoded_frames
+            .push((data.into().into(), width, height));
         self
     }
 
     /// Customizes the filter type used when downscaling the images. Defaults to [`FilterType::Lanczos3`].
-    pub fn filter_type(&mut self, filter_type: FilterType) -> &mut IcoBuilder {
-        self.filter_type = filter_type;
+    ///
+    /// ```no_run
+    /// # use ico_builder::{FilterType, IcoBuilder};
+    /// let report = IcoBuilder::from_sources(["app-icon-1024x1024.png"])
+    ///     .filter_type(FilterType::Auto)
+    ///     .build_file("app-icon.ico")?;
+    /// println!("{}", report.explain());
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn filter_type(&mut self, filter_type: impl Into<FilterType>) -> &mut IcoBuilder {
+        self.filter_type = filter_type.into();
+        self
+    }
+
+    /// Resizes the frames on the GPU of `resizer`, see [`GpuResizer`](gpu::GpuResizer).
+    /// Transformations and hooks are still applied on the CPU. Defaults to resizing on the CPU.
+    #[cfg(feature = "gpu")]
+    pub fn gpu_resizer(&mut self, resizer: &gpu::GpuResizer) -> &mut IcoBuilder {
+        self.gpu_resizer = Some(resizer.clone());
+        self
+    }
+
+    /// Adds a transformation that is applied to every frame after resizing.
+    /// Transformations are applied in the order they were added.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use ico_builder::transform::SuperellipseMask;
+    /// IcoBuilder::default()
+    ///     .add_source_file("app-icon-256x256.png")
+    ///     .add_transform(SuperellipseMask::default())
+    ///     .build_file("app-icon.ico");
+    /// ```
+    pub fn add_transform(&mut self, transform: impl Transform + 'static) -> &mut IcoBuilder {
+        self.transforms.push(Arc::new(transform));
         self
     }
 
+    /// Adds the version `badge` as a [transformation](IcoBuilder::add_transform), unless Cargo
+    /// builds with the `release` profile. This lets debug and internal builds of the same sources
+    /// be told apart in build scripts.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use ico_builder::transform::VersionBadge;
+    /// let mut icon = IcoBuilder::default();
+    /// icon.add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"]);
+    /// if let Some(badge) = VersionBadge::package_version() {
+    ///     icon.add_version_badge_cargo(badge);
+    /// }
+    /// icon.build_file_cargo("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn add_version_badge_cargo(&mut self, badge: transform::VersionBadge) -> &mut IcoBuilder {
+        if !env_overrides::is_cargo_release() {
+            self.add_transform(badge);
+        }
+        self
+    }
+
+    /// Customizes the filesystem that sources are read from and outputs are written to.
+    /// Defaults to [`StdFs`].
+    pub fn fs(&mut self, fs: impl Fs + 'static) -> &mut IcoBuilder {
+        self.fs = Arc::new(fs);
+        self.decoded_sources = None;
+        self
+    }
+
+    /// Removes frames that have the same content as a smaller frame, e.g. the 48px and 64px
+    /// frames generated from a flat source, to save bytes. Duplicate frames are always
+    /// reported as a [`Diagnostic::DuplicateFrame`]. Defaults to `false`.
+    pub fn drop_duplicate_frames(&mut self, drop_duplicate_frames: bool) -> &mut IcoBuilder {
+        self.drop_duplicate_frames = drop_duplicate_frames;
+        self
+    }
+
+    /// Measures the [`Quality`] of each frame compared to a reference rendering
+    /// and includes it in the [`BuildReport`]. This lets asset pipelines flag regressions
+    /// caused by changes to the filter or the sources. Defaults to `false`.
+    pub fn quality_report(&mut self, quality_report: bool) -> &mut IcoBuilder {
+        self.quality_report = quality_report;
+        self
+    }
+
+    /// Reports all problems with the sources and sizes at once as an [`Error::Multiple`]
+    /// instead of stopping at the first one. This saves build iterations
+    /// when fixing many broken assets. Defaults to `false`.
+    pub fn collect_all_errors(&mut self, collect_all_errors: bool) -> &mut IcoBuilder {
+        self.collect_all_errors = collect_all_errors;
+        self
+    }
+
+    /// Skips frames that can't be produced, e.g. sizes without a large enough source,
+    /// pre-encoded frames with invalid data, or frames whose transformation panicked,
+    /// and writes the remaining frames instead of failing the build. Each skipped frame
+    /// is reported as a [`Diagnostic::SkippedFrame`]. The build still fails if the sources
+    /// can't be decoded or if none of the frames resized from them can be produced.
+    /// Defaults to `false`.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// // Without a 256px source, the icon is written without its 256px frame.
+    /// let report = IcoBuilder::from_sources(["app-icon-16x16.png", "app-icon-48x48.png"])
+    ///     .best_effort(true)
+    ///     .build_file("app-icon.ico")?;
+    /// for diagnostic in &report.diagnostics {
+    ///     println!("{diagnostic}");
+    /// }
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn best_effort(&mut self, best_effort: bool) -> &mut IcoBuilder {
+        self.best_effort = best_effort;
+        self
+    }
+
+    /// Caches rendered frames in `cache_dir`, keyed by the source, size, filter type,
+    /// transformations and GPU. Rebuilding after changing only one source then doesn't redo the
+    /// frames rendered from the other sources. Disabled by default.
+    ///
+    /// Transformations are identified by their [`Debug`](std::fmt::Debug) representation,
+    /// so custom transformations should include all of their parameters in it.
+    /// Frames aren't cached while [hooks](IcoBuilder::after_resize) are registered.
+    /// The cache is never cleaned up automatically.
+    pub fn cache_dir(&mut self, cache_dir: impl AsRef<Path>) -> &mut IcoBuilder {
+        self.cache_dir = Some(cache_dir.as_ref().to_owned());
+        self
+    }
+
+    /// Customizes the order of the entries in the ICO directory. Defaults to [`EntryOrder::Ascending`].
+    pub fn entry_order(&mut self, entry_order: EntryOrder) -> &mut IcoBuilder {
+        self.entry_order = entry_order;
+        self
+    }
+
+    /// Overrides fields of the ICO directory entries. When called multiple times,
+    /// later overrides take precedence over earlier ones.
+    ///
+    /// ```no_run
+    /// # use ico_builder::{EntryOverrides, IcoBuilder};
+    /// IcoBuilder::default()
+    ///     .add_source_file("app-icon-256x256.png")
+    ///     .entry_overrides(EntryOverrides::all().planes(1))
+    ///     .build_file("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn entry_overrides(&mut self, entry_overrides: EntryOverrides) -> &mut IcoBuilder {
+        self.entry_overrides.push(entry_overrides);
+        self
+    }
+
+    /// Customizes how many times larger than a frame its source may be before a
+    /// [`Diagnostic::ExtremeDownscale`] is reported. Such frames tend to look muddy,
+    /// which is usually fixed by adding a source closer to the frame's size.
+    /// Defaults to `10.0`. Use [`f32::INFINITY`] to disable the diagnostic.
+    pub fn max_downscale_ratio(&mut self, max_downscale_ratio: f32) -> &mut IcoBuilder {
+        self.max_downscale_ratio = max_downscale_ratio;
+        self
+    }
+
+    /// Allows upscaling the largest source for sizes that no source is large enough for,
+    /// instead of failing with [`Error::MissingIconSize`]. Upscaled frames are reported
+    /// as a [`Diagnostic::Upscaled`]. Defaults to `false`.
+    pub fn allow_upscaling(&mut self, allow_upscaling: bool) -> &mut IcoBuilder {
+        self.allow_upscaling = allow_upscaling;
+        self
+    }
+
+    /// Customizes which source each frame is resized from.
+    /// Defaults to [`SelectionStrategy::NextBigger`].
+    ///
+    /// ```no_run
+    /// # use ico_builder::{IcoBuilder, SelectionStrategy};
+    /// // Fail instead of resizing if a size has no hand-drawn source.
+    /// IcoBuilder::default()
+    ///     .add_source_files(["app-icon-16x16.png", "app-icon-32x32.png", "app-icon-48x48.png"])
+    ///     .sizes(&[16, 32, 48])
+    ///     .selection_strategy(SelectionStrategy::ExactOnly)
+    ///     .build_file("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn selection_strategy(&mut self, selection_strategy: SelectionStrategy) -> &mut IcoBuilder {
+        self.selection_strategy = selection_strategy;
+        self
+    }
+
+    /// Customizes how much each color channel of a pixel may differ when [verifying](IcoBuilder::verify_file)
+    /// an ICO file, which allows for small differences between versions of the resizing filters.
+    /// Defaults to `4`.
+    pub fn verify_tolerance(&mut self, verify_tolerance: u8) -> &mut IcoBuilder {
+        self.verify_tolerance = verify_tolerance;
+        self
+    }
+
+    /// Makes the ICO file compatible with Windows XP and older, for products that still target
+    /// legacy systems: all entries are uncompressed BMPs, sizes above 48px are left out,
+    /// and an 8-bit entry is added for each size after the 32-bit entries. Defaults to `false`.
+    ///
+    /// The [report](BuildReport::frames) lists the 8-bit entries separately.
+    pub fn xp_compatible(&mut self, xp_compatible: bool) -> &mut IcoBuilder {
+        self.xp_compatible = xp_compatible;
+        self
+    }
+
+    /// Customizes the names of the files written by [`IcoBuilder::build_png_files`],
+    /// to match the convention of a packaging tool. `{name}` is replaced with the name
+    /// passed to `build_png_files` and `{size}` with the size of the frame.
+    /// Templates may contain directories, e.g. `{size}/{name}.png`.
+    /// Defaults to `{name}-{size}x{size}.png`.
+    pub fn png_file_name(&mut self, template: impl Into<String>) -> &mut IcoBuilder {
+        self.png_file_name = template.into();
+        self
+    }
+
+    /// Removes metadata like texts, timestamps and Exif data from [pre-encoded](IcoBuilder::add_encoded_frame)
+    /// PNG frames. This makes the ICO file smaller and the build independent of the tools that
+    /// created the frames. Generated frames never contain metadata. Defaults to `true`.
+    pub fn strip_png_metadata(&mut self, strip_png_metadata: bool) -> &mut IcoBuilder {
+        self.strip_png_metadata = strip_png_metadata;
+        self
+    }
+
+    /// Embeds color space information in the PNG frames. Frames that already have
+    /// color space information are left as they are. Defaults to [`PngColorSpace::Unspecified`].
+    pub fn png_color_space(&mut self, png_color_space: PngColorSpace) -> &mut IcoBuilder {
+        self.png_color_space = png_color_space;
+        self
+    }
+
+    /// Embeds a provenance record into the largest PNG frame, so that shipped icons can be traced
+    /// back to the exact sources that produced them. Defaults to [`Provenance::Omitted`].
+    /// Provenance is not recorded for [Windows XP compatible](IcoBuilder::xp_compatible) icons,
+    /// which have no PNG frames.
+    pub fn provenance(&mut self, provenance: Provenance) -> &mut IcoBuilder {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Encodes generated frames with at most 256 colors as indexed PNGs, which are often
+    /// much smaller than RGBA PNGs for flat artwork. A frame is only encoded as an indexed PNG
+    /// if that is actually smaller. 256px frames are always RGBA PNGs, since Windows Explorer
+    /// doesn't render them correctly otherwise. Defaults to `false`.
+    pub fn indexed_png(&mut self, indexed_png: bool) -> &mut IcoBuilder {
+        self.indexed_png = indexed_png;
+        self
+    }
+
+    /// Makes [`IcoBuilder::build_preview_png`] also write a preview for each
+    /// [`ColorVisionDeficiency`](transform::ColorVisionDeficiency), named after it,
+    /// e.g. `preview-protanopia.png`, so that status colors can be checked for accessibility.
+    /// Defaults to `false`.
+    pub fn color_vision_previews(&mut self, color_vision_previews: bool) -> &mut IcoBuilder {
+        self.color_vision_previews = color_vision_previews;
+        self
+    }
+
+    /// Makes [`IcoBuilder::build_file_cargo`] emit the path of the ICO file as `cargo:{key}={path}`
+    /// metadata. In crates with a `links` manifest key, the build scripts of dependent crates
+    /// can read it from the `DEP_{LINKS}_{KEY}` environment variable and reuse the icon.
+    /// Defaults to not emitting metadata.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// // In the build script of a crate with `links = "app-assets"`. Dependent build scripts
+    /// // can read the path from `DEP_APP_ASSETS_ICON_PATH`.
+    /// IcoBuilder::from_sources(["app-icon-256x256.png"])
+    ///     .links_metadata("icon_path")
+    ///     .build_file_cargo("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn links_metadata(&mut self, key: impl Into<String>) -> &mut IcoBuilder {
+        self.links_metadata_key = Some(key.into());
+        self
+    }
+
+    /// Calls `hook` with each frame and its size right after resizing, before the
+    /// [transformations](IcoBuilder::add_transform) are applied. Hooks allow last-mile tweaks
+    /// or collecting statistics without implementing [`Transform`].
+    ///
+    /// Frames aren't [cached](IcoBuilder::cache_dir) while hooks are registered,
+    /// since changes to a hook can't be detected.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// # use std::sync::Arc;
+    /// let transparent_pixels = Arc::new(AtomicU64::new(0));
+    /// let counter = transparent_pixels.clone();
+    /// IcoBuilder::default()
+    ///     .add_source_file("app-icon-256x256.png")
+    ///     .after_resize(move |frame, _size| {
+    ///         let count = frame.pixels().filter(|pixel| pixel[3] == 0).count();
+    ///         counter.fetch_add(count as u64, Ordering::Relaxed);
+    ///     })
+    ///     .build_file("app-icon.ico")?;
+    /// println!("{} transparent pixels", transparent_pixels.load(Ordering::Relaxed));
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn after_resize(
+        &mut self,
+        hook: impl Fn(&mut RgbaImage, u32) + Send + Sync + 'static,
+    ) -> &mut IcoBuilder {
+        self.after_resize.push(hook);
+        self
+    }
+
+    /// Calls `hook` with each generated frame and its size right before it is encoded,
+    /// after the transformations and the checks for [diagnostics](Diagnostic).
+    /// Frames added with [`IcoBuilder::add_encoded_frame`] are not passed to hooks.
+    pub fn before_encode(
+        &mut self,
+        hook: impl Fn(&mut RgbaImage, u32) + Send + Sync + 'static,
+    ) -> &mut IcoBuilder {
+        self.before_encode.push(hook);
+        self
+    }
+
+    /// Uses the recommended [sizes](IcoBuilder::sizes) and [encodings](IcoBuilder::xp_compatible)
+    /// for the supported Windows versions and display scales.
+    ///
+    /// ```no_run
+    /// # use ico_builder::{IcoBuilder, SizeRecommendation, WindowsVersion};
+    /// IcoBuilder::default()
+    ///     .add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"])
+    ///     .apply_size_recommendation(&SizeRecommendation::new(WindowsVersion::Vista).max_scale(200))
+    ///     .build_file("app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn apply_size_recommendation(
+        &mut self,
+        recommendation: &SizeRecommendation,
+    ) -> &mut IcoBuilder {
+        self.sizes = recommendation.sizes();
+        self.xp_compatible = recommendation.xp_compatible();
+        self
+    }
+
+    /// Applies overrides from environment variables, so that CI can tweak builds without code changes:
+    /// * `ICO_BUILDER_SIZES`: comma-separated sizes, e.g. `16,32`, see [`IcoBuilder::sizes`]
+    /// * `ICO_BUILDER_ALLOW_UPSCALING`: `true` or `false`, see [`IcoBuilder::allow_upscaling`]
+    ///
+    /// Unset variables leave the configuration unchanged. See [`Profiles::selected`] for
+    /// `ICO_BUILDER_PROFILE`. This is done automatically by
+    /// [`IcoBuilder::build_file_cargo`], which also supports `ICO_BUILDER_OUTPUT`.
+    pub fn apply_env_overrides(&mut self) -> Result<&mut IcoBuilder> {
+        let overrides = EnvOverrides::from_env()?;
+        if let Some(sizes) = overrides.sizes {
+            self.sizes = sizes;
+        }
+        if let Some(allow_upscaling) = overrides.allow_upscaling {
+            self.allow_upscaling = allow_upscaling;
+        }
+        Ok(self)
+    }
+
     /// Builds the ICO file and writes it to the specified `output_file_path`.
-    pub fn build_file(&self, output_file_path: impl AsRef<Path>) -> Result<()> {
-        let icons = decode_icons(&self.source_files)?;
-        let frames = create_ico_frames(&self.sizes, &icons, self.filter_type)?;
+    pub fn build_file(&self, output_file_path: impl AsRef<Path>) -> Result<BuildReport> {
+        let (encoded, report) = self.encode_ico()?;
+        self.write_file(output_file_path.as_ref(), &encoded)?;
+        Ok(report)
+    }
+
+    /// Builds the ICO file in memory, e.g. for an HTTP response, without touching the filesystem
+    /// except for reading the sources. Use [`IcoBuilder::build_writer`] to get the [`BuildReport`].
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// let ico = IcoBuilder::from_sources(["app-icon-256x256.png"]).build_vec()?;
+    /// assert_eq!(&ico[..4], b"\0\0\x01\0");
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_vec(&self) -> Result<Vec<u8>> {
+        Ok(self.encode_ico()?.0)
+    }
+
+    /// Builds the ICO file and writes it to `writer`, e.g. an entry of an archive or stdout.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use std::io;
+    /// let report = IcoBuilder::from_sources(["app-icon-256x256.png"]).build_writer(io::stdout())?;
+    /// for diagnostic in &report.diagnostics {
+    ///     eprintln!("{diagnostic}");
+    /// }
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_writer(&self, mut writer: impl Write) -> Result<BuildReport> {
+        let (encoded, report) = self.encode_ico()?;
+        writer.write_all(&encoded)?;
+        Ok(report)
+    }
+
+    /// Builds the ICO file with the same layout as an existing one: the same entries in the same order,
+    /// each encoded as a PNG or BMP with the bit count of the original, and with the same
+    /// directory fields. This allows refreshing icons where downstream tooling depends on
+    /// the exact entry structure.
+    ///
+    /// The sizes, [entry order](IcoBuilder::entry_order) and [encoded frames](IcoBuilder::add_encoded_frame)
+    /// of the builder are ignored. Duplicate frames are never dropped, and no frames are
+    /// skipped with [`IcoBuilder::best_effort`].
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// IcoBuilder::default()
+    ///     .add_source_file("new-icon-256x256.png")
+    ///     .build_file_with_layout("legacy.ico", "legacy.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_file_with_layout(
+        &self,
+        layout_file_path: impl AsRef<Path>,
+        output_file_path: impl AsRef<Path>,
+    ) -> Result<BuildReport> {
+        let layout = IcoFile::parse(&self.fs.read(layout_file_path.as_ref())?)?;
+        let mut sizes = Vec::new();
+        for entry in layout.entries() {
+            if entry.width() != entry.height() {
+                return Err(Error::NonSquareImage {
+                    path: layout_file_path.as_ref().to_owned(),
+                    width: entry.width(),
+                    height: entry.height(),
+                });
+            }
+            if !sizes.contains(&entry.width()) {
+                sizes.push(entry.width());
+            }
+        }
+
+        let mut builder = self.clone();
+        builder.drop_duplicate_frames = false;
+        // Every entry of the layout needs a frame, so no size can be skipped.
+        builder.best_effort = false;
+        let mut report = BuildReport::default();
+        let images = builder.generate_images(&sizes, &mut report)?;
+        let mut entries = Vec::with_capacity(layout.entries().len());
+        for entry in layout.entries() {
+            let (image, frame_report) = images
+                .iter()
+                .find(|(image, _)| image.width() == entry.width())
+                .expect("every size of the layout was rendered");
+            let data = match frame::bit_count(entry.data()) {
+                Some(bit_count @ (1 | 4 | 8 | 24 | 32)) if !entry.is_png() => {
+                    bmp::encode_bmp(image, bit_count)
+                }
+                _ => encode_png(image)?,
+            };
+            entries.push(IcoEntry::new(
+                entry.width(),
+                entry.height(),
+                entry.color_count(),
+                entry.planes(),
+                entry.bit_count(),
+                &data,
+            ));
+            report.frames.push(frame_report.clone());
+        }
+        let ico = IcoFile::from_entries(entries).to_bytes();
+        self.write_file(output_file_path.as_ref(), &ico)?;
+        Ok(report)
+    }
+
+    /// Builds variants of the ICO file for Windows high contrast themes next to `output_file_path`:
+    /// `{name}_contrast-black.ico` with a white [`Silhouette`](transform::Silhouette) of the artwork
+    /// for dark themes, and `{name}_contrast-white.ico` with a black one for light themes.
+    /// The variants use the same configuration as the regular ICO file.
+    ///
+    /// Returns the paths of the written files.
+    pub fn build_high_contrast_files(
+        &self,
+        output_file_path: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>> {
+        let output_file_path = output_file_path.as_ref();
+        let name = output_file_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let icon = self.with_decoded_sources()?;
+        icon.collect(export::CONTRAST_VARIANTS.iter().map(|&(qualifier, color)| {
+            let path = output_file_path.with_file_name(format!("{name}_{qualifier}.ico"));
+            icon.clone()
+                .add_transform(transform::Silhouette::new(color))
+                .build_file(&path)?;
+            Ok(path)
+        }))
+    }
 
-        let file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&output_file_path)?;
-        IcoEncoder::new(file).encode_images(&frames)?;
+    /// Builds the icon and writes it to a Windows resource (`.res`) file as the icon group `1`,
+    /// which Explorer shows as the icon of the executable. Resource files can be passed
+    /// directly to the linker, so no resource compiler such as `rc.exe` or `windres` is needed.
+    /// Use [`resource::IconLibrary`] for multiple icons or other resource IDs.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// // build.rs
+    /// let res_path = std::path::Path::new(&std::env::var_os("OUT_DIR").unwrap()).join("app-icon.res");
+    /// IcoBuilder::default()
+    ///     .add_source_file("app-icon-256x256.png")
+    ///     .build_res_file(&res_path)?;
+    /// println!("cargo:rustc-link-arg-bins={}", res_path.display());
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_res_file(&self, output_file_path: impl AsRef<Path>) -> Result<BuildReport> {
+        let (ico, report) = self.encode_ico()?;
+        let groups = [(resource::ResourceId::Ordinal(1), IcoFile::parse(&ico)?)];
+        self.write_file(output_file_path.as_ref(), &resource::write_res(&groups))?;
+        Ok(report)
+    }
 
-        Ok(())
+    /// Builds the icon and writes it to a COFF object file as the icon group `1`.
+    /// Unlike [`.res` files](IcoBuilder::build_res_file), objects can be passed to both the MSVC
+    /// and the GNU linker, which allows embedding the icon when cross-compiling to Windows.
+    /// See [`resource::IconLibrary::build_coff_file`] for an example.
+    pub fn build_coff_file(
+        &self,
+        output_file_path: impl AsRef<Path>,
+        machine: resource::Machine,
+    ) -> Result<BuildReport> {
+        let (ico, report) = self.encode_ico()?;
+        let groups = [(resource::ResourceId::Ordinal(1), IcoFile::parse(&ico)?)];
+        self.write_file(
+            output_file_path.as_ref(),
+            &resource::write_coff(&groups, machine),
+        )?;
+        Ok(report)
+    }
+
+    /// Builds the icon and rebuilds it whenever one of the source files changes, calling `callback`
+    /// with the result of each build, until the callback returns [`ControlFlow::Break`].
+    /// This is meant for GUI tools and development servers. Requires the `watch` feature.
+    ///
+    /// The source files are watched on the real filesystem, even if a custom [`Fs`] is used.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use std::ops::ControlFlow;
+    /// IcoBuilder::default()
+    ///     .add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"])
+    ///     .watch("app-icon.ico", |result| {
+    ///         match result {
+    ///             Ok(report) => println!("Rebuilt with {} frames", report.frames.len()),
+    ///             Err(error) => eprintln!("{error}"),
+    ///         }
+    ///         ControlFlow::Continue(())
+    ///     })?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    #[cfg(feature = "watch")]
+    pub fn watch(
+        &self,
+        output_file_path: impl AsRef<Path>,
+        mut callback: impl FnMut(Result<BuildReport>) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let output_file_path = output_file_path.as_ref();
+        // Sources that were decoded up front have to be decoded again after changes.
+        let mut icon = self.clone();
+        icon.decoded_sources = None;
+        if callback(icon.build_file(output_file_path)).is_break() {
+            return Ok(());
+        }
+        let paths: Vec<_> = self
+            .sources
+            .iter()
+            .filter_map(Source::as_file)
+            .map(Path::to_owned)
+            .collect();
+        watch::watch_files(&paths, || callback(icon.build_file(output_file_path)))
+    }
+
+    /// Rebuilds the ICO file in memory and checks that the file at `path` has the same frames,
+    /// failing with [`Error::OutdatedIcon`] otherwise. This lets CI enforce that icons
+    /// committed to the repository are up to date with their sources, like `cargo fmt --check`.
+    ///
+    /// The frames are compared after decoding, within the [tolerance](IcoBuilder::verify_tolerance).
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// IcoBuilder::default()
+    ///     .add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"])
+    ///     .verify_file("assets/app-icon.ico")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn verify_file(&self, path: impl AsRef<Path>) -> Result<BuildReport> {
+        let path = path.as_ref();
+        let (expected, report) = self.encode_ico()?;
+        let actual = IcoFile::parse(&self.fs.read(path)?)?;
+        let expected = IcoFile::parse(&expected)?;
+        verify::compare(&expected, &actual, self.verify_tolerance).map_err(|reason| {
+            Error::OutdatedIcon {
+                path: path.to_owned(),
+                reason,
+            }
+        })?;
+        Ok(report)
+    }
+
+    /// Builds the ICO file in memory and compares its frames to those of the baseline ICO file
+    /// at `baseline_path`, within the [tolerance](IcoBuilder::verify_tolerance).
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// let diff = IcoBuilder::default()
+    ///     .add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"])
+    ///     .visual_diff("assets/app-icon.ico")?;
+    /// println!("{diff}");
+    /// diff.write_report("target/icon-diff")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn visual_diff(&self, baseline_path: impl AsRef<Path>) -> Result<regression::VisualDiff> {
+        let baseline = IcoFile::parse(&self.fs.read(baseline_path.as_ref())?)?;
+        let current = IcoFile::parse(&self.encode_ico()?.0)?;
+        regression::VisualDiff::new(&baseline, &current, self.verify_tolerance)
     }
 
     /// Builds the ICO file and writes it to `OUT_DIR`.
-    /// Tells Cargo to re-build when one of the specified sources changes.
-    /// ## Panics
-    /// This function panics if the path of one of the source files is not valid UTF-8.
+    /// Tells Cargo to re-build when one of the specified sources changes
+    /// and emits diagnostics as Cargo warnings, as well as the [path](IcoBuilder::links_metadata)
+    /// for dependent crates if enabled.
+    ///
+    /// [Environment overrides](IcoBuilder::apply_env_overrides) are applied. Additionally,
+    /// `ICO_BUILDER_OUTPUT` overrides the path of the ICO file.
+    ///
+    /// Fails with [`Error::NonUtf8Path`] if the path of one of the source files is not valid UTF-8,
+    /// since Cargo can't track it.
     pub fn build_file_cargo(&self, file_name: impl AsRef<OsStr>) -> Result<PathBuf> {
         let out_dir = env::var_os("OUT_DIR").expect(
             "OUT_DIR environment variable is required.\nHint: This function is intended to be used in Cargo build scripts.",
         );
-        let output_path: PathBuf = [&out_dir, file_name.as_ref()].iter().collect();
-
-        for file in &self.source_files {
-            println!(
-                "cargo:rerun-if-changed={}",
-                file.to_str().expect("Path needs to be valid UTF-8")
-            )
+        for name in env_overrides::VARIABLES {
+            println!("cargo:rerun-if-env-changed={name}");
+        }
+        let mut builder = self.clone();
+        builder.apply_env_overrides()?;
+        let output_path = match EnvOverrides::from_env()?.output {
+            Some(output_path) => output_path,
+            None => [&out_dir, file_name.as_ref()].iter().collect(),
+        };
+        let files = self
+            .sources
+            .iter()
+            .filter_map(Source::as_file)
+            .map(|file| {
+                file.to_str()
+                    .ok_or_else(|| Error::NonUtf8Path(file.to_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for file in files {
+            println!("cargo:rerun-if-changed={file}");
         }
 
-        self.build_file(&output_path)?;
+        let report = builder.build_file(&output_path)?;
+        for diagnostic in &report.diagnostics {
+            println!("cargo:warning={diagnostic}");
+        }
+        if let Some(key) = &self.links_metadata_key {
+            let path = output_path
+                .to_str()
+                .ok_or_else(|| Error::NonUtf8Path(output_path.clone()))?;
+            println!("cargo:{key}={path}");
+        }
 
         Ok(output_path)
     }
-}
 
-/// A list of icon sizes.
-#[derive(Debug)]
-pub struct IconSizes(Cow<'static, [u32]>);
+    /// Like [`IcoBuilder::build_file_cargo`], but returns the path as a [`camino::Utf8PathBuf`].
+    /// Fails with [`Error::NonUtf8Path`] if `OUT_DIR` or `ICO_BUILDER_OUTPUT` is not valid UTF-8.
+    ///
+    /// All other paths of the API accept [`camino::Utf8Path`]s directly.
+    /// ```no_run
+    /// # use camino::Utf8Path;
+    /// # use ico_builder::IcoBuilder;
+    /// let icons = Utf8Path::new("assets/icons");
+    /// let path = IcoBuilder::default()
+    ///     .add_source_files([icons.join("app-icon-16x16.png"), icons.join("app-icon-256x256.png")])
+    ///     .build_file_cargo_utf8("app-icon.ico")?;
+    /// println!("cargo:rustc-env=APP_ICON={path}");
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    #[cfg(feature = "camino")]
+    pub fn build_file_cargo_utf8(&self, file_name: impl AsRef<str>) -> Result<camino::Utf8PathBuf> {
+        Ok(self.build_file_cargo(file_name.as_ref())?.try_into()?)
+    }
+
+    /// Builds a PNG file for each size and writes them to `output_dir`,
+    /// named after the [file name template](IcoBuilder::png_file_name). Returns the paths of the written files.
+    ///
+    /// Together with a [`Transform`] this can be used to emit variants from the same sources:
+    /// ```no_run
+    /// # use ico_builder::{IcoBuilder, Rgba};
+    /// # use ico_builder::transform::Silhouette;
+    /// let mut builder = IcoBuilder::default();
+    /// builder.add_source_file("app-icon-256x256.png");
+    /// builder.build_file("app-icon.ico")?;
+    /// builder
+    ///     .clone()
+    ///     .add_transform(Silhouette::new(Rgba([0, 0, 0, 255])))
+    ///     .build_png_files("badges", "app-icon-silhouette")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_png_files(
+        &self,
+        output_dir: impl AsRef<Path>,
+        name: &str,
+    ) -> Result<Vec<PathBuf>> {
+        self.render(&self.sizes)?
+            .iter()
+            .map(|frame| {
+                let file_name = self
+                    .png_file_name
+                    .replace("{name}", name)
+                    .replace("{size}", &frame.width().to_string());
+                let path = output_dir.as_ref().join(file_name);
+                if let Some(parent) = path.parent() {
+                    self.create_dir_all(parent)?;
+                }
+                self.write_file(&path, &encode_png(frame)?)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    /// Renders all frames of the ICO file side by side, labelled with their sizes,
+    /// into a single PNG for design reviews and pull request screenshots.
+    /// The frames are shown exactly as they would be included in the ICO file.
+    /// See [`IcoBuilder::color_vision_previews`] for additional accessibility previews.
+    pub fn build_preview_png(&self, output_file_path: impl AsRef<Path>) -> Result<BuildReport> {
+        let (ico, report) = self.encode_ico()?;
+        let frames = IcoFile::parse(&ico)?
+            .entries()
+            .iter()
+            .map(IcoEntry::decode)
+            .collect::<Result<Vec<_>>>()?;
+        let output_file_path = output_file_path.as_ref();
+        let sheet = contact_sheet::render(&frames);
+        self.write_file(output_file_path, &encode_png(&sheet)?)?;
+        if self.color_vision_previews {
+            for deficiency in transform::ColorVisionDeficiency::ALL {
+                let simulation = transform::ColorVisionSimulation::new(deficiency);
+                let mut frames = frames.clone();
+                for frame in &mut frames {
+                    simulation.apply(frame);
+                }
+                let mut file_name = output_file_path.file_stem().unwrap_or_default().to_owned();
+                file_name.push(format!("-{}.png", deficiency.name()));
+                let sheet = contact_sheet::render(&frames);
+                self.write_file(
+                    &output_file_path.with_file_name(file_name),
+                    &encode_png(&sheet)?,
+                )?;
+            }
+        }
+        Ok(report)
+    }
 
-impl IconSizes {
-    /// The [bare minimum] recommended icon sizes: 16x16, 24x24, 32x32, 48x48, and 256x256.
+    /// Predicts the size of the ICO file and of each frame without building it, e.g. to show
+    /// the impact of adding a 256px frame. Generated PNG frames are estimated by resizing and
+    /// encoding frames of at most 64px and extrapolating to the frame size, which is much faster
+    /// than resizing and encoding the full frames. Transformations and hooks are not applied.
     ///
-    /// [bare minimum]: https://learn.microsoft.com/en-us/windows/apps/design/style/iconography/app-icon-construction#icon-scaling
-    pub const MINIMAL: Self = Self::new(&[16, 24, 32, 48, 256]);
+    /// ```no_run
+    /// # use ico_builder::{IcoBuilder, IconSizes};
+    /// let mut icon = IcoBuilder::from_sources(["app-icon-256x256.png"]);
+    /// let without_256 = icon.sizes(&[16, 24, 32, 48]).estimate_output_size()?;
+    /// let with_256 = icon.sizes(IconSizes::MINIMAL).estimate_output_size()?;
+    /// println!("256px frame: +{} bytes", with_256.total_bytes - without_256.total_bytes);
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn estimate_output_size(&self) -> Result<SizeEstimate> {
+        let encoded_frames = self.collect(
+            self.encoded_frames
+                .iter()
+                .map(|(data, width, height)| Frame::from_encoded(data, *width, *height)),
+        )?;
+        let encoded_sizes: IconSizes = encoded_frames.iter().map(Frame::size).collect();
+        let sizes = self.output_sizes(&encoded_sizes);
+        let icons = if sizes.iter().all(|size| encoded_sizes.contains(size)) {
+            Vec::new()
+        } else {
+            self.decode_icons()?
+        };
 
-    pub const fn new(sizes: &'static [u32]) -> IconSizes {
-        Self(Cow::Borrowed(sizes))
+        let mut frames = self.collect(sizes.iter().map(|&size| {
+            let encoded = encoded_frames.iter().find(|frame| frame.size == size);
+            let encoding = self.frame_encoding(size, encoded);
+            match (encoding, encoded) {
+                (FrameEncoding::Bmp, _) => Ok(FrameEstimate::bmp(size, 32)),
+                (_, Some(frame)) => Ok(FrameEstimate::encoded(size, &frame.data)),
+                (_, None) => {
+                    let (_, source) =
+                        self.selection_strategy
+                            .select(&icons, size, self.allow_upscaling)?;
+                    let filter = self.filter_type.resampling(source, size).filter;
+                    let indexed = encoding == FrameEncoding::IndexedPng;
+                    FrameEstimate::png(source, size, filter, indexed)
+                }
+            }
+        }))?;
+        if self.xp_compatible {
+            frames.extend(sizes.iter().map(|&size| FrameEstimate::bmp(size, 8)));
+        }
+        Ok(SizeEstimate::new(frames))
     }
-}
 
-impl Default for IconSizes {
-    fn default() -> Self {
-        IconSizes::MINIMAL
+    /// Runs the source selection, resizing, transformations, and encoding
+    /// without writing an ICO file. This allows assembling the frames into custom containers.
+    pub fn build_frames(&self) -> Result<Vec<Frame>> {
+        Ok(self.encode_frames()?.0)
     }
-}
 
-impl<'a, I> From<I> for IconSizes
-where
-    I: IntoIterator<Item = &'a u32>,
-{
-    fn from(value: I) -> Self {
-        IconSizes(value.into_iter().copied().collect::<Vec<_>>().into())
+    pub(crate) fn encode_ico(&self) -> Result<(Vec<u8>, BuildReport)> {
+        let (frames, report) = self.encode_frames()?;
+        let frames = create_ico_frames(&frames)?;
+
+        let mut encoded = Vec::new();
+        IcoEncoder::new(&mut encoded).encode_images(&frames)?;
+        if !self.entry_overrides.is_empty() {
+            let entries = IcoFile::parse(&encoded)?
+                .entries()
+                .iter()
+                .map(|entry| {
+                    self.entry_overrides
+                        .iter()
+                        .fold(entry.clone(), |entry, overrides| overrides.apply(&entry))
+                })
+                .collect();
+            encoded = IcoFile::from_entries(entries).to_bytes();
+        }
+        Ok((encoded, report))
+    }
+
+    pub(crate) fn encode_frames(&self) -> Result<(Vec<Frame>, BuildReport)> {
+        if self.provenance != Provenance::Omitted && self.decoded_sources.is_none() {
+            // The provenance record hashes the data that the sources were decoded from.
+            return self.with_decoded_sources()?.encode_frames();
+        }
+        let mut report = BuildReport::default();
+        let encoded_frames = self
+            .encoded_frames
+            .iter()
+            .map(|(data, width, height)| (*width, Frame::from_encoded(data, *width, *height)));
+        let mut encoded_frames = if self.best_effort {
+            encoded_frames
+                .filter_map(|(size, frame)| {
+                    frame
+                        .map_err(|error| report.diagnostics.push(skipped_frame(size, &error)))
+                        .ok()
+                })
+                .collect()
+        } else {
+            self.collect(encoded_frames.map(|(_, frame)| frame))?
+        };
+        if self.strip_png_metadata {
+            for frame in &mut encoded_frames {
+                frame.data = strip_metadata_chunks(std::mem::take(&mut frame.data));
+            }
+        }
+        let encoded_sizes: IconSizes = encoded_frames.iter().map(Frame::size).collect();
+        let sizes = self.output_sizes(&encoded_sizes);
+
+        let generated_sizes: Vec<_> = sizes
+            .iter()
+            .copied()
+            .filter(|size| !encoded_sizes.contains(size))
+            .collect();
+        let mut generated = self.generate_frames(&generated_sizes, &mut report)?;
+
+        let mut frames = Vec::with_capacity(sizes.len());
+        let mut frame_reports = Vec::with_capacity(sizes.len());
+        for size in sizes {
+            if let Some(frame) = encoded_frames.iter().find(|frame| frame.size == size) {
+                frames.push(frame.clone());
+                frame_reports.push(FrameReport {
+                    size,
+                    origin: FrameOrigin::Encoded,
+                    quality: None,
+                });
+            } else if let Some(index) = generated.iter().position(|(frame, _)| frame.size == size) {
+                let (frame, frame_report) = generated.remove(index);
+                frames.push(frame);
+                frame_reports.push(frame_report);
+            }
+        }
+        report.frames = frame_reports;
+
+        let mut frames = frames
+            .into_iter()
+            .map(|frame| {
+                Ok(self
+                    .png_color_space
+                    .apply(ensure_rgba_png(frame, &mut report)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if self.xp_compatible {
+            return bmp::xp_compatible_frames(frames, report);
+        }
+        let decoded_sources = self.decoded_sources.as_deref().unwrap_or_default();
+        self.provenance
+            .apply(&self.sources, decoded_sources, &mut frames);
+        Ok((frames, report))
+    }
+
+    /// The sizes of the output frames, in the order of the output.
+    fn output_sizes(&self, encoded_sizes: &IconSizes) -> Vec<u32> {
+        let mut sizes = self.entry_order.apply(&self.sizes.union(encoded_sizes));
+        if self.xp_compatible {
+            sizes.retain(|&size| size <= bmp::XP_MAX_SIZE);
+        }
+        sizes
+    }
+
+    /// How the output frame of `size` is encoded, given the encoded frame of that size, if any.
+    /// [`IcoBuilder::estimate_output_size`] makes the same decisions as the build.
+    fn frame_encoding(&self, size: u32, encoded: Option<&Frame>) -> FrameEncoding {
+        match encoded {
+            // PNG frames are converted by `bmp::xp_compatible_frames`.
+            Some(frame) if self.xp_compatible && frame.data.starts_with(png::PNG_SIGNATURE) => {
+                FrameEncoding::Bmp
+            }
+            Some(_) => FrameEncoding::Encoded,
+            None if self.xp_compatible => FrameEncoding::Bmp,
+            None if self.indexed_png && size < 256 => FrameEncoding::IndexedPng,
+            None => FrameEncoding::Png,
+        }
+    }
+
+    fn generate_frames(
+        &self,
+        sizes: &[u32],
+        report: &mut BuildReport,
+    ) -> Result<Vec<(Frame, FrameReport)>> {
+        self.generate_images(sizes, report)?
+            .into_iter()
+            .map(|(frame, frame_report)| {
+                let encoding = self.frame_encoding(frame.width(), None);
+                let data = if encoding == FrameEncoding::IndexedPng {
+                    png::encode_indexed_png(&frame)?
+                } else {
+                    encode_png(&frame)?
+                };
+                let frame = Frame {
+                    size: frame.width(),
+                    data,
+                };
+                Ok((frame, frame_report))
+            })
+            .collect()
+    }
+
+    /// Renders the frames for the given sizes and checks them for issues.
+    fn generate_images(
+        &self,
+        sizes: &[u32],
+        report: &mut BuildReport,
+    ) -> Result<Vec<(RgbaImage, FrameReport)>> {
+        if sizes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let icons = self.decode_icons()?;
+        let rendered = if self.best_effort {
+            self.render_frames_best_effort(&icons, sizes, report)?
+        } else {
+            self.render_frames(&icons, sizes)?
+        };
+        let (mut frames, origins): (Vec<_>, Vec<_>) = rendered.into_iter().unzip();
+        for (frame, origin) in frames.iter().zip(origins) {
+            if let FrameOrigin::Resized { source_size, .. } = origin {
+                let ratio = source_size as f32 / frame.width() as f32;
+                if source_size < frame.width() {
+                    report.diagnostics.push(Diagnostic::Upscaled {
+                        size: frame.width(),
+                        source_size,
+                    });
+                } else if ratio > self.max_downscale_ratio {
+                    report.diagnostics.push(Diagnostic::ExtremeDownscale {
+                        size: frame.width(),
+                        source_size,
+                        ratio,
+                    });
+                }
+            }
+            report.frames.push(FrameReport {
+                size: frame.width(),
+                origin,
+                quality: self
+                    .quality_report
+                    .then(|| self.measure_quality(&icons, frame)),
+            });
+        }
+        check_legibility(&frames, report);
+        check_duplicate_frames(&mut frames, self.drop_duplicate_frames, report);
+
+        for frame in &mut frames {
+            self.before_encode.run(frame);
+        }
+
+        let frame_reports = std::mem::take(&mut report.frames);
+        Ok(frames.into_iter().zip(frame_reports).collect())
+    }
+
+    pub(crate) fn write_file(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        Ok(self.fs.write(path, contents)?)
+    }
+
+    pub(crate) fn create_dir_all(&self, path: &Path) -> Result<()> {
+        Ok(self.fs.create_dir_all(path)?)
+    }
+
+    /// Decodes the sources and renders a frame for each of the given sizes.
+    pub(crate) fn render(&self, sizes: &[u32]) -> Result<Vec<RgbaImage>> {
+        let icons = self.decode_icons()?;
+        let frames = self.render_frames(&icons, sizes)?;
+        Ok(frames.into_iter().map(|(frame, _)| frame).collect())
+    }
+
+    fn render_frames(
+        &self,
+        icons: &[Arc<DynamicImage>],
+        sizes: &[u32],
+    ) -> Result<Vec<(RgbaImage, FrameOrigin)>> {
+        let hashes = cache::SourceHashes::new(icons.len());
+        self.collect(
+            sizes
+                .iter()
+                .map(|&size| self.render_frame(icons, &hashes, size)),
+        )
+    }
+
+    /// Renders the frames that can be produced and reports the others as skipped.
+    /// Fails with the errors of the frames if no frame can be produced.
+    fn render_frames_best_effort(
+        &self,
+        icons: &[Arc<DynamicImage>],
+        sizes: &[u32],
+        report: &mut BuildReport,
+    ) -> Result<Vec<(RgbaImage, FrameOrigin)>> {
+        let mut frames = Vec::with_capacity(sizes.len());
+        let mut errors = Vec::new();
+        let mut first_panic = None;
+        let hashes = cache::SourceHashes::new(icons.len());
+        for &size in sizes {
+            let render = || self.render_frame(icons, &hashes, size);
+            match panic::catch_unwind(AssertUnwindSafe(render)) {
+                Ok(Ok(frame)) => frames.push(frame),
+                Ok(Err(error)) => {
+                    report.diagnostics.push(skipped_frame(size, &error));
+                    errors.push(error);
+                }
+                Err(panic) => {
+                    let reason = panic
+                        .downcast_ref::<&str>()
+                        .map(|reason| reason.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "rendering panicked".to_owned());
+                    report
+                        .diagnostics
+                        .push(Diagnostic::SkippedFrame { size, reason });
+                    first_panic.get_or_insert(panic);
+                }
+            }
+        }
+        if frames.is_empty() {
+            self.collect(errors.into_iter().map(Err::<(), _>))?;
+            if let Some(panic) = first_panic {
+                panic::resume_unwind(panic);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Writes a JSON manifest of files written by exporters, so that downstream packaging
+    /// steps can consume the outputs reliably. Each file is listed with its path relative to
+    /// the manifest, its size in bytes, its `width` and `height` if it is an image in a
+    /// supported format, and a hash of its contents. See also [`Artifacts::manifest`].
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use ico_builder::web::WebExport;
+    /// let mut icon = IcoBuilder::default();
+    /// icon.add_source_file("app-icon-512x512.png");
+    /// let written = WebExport::default().build(&icon, "public")?;
+    /// icon.write_manifest(&written, "public/icons.json")?;
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn write_manifest(
+        &self,
+        paths: &[PathBuf],
+        manifest_path: impl AsRef<Path>,
+    ) -> Result<PathBuf> {
+        let manifest_path = manifest_path.as_ref();
+        manifest::write_manifest(self, paths, manifest_path)?;
+        Ok(manifest_path.to_owned())
+    }
+
+    /// Decodes the sources now instead of on every build, so that clones of the builder
+    /// share the decoded images. Adding sources afterwards discards them.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use std::thread;
+    /// let mut template = IcoBuilder::default();
+    /// template.add_source_file("app-icon-256x256.png").decode_sources()?;
+    /// thread::scope(|scope| {
+    ///     for (tenant, sizes) in [("legacy", &[16, 32, 48][..]), ("modern", &[16, 32, 48, 256])] {
+    ///         let mut icon = template.clone();
+    ///         scope.spawn(move || icon.sizes(sizes).build_file(format!("{tenant}.ico")));
+    ///     }
+    /// });
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn decode_sources(&mut self) -> Result<&mut IcoBuilder> {
+        self.decoded_sources = Some(self.decode_all_sources()?.into());
+        Ok(self)
+    }
+
+    /// Returns a copy of the builder that decodes its sources only once,
+    /// so that builds from multiple threads can share them.
+    pub(crate) fn with_decoded_sources(&self) -> Result<IcoBuilder> {
+        let mut builder = self.clone();
+        builder.decode_sources()?;
+        Ok(builder)
+    }
+
+    fn decode_icons(&self) -> Result<Vec<Arc<DynamicImage>>> {
+        if let Some(decoded_sources) = &self.decoded_sources {
+            return Ok(decoded_sources
+                .iter()
+                .map(|decoded| decoded.image.clone())
+                .collect());
+        }
+        Ok(self
+            .decode_all_sources()?
+            .into_iter()
+            .map(|decoded| decoded.image)
+            .collect())
+    }
+
+    fn decode_all_sources(&self) -> Result<Vec<DecodedSource>> {
+        let fs = self.fs.as_ref();
+        if self.sources.len() <= 1 {
+            return self.collect(self.sources.iter().map(|source| decode_icon(fs, source)));
+        }
+        // Decoding large sources dominates the build time, so they are decoded in parallel,
+        // with no more threads than the cores that can run them.
+        let threads = thread::available_parallelism().map_or(1, usize::from);
+        let chunk_size = self.sources.len().div_ceil(threads);
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .sources
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|source| decode_icon(fs, source))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+        self.collect(results.into_iter())
+    }
+
+    /// Stops at the first error, unless [`IcoBuilder::collect_all_errors`] is enabled.
+    fn collect<T>(&self, results: impl Iterator<Item = Result<T>>) -> Result<Vec<T>> {
+        if !self.collect_all_errors {
+            return results.collect();
+        }
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(value) => values.push(value),
+                Err(error) => errors.push(error),
+            }
+        }
+        match errors.len() {
+            0 => Ok(values),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+
+    fn render_frame(
+        &self,
+        icons: &[Arc<DynamicImage>],
+        source_hashes: &cache::SourceHashes,
+        size: u32,
+    ) -> Result<(RgbaImage, FrameOrigin)> {
+        let (index, next_bigger_icon) =
+            self.selection_strategy
+                .select(icons, size, self.allow_upscaling)?;
+        let resampling = self.filter_type.resampling(next_bigger_icon, size);
+        let origin = |cached| FrameOrigin::Resized {
+            source: index,
+            path: self.sources[index].as_file().map(Path::to_owned),
+            source_size: next_bigger_icon.width(),
+            filter: resampling.filter.into(),
+            sharpened: resampling.sharpen,
+            transforms: self.transforms.iter().map(|t| format!("{t:?}")).collect(),
+            cached,
+        };
+        let render = || -> Result<RgbaImage> {
+            let mut frame = self.resize_icon(next_bigger_icon, size, resampling.filter)?;
+            if resampling.sharpen {
+                filter::sharpen(&mut frame);
+            }
+            self.after_resize.run(&mut frame);
+            self.apply_transforms(&mut frame);
+            Ok(frame)
+        };
+        // Hooks can't be identified, so frames that they might change aren't cached.
+        let cache_dir = self
+            .cache_dir
+            .as_ref()
+            .filter(|_| self.after_resize.is_empty());
+        let Some(cache_dir) = cache_dir else {
+            return Ok((render()?, origin(false)));
+        };
+
+        #[cfg(feature = "gpu")]
+        let resizer = self.gpu_resizer.as_ref().map(gpu::GpuResizer::adapter_name);
+        #[cfg(not(feature = "gpu"))]
+        let resizer = None;
+        let key = cache::frame_key(
+            source_hashes.get(index, next_bigger_icon),
+            size,
+            self.filter_type,
+            &self.transforms,
+            resizer,
+        );
+        let cache_path = cache_dir.join(format!("{key:016x}.png"));
+        if let Some(frame) = self.read_cached_frame(&cache_path, size) {
+            return Ok((frame, origin(true)));
+        }
+        let frame = render()?;
+        self.fs.create_dir_all(cache_dir)?;
+        self.write_file(&cache_path, &encode_png(&frame)?)?;
+        Ok((frame, origin(false)))
+    }
+
+    fn resize_icon(
+        &self,
+        icon: &DynamicImage,
+        size: u32,
+        filter: imageops::FilterType,
+    ) -> Result<RgbaImage> {
+        #[cfg(feature = "gpu")]
+        if let Some(gpu_resizer) = &self.gpu_resizer {
+            return gpu_resizer.resize(icon, size, size, filter);
+        }
+        Ok(resize(icon, size, size, filter))
+    }
+
+    /// Unreadable or corrupt cache entries are treated as missing.
+    fn read_cached_frame(&self, path: &Path, size: u32) -> Option<RgbaImage> {
+        let contents = self.fs.read(path).ok()?;
+        let frame = image::load_from_memory_with_format(&contents, ImageFormat::Png).ok()?;
+        (frame.width() == size && frame.height() == size).then(|| frame.to_rgba8())
+    }
+
+    fn apply_transforms(&self, frame: &mut RgbaImage) {
+        for transform in &self.transforms {
+            transform.apply(frame);
+        }
+    }
+
+    fn measure_quality(&self, icons: &[Arc<DynamicImage>], frame: &RgbaImage) -> Quality {
+        let largest_icon = icons
+            .iter()
+            .max_by_key(|icon| icon.width())
+            .expect("frames can only be rendered with at least one source");
+        let mut reference = resize(
+            &**largest_icon,
+            frame.width(),
+            frame.width(),
+            imageops::FilterType::Lanczos3,
+        );
+        self.apply_transforms(&mut reference);
+        quality::measure(frame, &reference)
     }
 }
 
-impl Deref for IconSizes {
-    type Target = [u32];
+#[derive(Debug, Clone)]
+enum Source {
+    File(PathBuf),
+    Image(Arc<DynamicImage>),
+    Encoded(Arc<[u8]>),
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl Source {
+    fn as_file(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) => Some(path),
+            Source::Image(_) | Source::Encoded(_) => None,
+        }
     }
 }
 
-fn decode_icons(
-    icon_sources: impl IntoIterator<Item = impl AsRef<Path>>,
-) -> Result<Vec<DynamicImage>> {
-    icon_sources
-        .into_iter()
-        .map(|path| decode_icon(path.as_ref()))
-        .collect()
+/// How a frame of the output is encoded, see [`IcoBuilder::frame_encoding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameEncoding {
+    /// Added with [`IcoBuilder::add_encoded_frame`] and kept as is.
+    Encoded,
+    Png,
+    IndexedPng,
+    /// A 32-bit BMP for Windows XP, which also gets an 8-bit copy.
+    Bmp,
 }
 
-fn decode_icon(path: &Path) -> Result<DynamicImage> {
-    let image = ImageReader::open(path)?.decode()?;
+/// A decoded source and a hash of the data it was decoded from.
+#[derive(Debug)]
+pub(crate) struct DecodedSource {
+    image: Arc<DynamicImage>,
+    pub(crate) hash: u64,
+}
+
+fn decode_icon(fs: &dyn Fs, source: &Source) -> Result<DecodedSource> {
+    let (image, hash) = match source {
+        Source::File(path) => {
+            let contents = fs.read(path)?;
+            let hash = Fnv1a::default().write(&contents).finish();
+            (Arc::new(decode_file(&contents, path)?), hash)
+        }
+        Source::Image(image) => {
+            let hash = Fnv1a::default()
+                .write(&image.width().to_le_bytes())
+                .write(&image.height().to_le_bytes())
+                .write(image.as_bytes())
+                .finish();
+            (image.clone(), hash)
+        }
+        Source::Encoded(contents) => {
+            let hash = Fnv1a::default().write(contents).finish();
+            (Arc::new(decode_encoded(contents)?), hash)
+        }
+    };
 
     if is_square(&image) {
-        Ok(image)
+        Ok(DecodedSource { image, hash })
     } else {
         Err(Error::NonSquareImage {
-            path: path.to_owned(),
+            path: source.as_file().map(Path::to_owned).unwrap_or_default(),
             width: image.width(),
             height: image.height(),
         })
     }
 }
 
-fn is_square(image: &DynamicImage) -> bool {
-    image.width() == image.height()
+fn decode_file(contents: &[u8], path: &Path) -> Result<DynamicImage> {
+    if let Some(image) = decode_outside_image(contents, path) {
+        return image;
+    }
+    let contents = Cursor::new(contents);
+    let reader = match ImageFormat::from_path(path) {
+        Ok(format) => ImageReader::with_format(contents, format),
+        Err(_) => ImageReader::new(contents).with_guessed_format()?,
+    };
+    decode_reader(reader, path)
 }
 
-fn find_next_bigger_icon(icons: &[DynamicImage], size: u32) -> Result<&DynamicImage> {
-    icons
-        .iter()
-        .filter(|icon| icon.width() >= size)
-        .min_by_key(|icon| icon.width())
-        .ok_or(Error::MissingIconSize(size))
+/// Decodes an in-memory source, which is reported with an empty path.
+fn decode_encoded(contents: &[u8]) -> Result<DynamicImage> {
+    if let Some(image) = decode_outside_image(contents, Path::new("")) {
+        return image;
+    }
+    decode_reader(
+        ImageReader::new(Cursor::new(contents)).with_guessed_format()?,
+        Path::new(""),
+    )
 }
 
-fn create_ico_frames(
-    sizes: &IconSizes,
-    icons: &[DynamicImage],
-    filter_type: FilterType,
-) -> Result<Vec<IcoFrame<'static>>> {
-    sizes
-        .iter()
-        .copied()
-        .map(|size| create_ico_frame(icons, size, filter_type))
-        .collect()
+/// Decodes the sources that the `image` crate can't decode, or can't decode with alpha.
+/// Returns `None` for other sources.
+fn decode_outside_image(contents: &[u8], path: &Path) -> Option<Result<DynamicImage>> {
+    if let Some(image) = bmp::decode_with_alpha(contents) {
+        return Some(Ok(image.into()));
+    }
+    #[cfg(feature = "heif")]
+    if let Some(image) = heif::decode(contents) {
+        return Some(image.map(DynamicImage::from));
+    }
+    #[cfg(feature = "jxl")]
+    if let Some(image) = jxl::decode(contents) {
+        return Some(image);
+    }
+    // The format is recognized, but its feature is disabled.
+    let format = FeatureFormat::from_signature(contents)?;
+    Some(Err(Error::UnsupportedFeatureFormat {
+        path: path.to_owned(),
+        format,
+    }))
 }
 
-fn create_ico_frame(
-    icons: &[DynamicImage],
-    size: u32,
-    filter_type: FilterType,
-) -> Result<IcoFrame<'static>> {
-    let next_bigger_icon = find_next_bigger_icon(icons, size)?;
-    let resized = resize(next_bigger_icon, size, size, filter_type);
-    encode_ico_frame(resized.as_raw(), size)
+fn decode_reader<R: std::io::BufRead + std::io::Seek>(
+    reader: ImageReader<R>,
+    path: &Path,
+) -> Result<DynamicImage> {
+    reader.decode().map_err(|error| match error {
+        ImageError::Unsupported(ref unsupported) => match unsupported.kind() {
+            UnsupportedErrorKind::Format(ImageFormatHint::Exact(format)) => {
+                Error::UnsupportedFormat {
+                    path: path.to_owned(),
+                    format,
+                }
+            }
+            _ => Error::Image(error),
+        },
+        _ => Error::Image(error),
+    })
 }
 
-fn encode_ico_frame(buffer: &[u8], size: u32) -> Result<IcoFrame<'static>> {
-    let color_type = ExtendedColorType::Rgba8;
-    let mut encoded = Vec::new();
-    PngEncoder::new(Cursor::new(&mut encoded)).write_image(buffer, size, size, color_type)?;
-    Ok(IcoFrame::with_encoded(encoded, size, size, color_type)?)
+fn skipped_frame(size: u32, error: &Error) -> Diagnostic {
+    Diagnostic::SkippedFrame {
+        size,
+        reason: error.to_string(),
+    }
+}
+
+fn is_square(image: &DynamicImage) -> bool {
+    image.width() == image.height()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filesystem::MemoryFs;
+
+    #[test]
+    fn layouts_are_built_without_skipping_frames() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut layout = IcoBuilder::default();
+        layout
+            .fs(fs.clone())
+            .sizes(&[16, 32])
+            .allow_upscaling(true)
+            .add_source_bytes(encode_png(&RgbaImage::new(16, 16)).unwrap());
+        layout.build_file("layout.ico").unwrap();
+
+        let mut builder = IcoBuilder::default();
+        builder
+            .fs(fs.clone())
+            .best_effort(true)
+            .add_source_bytes(encode_png(&RgbaImage::new(16, 16)).unwrap());
+        assert!(matches!(
+            builder.build_file_with_layout("layout.ico", "icon.ico"),
+            Err(Error::MissingIconSize(32))
+        ));
+        builder.allow_upscaling(true);
+        builder
+            .build_file_with_layout("layout.ico", "icon.ico")
+            .unwrap();
+        let icon = IcoFile::parse(&fs.read(Path::new("icon.ico")).unwrap()).unwrap();
+        assert_eq!(icon.entries().len(), 2);
+    }
+
+    #[test]
+    fn in_memory_sources_report_unsupported_formats() {
+        let webp =
+            b"RIFF\x1a\0\0\0WEBPVP8L\x0d\0\0\0\x2f\0\0\0\x10\x07\x10\x11\x11\x88\x88\xfe\x07\0";
+        let error = IcoBuilder::default()
+            .add_source_bytes(&webp[..])
+            .encode_ico()
+            .unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                Error::UnsupportedFormat { path, format: ImageFormat::WebP }
+                    if path.as_os_str().is_empty()
+            ),
+            "{error:?}"
+        );
+
+        #[cfg(not(feature = "heif"))]
+        {
+            let heif = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+            let error = IcoBuilder::default()
+                .add_source_bytes(&heif[..])
+                .encode_ico()
+                .unwrap_err();
+            assert!(
+                matches!(
+                    &error,
+                    Error::UnsupportedFeatureFormat { path, format: FeatureFormat::Heif }
+                        if path.as_os_str().is_empty()
+                ),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn frames_changed_by_hooks_are_not_cached() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let fs = Arc::new(MemoryFs::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut builder = IcoBuilder::default();
+        builder
+            .fs(fs.clone())
+            .sizes(&[16, 32])
+            .cache_dir("cache")
+            .add_source_image(RgbaImage::new(64, 64));
+
+        let cached = |report: &BuildReport| {
+            report
+                .frames
+                .iter()
+                .filter(|frame| matches!(frame.origin, FrameOrigin::Resized { cached: true, .. }))
+                .count()
+        };
+        assert_eq!(cached(&builder.build_file("icon.ico").unwrap()), 0);
+        assert_eq!(cached(&builder.build_file("icon.ico").unwrap()), 2);
+
+        let counter = calls.clone();
+        builder.after_resize(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(cached(&builder.build_file("icon.ico").unwrap()), 0);
+        assert_eq!(cached(&builder.build_file("icon.ico").unwrap()), 0);
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+        // The ICO file and the 2 frames cached before the hook was added.
+        assert_eq!(fs.paths().len(), 3);
+    }
+
+    #[test]
+    fn sources_are_decoded_in_order_on_limited_threads() {
+        let threads = thread::available_parallelism().map_or(1, usize::from);
+        let mut builder = IcoBuilder::default();
+        for width in 1..=2 * threads as u32 + 1 {
+            builder.add_source_bytes(encode_png(&RgbaImage::new(width, width)).unwrap());
+        }
+        let widths: Vec<_> = builder
+            .decode_icons()
+            .unwrap()
+            .iter()
+            .map(|image| image.width())
+            .collect();
+        assert_eq!(widths, (1..=2 * threads as u32 + 1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn provenance_hashes_the_decoded_sources() {
+        let fs = Arc::new(MemoryFs::default());
+        let original = encode_png(&RgbaImage::new(32, 32)).unwrap();
+        fs.write(Path::new("icon.png"), &original).unwrap();
+        let mut builder = IcoBuilder::default();
+        builder
+            .fs(fs.clone())
+            .sizes(&[16, 32])
+            .provenance(Provenance::Sources)
+            .add_source_file("icon.png")
+            .decode_sources()
+            .unwrap();
+        // Changing the file after decoding doesn't change the record.
+        fs.write(
+            Path::new("icon.png"),
+            &encode_png(&RgbaImage::new(48, 48)).unwrap(),
+        )
+        .unwrap();
+
+        let (data, _) = builder.encode_ico().unwrap();
+        let icon = IcoFile::parse(&data).unwrap();
+        let largest = icon
+            .entries()
+            .iter()
+            .max_by_key(|entry| entry.width())
+            .unwrap();
+        let record = String::from_utf8_lossy(largest.data()).into_owned();
+        let hash = format!(
+            "fnv1a64:{:016x}",
+            Fnv1a::default().write(&original).finish()
+        );
+        assert!(record.contains(&hash), "{record}");
+        assert!(record.contains(r#""path": "icon.png""#), "{record}");
+    }
+
+    #[test]
+    fn estimates_match_the_built_files() {
+        // A flat square on a transparent background, which compresses predictably.
+        let source = RgbaImage::from_fn(256, 256, |x, y| {
+            if (64..192).contains(&x) && (64..192).contains(&y) {
+                Rgba([200, 40, 40, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let configure: [fn(&mut IcoBuilder); 4] = [
+            |_| {},
+            |builder| {
+                builder.indexed_png(true);
+            },
+            |builder| {
+                builder.xp_compatible(true);
+            },
+            |builder| {
+                builder.xp_compatible(true).add_encoded_frame(
+                    encode_png(&RgbaImage::new(16, 16)).unwrap(),
+                    16,
+                    16,
+                );
+            },
+        ];
+        for configure in configure {
+            let mut builder = IcoBuilder::default();
+            builder
+                .sizes(&[16, 32, 48, 256])
+                .add_source_image(source.clone());
+            configure(&mut builder);
+            let estimate = builder.estimate_output_size().unwrap();
+            let (built, _) = builder.encode_ico().unwrap();
+            let icon = IcoFile::parse(&built).unwrap();
+            assert_eq!(estimate.frames.len(), icon.entries().len());
+            for (frame, entry) in estimate.frames.iter().zip(icon.entries()) {
+                assert_eq!(frame.size, entry.width());
+                let (estimated, actual) = (frame.bytes as f64, entry.data().len() as f64);
+                if entry.data().starts_with(png::PNG_SIGNATURE) {
+                    assert!((estimated - actual).abs() <= 0.05 * actual, "{estimate:?}");
+                } else {
+                    // BMPs are estimated with the largest possible palette.
+                    assert!(estimated >= actual, "{estimate:?}");
+                }
+            }
+        }
+    }
 }
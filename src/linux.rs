@@ -0,0 +1,91 @@
+//! App icons for Linux desktops.
+
+use crate::export::write_png;
+use crate::{IcoBuilder, Result};
+use std::path::{Path, PathBuf};
+
+/// The sizes of the `hicolor` theme that desktop environments commonly look up.
+const SIZES: &[u32] = &[16, 22, 24, 32, 48, 64, 96, 128, 256, 512];
+
+/// Exports app icons in the layout of the freedesktop.org `hicolor` icon theme
+/// from the sources of an [`IcoBuilder`], as `hicolor/{size}x{size}/apps/{name}.png`.
+/// The icon is referenced by its name as `Icon` in `.desktop` files.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::linux::LinuxExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-512x512.png");
+/// LinuxExport::default()
+///     .name("org.example.App")
+///     .build(&icon, "pkg/usr/share/icons")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LinuxExport {
+    name: String,
+}
+
+impl Default for LinuxExport {
+    fn default() -> Self {
+        LinuxExport {
+            name: "app-icon".to_owned(),
+        }
+    }
+}
+
+impl LinuxExport {
+    /// Customizes the name of the icon, usually the application ID. Defaults to `app-icon`.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut LinuxExport {
+        self.name = name.into();
+        self
+    }
+
+    /// Writes the icons into the `hicolor` theme in `icons_dir`, e.g. `/usr/share/icons`,
+    /// and returns the paths of the written files.
+    pub fn build(&self, icon: &IcoBuilder, icons_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let theme_dir = icons_dir.as_ref().join("hicolor");
+        let file_name = format!("{}.png", self.name);
+        icon.render(SIZES)?
+            .iter()
+            .zip(SIZES)
+            .map(|(frame, size)| {
+                let dir = theme_dir.join(format!("{size}x{size}")).join("apps");
+                icon.create_dir_all(&dir)?;
+                write_png(icon, &dir, &file_name, frame)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::{Rgba, RgbaImage};
+    use std::sync::Arc;
+
+    #[test]
+    fn writes_the_hicolor_layout() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone()).add_source_image(RgbaImage::from_pixel(
+            512,
+            512,
+            Rgba([0, 120, 215, 255]),
+        ));
+        let paths = LinuxExport::default()
+            .name("org.example.App")
+            .build(&icon, "icons")
+            .unwrap();
+        assert_eq!(paths.len(), SIZES.len());
+        for (path, size) in paths.iter().zip(SIZES) {
+            let expected: PathBuf = ["icons", "hicolor", &format!("{size}x{size}"), "apps"]
+                .iter()
+                .collect();
+            assert_eq!(*path, expected.join("org.example.App.png"));
+            let frame = image::load_from_memory(&fs.read(path).unwrap()).unwrap();
+            assert_eq!(frame.width(), *size);
+        }
+    }
+}
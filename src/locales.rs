@@ -0,0 +1,156 @@
+use crate::transform::Transform;
+use crate::{BatchReport, Error, IcoBuilder, Result};
+use std::path::Path;
+use std::sync::Arc;
+use std::{panic, thread};
+
+/// A set of ICO files that only differ by a locale-specific layer, e.g. a translated wordmark.
+/// Each locale adds a transformation, usually a [`Layer`](crate::transform::Layer),
+/// after the transformations of the base configuration.
+///
+/// The sources are decoded only once and the icons are built in parallel.
+///
+/// ```no_run
+/// # use ico_builder::{IcoBuilder, LocaleVariants};
+/// # use ico_builder::transform::Layer;
+/// let mut base = IcoBuilder::default();
+/// base.add_source_file("app-icon-256x256.png");
+///
+/// // Writes app-icon.de.ico and app-icon.fr.ico
+/// LocaleVariants::new(base)
+///     .locale("de", Layer::new(image::open("wordmark-de.png")?))
+///     .locale("fr", Layer::new(image::open("wordmark-fr.png")?))
+///     .build("app-icon.ico")?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocaleVariants {
+    base: IcoBuilder,
+    locales: Vec<(String, Arc<dyn Transform>)>,
+}
+
+impl LocaleVariants {
+    /// Creates locale variants of the configuration of `base`.
+    pub fn new(base: IcoBuilder) -> LocaleVariants {
+        LocaleVariants {
+            base,
+            locales: Vec::new(),
+        }
+    }
+
+    /// Adds a locale, such as `de` or `pt-BR`, whose icon is transformed by `overlay`.
+    /// Adding a locale again replaces its overlay.
+    pub fn locale(
+        &mut self,
+        locale: impl Into<String>,
+        overlay: impl Transform + 'static,
+    ) -> &mut LocaleVariants {
+        let locale = locale.into();
+        let overlay: Arc<dyn Transform> = Arc::new(overlay);
+        match self.locales.iter_mut().find(|(name, _)| *name == locale) {
+            Some((_, existing)) => *existing = overlay,
+            None => self.locales.push((locale, overlay)),
+        }
+        self
+    }
+
+    /// The locales in the order they were added.
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.locales.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Builds an ICO file per locale next to `output_file_path`,
+    /// inserting the locale before the extension, e.g. `app-icon.de.ico`.
+    pub fn build(&self, output_file_path: impl AsRef<Path>) -> Result<BatchReport> {
+        let output_file_path = output_file_path.as_ref();
+        let name = output_file_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let base = self.base.with_decoded_sources()?;
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .locales
+                .iter()
+                .map(|(locale, overlay)| {
+                    let path = output_file_path.with_file_name(format!("{name}.{locale}.ico"));
+                    let mut icon = base.clone();
+                    icon.transforms.push(overlay.clone());
+                    scope.spawn(move || match icon.build_file(&path) {
+                        Ok(report) => Ok((path, report)),
+                        Err(error) => Err(Error::BatchIcon {
+                            path,
+                            error: Box::new(error),
+                        }),
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+        let icons = base.collect(results.into_iter())?;
+        Ok(BatchReport { icons })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::{Rgba, RgbaImage};
+
+    #[derive(Debug)]
+    struct Fill(Rgba<u8>);
+
+    impl Transform for Fill {
+        fn apply(&self, frame: &mut RgbaImage) {
+            frame.pixels_mut().for_each(|pixel| *pixel = self.0);
+        }
+    }
+
+    #[test]
+    fn adding_a_locale_again_replaces_its_overlay() {
+        let mut variants = LocaleVariants::new(IcoBuilder::default());
+        variants
+            .locale("de", Fill(Rgba([0, 0, 0, 0xff])))
+            .locale("fr", Fill(Rgba([0, 0, 0, 0xff])))
+            .locale("de", Fill(Rgba([0xff, 0, 0, 0xff])));
+        assert_eq!(variants.locales().collect::<Vec<_>>(), ["de", "fr"]);
+        assert_eq!(
+            format!("{:?}", variants.locales[0].1),
+            "Fill(Rgba([255, 0, 0, 255]))"
+        );
+    }
+
+    #[test]
+    fn builds_an_icon_per_locale() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut base = IcoBuilder::default();
+        base.fs(fs.clone())
+            .sizes(&[16, 32])
+            .add_source_image(RgbaImage::new(32, 32));
+        let (red, blue) = (Rgba([0xff, 0, 0, 0xff]), Rgba([0, 0, 0xff, 0xff]));
+        let report = LocaleVariants::new(base)
+            .locale("de", Fill(red))
+            .locale("pt-BR", Fill(blue))
+            .build("icons/app-icon.ico")
+            .unwrap();
+        let paths: Vec<_> = report.icons.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("icons/app-icon.de.ico"),
+                Path::new("icons/app-icon.pt-BR.ico")
+            ]
+        );
+        for (path, color) in paths.iter().zip([red, blue]) {
+            let icon = image::load_from_memory(&fs.read(path).unwrap())
+                .unwrap()
+                .into_rgba8();
+            assert_eq!(icon.width(), 32);
+            assert!(icon.pixels().all(|pixel| *pixel == color));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! App icons for macOS apps.
+
+use crate::{encode_png, IcoBuilder, Result};
+use std::path::{Path, PathBuf};
+
+/// PNG entries of ICNS files as `(type, size in pixels)`. Retina entries have twice
+/// the pixels of their size in points and share the rendered frames with the others.
+const ENTRIES: &[(&[u8; 4], u32)] = &[
+    (b"icp4", 16),
+    (b"icp5", 32),
+    (b"ic11", 32),
+    (b"ic12", 64),
+    (b"ic07", 128),
+    (b"ic13", 256),
+    (b"ic08", 256),
+    (b"ic14", 512),
+    (b"ic09", 512),
+    (b"ic10", 1024),
+];
+
+/// The size of the file header and of each entry header.
+const HEADER_SIZE: usize = 8;
+
+/// Exports an ICNS file for macOS app bundles from the sources of an [`IcoBuilder`],
+/// with PNG frames from 16px to 1024px, including the Retina variants.
+/// The file is referenced as `CFBundleIconFile` in `Info.plist`.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::macos::MacosExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-1024x1024.png");
+/// MacosExport::default().build(&icon, "App.app/Contents/Resources")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MacosExport {
+    name: String,
+}
+
+impl Default for MacosExport {
+    fn default() -> Self {
+        MacosExport {
+            name: "AppIcon".to_owned(),
+        }
+    }
+}
+
+impl MacosExport {
+    /// Customizes the file name of the icon without the `.icns` extension. Defaults to `AppIcon`.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut MacosExport {
+        self.name = name.into();
+        self
+    }
+
+    /// Writes the ICNS file to `output_dir` and returns the path of the written file.
+    pub fn build(&self, icon: &IcoBuilder, output_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let mut sizes: Vec<_> = ENTRIES.iter().map(|&(_, size)| size).collect();
+        sizes.dedup();
+        let frames = icon
+            .render(&sizes)?
+            .iter()
+            .map(encode_png)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut entries = Vec::new();
+        for &(entry_type, size) in ENTRIES {
+            let index = sizes
+                .iter()
+                .position(|&s| s == size)
+                .expect("sizes of all entries are rendered");
+            let data = &frames[index];
+            entries.extend_from_slice(entry_type);
+            entries.extend_from_slice(&((HEADER_SIZE + data.len()) as u32).to_be_bytes());
+            entries.extend_from_slice(data);
+        }
+        let mut icns = Vec::with_capacity(HEADER_SIZE + entries.len());
+        icns.extend_from_slice(b"icns");
+        icns.extend_from_slice(&((HEADER_SIZE + entries.len()) as u32).to_be_bytes());
+        icns.extend_from_slice(&entries);
+
+        let output_dir = output_dir.as_ref();
+        icon.create_dir_all(output_dir)?;
+        let path = output_dir.join(format!("{}.icns", self.name));
+        icon.write_file(&path, &icns)?;
+        Ok(vec![path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::{Rgba, RgbaImage};
+    use std::sync::Arc;
+
+    #[test]
+    fn writes_icns_entries() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .allow_upscaling(true)
+            .add_source_image(RgbaImage::from_pixel(16, 16, Rgba([0, 120, 215, 255])));
+        let paths = MacosExport::default()
+            .name("App")
+            .build(&icon, "Resources")
+            .unwrap();
+        assert_eq!(paths, [Path::new("Resources").join("App.icns")]);
+
+        let icns = fs.read(&paths[0]).unwrap();
+        assert_eq!(&icns[..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(icns[4..8].try_into().unwrap()) as usize,
+            icns.len()
+        );
+        let mut rest = &icns[HEADER_SIZE..];
+        for &(entry_type, size) in ENTRIES {
+            assert_eq!(&rest[..4], entry_type);
+            let length = u32::from_be_bytes(rest[4..8].try_into().unwrap()) as usize;
+            let frame = image::load_from_memory(&rest[HEADER_SIZE..length]).unwrap();
+            assert_eq!((frame.width(), frame.height()), (size, size));
+            rest = &rest[length..];
+        }
+        assert!(rest.is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! A JSON manifest of the files written by exporters, for downstream packaging steps.
+
+use crate::hash::Fnv1a;
+use crate::report::json_string;
+use crate::{IcoBuilder, Result};
+use image::ImageReader;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Writes a manifest listing each file with its path relative to the manifest,
+/// its size in bytes, its dimensions if it is an image, and an FNV-1a hash of its contents.
+pub(crate) fn write_manifest(
+    icon: &IcoBuilder,
+    paths: &[PathBuf],
+    manifest_path: &Path,
+) -> Result<()> {
+    let base = manifest_path.parent().unwrap_or(Path::new(""));
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let contents = icon.fs.read(path)?;
+        let dimensions = match ImageReader::new(Cursor::new(&contents))
+            .with_guessed_format()?
+            .into_dimensions()
+        {
+            Ok((width, height)) => format!("{{ \"width\": {width}, \"height\": {height} }}"),
+            Err(_) => "null".to_owned(),
+        };
+        files.push(format!(
+            "    {{\n      \
+               \"path\": {path},\n      \
+               \"size\": {size},\n      \
+               \"dimensions\": {dimensions},\n      \
+               \"hash\": \"fnv1a64:{hash:016x}\"\n    \
+             }}",
+            path = json_string(&relative_path(path, base)),
+            size = contents.len(),
+            hash = Fnv1a::default().write(&contents).finish(),
+        ));
+    }
+    let json = if files.is_empty() {
+        "{\n  \"files\": []\n}\n".to_owned()
+    } else {
+        format!("{{\n  \"files\": [\n{}\n  ]\n}}\n", files.join(",\n"))
+    };
+    icon.write_file(manifest_path, json.as_bytes())
+}
+
+/// The path relative to `base` with `/` as separator on all platforms,
+/// or the path as is if it is outside of `base`.
+fn relative_path(path: &Path, base: &Path) -> String {
+    let Ok(relative) = path.strip_prefix(base) else {
+        return path.to_string_lossy().into_owned();
+    };
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_png;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::RgbaImage;
+    use std::sync::Arc;
+
+    #[test]
+    fn lists_files_relative_to_the_manifest() {
+        let fs = Arc::new(MemoryFs::default());
+        let png = encode_png(&RgbaImage::new(48, 48)).unwrap();
+        fs.write(Path::new("public/icons/app-48.png"), &png)
+            .unwrap();
+        fs.write(Path::new("public/site.webmanifest"), b"{}")
+            .unwrap();
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone());
+        let paths = [
+            PathBuf::from("public/icons/app-48.png"),
+            PathBuf::from("public/site.webmanifest"),
+        ];
+        write_manifest(&icon, &paths, Path::new("public/icons.json")).unwrap();
+
+        let json = String::from_utf8(fs.read(Path::new("public/icons.json")).unwrap()).unwrap();
+        let hash = |contents: &[u8]| Fnv1a::default().write(contents).finish();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{
+  "files": [
+    {{
+      "path": "icons/app-48.png",
+      "size": {size},
+      "dimensions": {{ "width": 48, "height": 48 }},
+      "hash": "fnv1a64:{png_hash:016x}"
+    }},
+    {{
+      "path": "site.webmanifest",
+      "size": 2,
+      "dimensions": null,
+      "hash": "fnv1a64:{json_hash:016x}"
+    }}
+  ]
+}}
+"#,
+                size = png.len(),
+                png_hash = hash(&png),
+                json_hash = hash(b"{}"),
+            )
+        );
+
+        write_manifest(&icon, &[], Path::new("empty.json")).unwrap();
+        assert_eq!(
+            fs.read(Path::new("empty.json")).unwrap(),
+            b"{\n  \"files\": []\n}\n"
+        );
+    }
+
+    #[test]
+    fn paths_outside_of_the_base_are_kept() {
+        let base = Path::new("public");
+        assert_eq!(
+            relative_path(&Path::new("public").join("a").join("b.png"), base),
+            "a/b.png"
+        );
+        assert_eq!(
+            relative_path(Path::new("assets/b.png"), base),
+            "assets/b.png"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Converting legacy ICO files to PNG-compressed entries.
+
+use crate::reader::{IcoEntry, IcoFile};
+use crate::{encode_png, Result};
+use std::path::Path;
+
+/// Re-encodes the uncompressed BMP entries of legacy ICO files as PNGs, which are supported
+/// since Windows Vista and typically much smaller, especially at 256px.
+/// PNG entries are kept as they are.
+///
+/// ```no_run
+/// # use ico_builder::modernize::Modernizer;
+/// Modernizer::default()
+///     .drop_legacy_bit_depths(true)
+///     .modernize_file("legacy.ico")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Modernizer {
+    drop_legacy_bit_depths: bool,
+}
+
+impl Modernizer {
+    /// Drops entries with less than 32 bits per pixel if there is a 32-bit entry of the same size.
+    /// Such entries were only used by Windows versions before XP. Defaults to `false`.
+    pub fn drop_legacy_bit_depths(&mut self, drop_legacy_bit_depths: bool) -> &mut Modernizer {
+        self.drop_legacy_bit_depths = drop_legacy_bit_depths;
+        self
+    }
+
+    /// Converts the entries of an ICO file, keeping them in directory order.
+    pub fn modernize(&self, ico: &IcoFile) -> Result<IcoFile> {
+        let entries = ico.entries();
+        let mut modernized = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let is_legacy = entry.bit_count() < 32
+                && entries.iter().any(|other| {
+                    other.bit_count() >= 32
+                        && (other.width(), other.height()) == (entry.width(), entry.height())
+                });
+            if self.drop_legacy_bit_depths && is_legacy {
+                continue;
+            }
+            if entry.is_png() {
+                modernized.push(entry.clone());
+                continue;
+            }
+            let png = encode_png(&entry.decode()?)?;
+            modernized.push(IcoEntry::new(entry.width(), entry.height(), 0, 1, 32, &png));
+        }
+        Ok(IcoFile::from_entries(modernized))
+    }
+
+    /// Converts the ICO file at `path` in place.
+    /// Returns how many bytes smaller the file became.
+    pub fn modernize_file(&self, path: impl AsRef<Path>) -> Result<u64> {
+        let path = path.as_ref();
+        let original = std::fs::read(path)?;
+        let modernized = self.modernize(&IcoFile::parse(&original)?)?.to_bytes();
+        std::fs::write(path, &modernized)?;
+        Ok(original.len().saturating_sub(modernized.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// An uncompressed BMP entry with a transparency mask that hides nothing, in the layout
+    /// of ICO files: without the file header and with the mask included in the height.
+    fn bmp(size: u32, bit_count: u16, color: Rgba<u8>) -> IcoEntry {
+        let Rgba([r, g, b, a]) = color;
+        let mut data = Vec::new();
+        let palette: &[u8] = if bit_count == 8 { &[b, g, r, 0] } else { &[] };
+        for value in [40, size, 2 * size] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bit_count.to_le_bytes());
+        for value in [0, 0, 0, 0, palette.len() as u32 / 4, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(palette);
+        for _ in 0..size {
+            for _ in 0..size {
+                match bit_count {
+                    8 => data.push(0),
+                    _ => data.extend_from_slice(&[b, g, r, a]),
+                }
+            }
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+        }
+        data.resize(data.len() + (size.div_ceil(32) * 4 * size) as usize, 0);
+        IcoEntry::new(size, size, 0, 1, bit_count, &data)
+    }
+
+    /// A legacy icon with 32-bit and 8-bit BMP entries of each size.
+    fn legacy() -> IcoFile {
+        let color = Rgba([0, 120, 215, 255]);
+        IcoFile::from_entries(
+            [16, 32]
+                .into_iter()
+                .flat_map(|size| [bmp(size, 32, color), bmp(size, 8, color)])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn re_encodes_bmp_entries_as_png() {
+        let legacy = legacy();
+        let modernized = Modernizer::default().modernize(&legacy).unwrap();
+        assert_eq!(modernized.entries().len(), legacy.entries().len());
+        for (modernized, legacy) in modernized.entries().iter().zip(legacy.entries()) {
+            assert!(modernized.is_png());
+            assert_eq!(modernized.width(), legacy.width());
+            assert_eq!(modernized.decode().unwrap(), legacy.decode().unwrap());
+        }
+        // PNG entries are kept.
+        let again = Modernizer::default().modernize(&modernized).unwrap();
+        assert_eq!(again.to_bytes(), modernized.to_bytes());
+    }
+
+    #[test]
+    fn drops_legacy_bit_depths() {
+        let modernized = Modernizer::default()
+            .drop_legacy_bit_depths(true)
+            .modernize(&legacy())
+            .unwrap();
+        let entries: Vec<_> = modernized
+            .entries()
+            .iter()
+            .map(|entry| (entry.width(), entry.bit_count()))
+            .collect();
+        assert_eq!(entries, [(16, 32), (32, 32)]);
+    }
+}
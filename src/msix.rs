@@ -0,0 +1,205 @@
+//! Visual assets for Windows app packages (MSIX).
+
+use crate::export::{center_on_canvas, write_png, CONTRAST_VARIANTS};
+use crate::transform::Silhouette;
+use crate::{IcoBuilder, Result};
+use std::path::{Path, PathBuf};
+
+/// Logos as `(file name prefix, size at 100% scale, artwork fraction)`.
+/// Tile artwork is centered on a transparent canvas with padding, while the other logos fill the whole image.
+const LOGOS: &[(&str, u32, f32)] = &[
+    ("Square44x44Logo", 44, 1.0),
+    ("Square150x150Logo", 150, 0.5),
+    ("StoreLogo", 50, 1.0),
+];
+
+/// The `targetsize-*` sizes of the app list icon, used by the taskbar, Start menu and File Explorer.
+const TARGET_SIZES: &[u32] = &[16, 20, 24, 30, 32, 36, 40, 48, 60, 64, 72, 80, 96, 256];
+
+/// Qualifiers of the unplated app list icons, which are shown on the taskbar without a colored plate.
+const UNPLATED_QUALIFIERS: &[&str] = &["altform-unplated", "altform-lightunplated"];
+
+/// Exports the visual assets of a Windows app package from the sources of an [`IcoBuilder`]:
+/// * `Square44x44Logo.scale-*.png` and `Square44x44Logo.targetsize-*.png`
+/// * `Square150x150Logo.scale-*.png`
+/// * `StoreLogo.scale-*.png`
+/// * optionally, [unplated](MsixExport::unplated) and [high contrast](MsixExport::high_contrast) variants
+///
+/// The assets can be referenced in `Package.appxmanifest` without the qualifiers,
+/// e.g. as `Assets\Square44x44Logo.png`.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::msix::MsixExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-512x512.png");
+/// MsixExport::default().build(&icon, "Assets")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MsixExport {
+    scales: Vec<u32>,
+    unplated: bool,
+    high_contrast: bool,
+}
+
+impl Default for MsixExport {
+    fn default() -> Self {
+        MsixExport {
+            scales: vec![100, 125, 150, 200, 400],
+            unplated: false,
+            high_contrast: false,
+        }
+    }
+}
+
+impl MsixExport {
+    /// Customizes the scale factors in percent for which `scale-*` assets are written.
+    /// Defaults to `100`, `125`, `150`, `200` and `400`.
+    pub fn scales(&mut self, scales: &[u32]) -> &mut MsixExport {
+        self.scales = scales.to_owned();
+        self
+    }
+
+    /// Enables writing `altform-unplated` and `altform-lightunplated` variants of the
+    /// `targetsize-*` assets. The artwork is used as is, so it should look good on both
+    /// dark and light taskbars. Defaults to `false`.
+    pub fn unplated(&mut self, unplated: bool) -> &mut MsixExport {
+        self.unplated = unplated;
+        self
+    }
+
+    /// Enables writing `contrast-black` and `contrast-white` variants of all assets,
+    /// which turn the artwork into a white or black [`Silhouette`] respectively.
+    /// Defaults to `false`.
+    pub fn high_contrast(&mut self, high_contrast: bool) -> &mut MsixExport {
+        self.high_contrast = high_contrast;
+        self
+    }
+
+    /// Writes all assets to `output_dir` and returns the paths of the written files.
+    pub fn build(&self, icon: &IcoBuilder, output_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let output_dir = output_dir.as_ref();
+        let mut written = Vec::new();
+
+        self.build_logos(icon, output_dir, "", &mut written)?;
+        build_target_sizes(icon, output_dir, "", &mut written)?;
+
+        if self.unplated {
+            for qualifier in UNPLATED_QUALIFIERS {
+                build_target_sizes(icon, output_dir, &format!("_{qualifier}"), &mut written)?;
+            }
+        }
+
+        if self.high_contrast {
+            for &(qualifier, color) in CONTRAST_VARIANTS {
+                let mut icon = icon.clone();
+                icon.add_transform(Silhouette::new(color));
+                let qualifier = format!("_{qualifier}");
+                self.build_logos(&icon, output_dir, &qualifier, &mut written)?;
+                build_target_sizes(&icon, output_dir, &qualifier, &mut written)?;
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn build_logos(
+        &self,
+        icon: &IcoBuilder,
+        output_dir: &Path,
+        qualifier: &str,
+        written: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for &(prefix, base_size, artwork_fraction) in LOGOS {
+            let sizes: Vec<_> = self
+                .scales
+                .iter()
+                .map(|&scale| scaled_size(base_size, scale))
+                .collect();
+            let artwork_sizes: Vec<_> = sizes
+                .iter()
+                .map(|&size| (size as f32 * artwork_fraction).round() as u32)
+                .collect();
+            let artworks = icon.render(&artwork_sizes)?;
+            for ((artwork, &size), scale) in artworks.iter().zip(&sizes).zip(&self.scales) {
+                let name = format!("{prefix}.scale-{scale}{qualifier}.png");
+                let image = center_on_canvas(artwork, size, size);
+                written.push(write_png(icon, output_dir, &name, &image)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn build_target_sizes(
+    icon: &IcoBuilder,
+    output_dir: &Path,
+    qualifier: &str,
+    written: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for (frame, size) in icon.render(TARGET_SIZES)?.iter().zip(TARGET_SIZES) {
+        let name = format!("Square44x44Logo.targetsize-{size}{qualifier}.png");
+        written.push(write_png(icon, output_dir, &name, frame)?);
+    }
+    Ok(())
+}
+
+/// The pixel size of an asset at the given scale factor, rounded to the nearest pixel.
+fn scaled_size(base_size: u32, scale: u32) -> u32 {
+    (base_size * scale + 50) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::{Rgba, RgbaImage};
+    use std::sync::Arc;
+
+    #[test]
+    fn sizes_are_rounded_to_the_nearest_pixel() {
+        assert_eq!(scaled_size(44, 100), 44);
+        assert_eq!(scaled_size(44, 125), 55);
+        assert_eq!(scaled_size(150, 150), 225);
+        assert_eq!(scaled_size(50, 125), 63);
+    }
+
+    #[test]
+    fn writes_the_enabled_variants() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .allow_upscaling(true)
+            .add_source_image(RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255])));
+        let written = MsixExport::default()
+            .scales(&[100, 200])
+            .unplated(true)
+            .high_contrast(true)
+            .build(&icon, "Assets")
+            .unwrap();
+        let logos = LOGOS.len() * 2 + TARGET_SIZES.len();
+        assert_eq!(
+            written.len(),
+            logos + 2 * TARGET_SIZES.len() + CONTRAST_VARIANTS.len() * logos
+        );
+
+        let load = |name: &str| {
+            image::load_from_memory(&fs.read(&Path::new("Assets").join(name)).unwrap())
+                .unwrap()
+                .into_rgba8()
+        };
+        let tile = load("Square150x150Logo.scale-200.png");
+        assert_eq!(tile.dimensions(), (300, 300));
+        // Tile artwork covers half of the tile.
+        assert_eq!(tile.get_pixel(70, 150)[3], 0);
+        assert_eq!(*tile.get_pixel(150, 150), Rgba([255, 0, 0, 255]));
+        let unplated = load("Square44x44Logo.targetsize-24_altform-unplated.png");
+        assert_eq!(unplated.dimensions(), (24, 24));
+        for &(qualifier, color) in CONTRAST_VARIANTS {
+            let logo = load(&format!("StoreLogo.scale-100_{qualifier}.png"));
+            assert_eq!(logo.dimensions(), (50, 50));
+            assert_eq!(*logo.get_pixel(25, 25), color);
+        }
+    }
+}
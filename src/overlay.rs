@@ -0,0 +1,109 @@
+//! Generators for the small badge icons used as Windows taskbar overlays.
+
+use crate::transform::{SuperellipseMask, Transform};
+use crate::{font, IcoBuilder, IconSizes};
+use image::{Rgba, RgbaImage};
+
+/// A taskbar overlay badge, e.g. for status indicators or unread counts.
+///
+/// ```no_run
+/// # use ico_builder::overlay::Badge;
+/// # use ico_builder::Rgba;
+/// Badge::Dot(Rgba([0, 200, 80, 255]))
+///     .builder()
+///     .build_file("status-online.ico")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Badge {
+    /// A filled circle in the given color.
+    Dot(Rgba<u8>),
+    /// A circle with a number. Numbers above 9 are shown as `9+`.
+    Count {
+        count: u32,
+        background: Rgba<u8>,
+        foreground: Rgba<u8>,
+    },
+}
+
+impl Badge {
+    /// Renders the badge at the given size.
+    pub fn render(&self, size: u32) -> RgbaImage {
+        let (background, label) = match *self {
+            Badge::Dot(color) => (color, None),
+            Badge::Count {
+                count,
+                background,
+                foreground,
+            } => (background, Some((count_label(count), foreground))),
+        };
+
+        let mut image = RgbaImage::from_pixel(size, size, background);
+        if let Some((text, color)) = label {
+            let scale = (size / 16).max(1);
+            let x = (i64::from(size) - i64::from(font::text_width(&text, scale))) / 2;
+            let y = (i64::from(size) - i64::from(font::text_height(scale))) / 2;
+            font::draw_text(&mut image, &text, (x, y), scale, color);
+        }
+        SuperellipseMask::new(2.0).apply(&mut image);
+        image
+    }
+
+    /// Creates a builder with the [`IconSizes::OVERLAY`] sizes and the badge rendered
+    /// at each of these sizes as sources. Further transformations can be added to the builder.
+    pub fn builder(&self) -> IcoBuilder {
+        let mut builder = IcoBuilder::default();
+        builder.sizes(IconSizes::OVERLAY);
+        for &size in IconSizes::OVERLAY.iter() {
+            builder.add_source_image(self.render(size));
+        }
+        builder
+    }
+}
+
+fn count_label(count: u32) -> String {
+    if count > 9 {
+        "9+".to_owned()
+    } else {
+        count.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_above_nine_are_abbreviated() {
+        assert_eq!(count_label(0), "0");
+        assert_eq!(count_label(9), "9");
+        assert_eq!(count_label(10), "9+");
+    }
+
+    #[test]
+    fn renders_round_badges() {
+        let green = Rgba([0, 200, 80, 255]);
+        let dot = Badge::Dot(green).render(16);
+        assert_eq!(*dot.get_pixel(8, 8), green);
+        assert_eq!(dot.get_pixel(0, 0)[3], 0);
+
+        let white = Rgba([255, 255, 255, 255]);
+        let count = Badge::Count {
+            count: 3,
+            background: Rgba([200, 0, 0, 255]),
+            foreground: white,
+        };
+        let label_pixels = |size| {
+            count
+                .render(size)
+                .pixels()
+                .filter(|pixel| **pixel == white)
+                .count()
+        };
+        // The glyph of `3` has 11 pixels, which are scaled with the badge.
+        assert_eq!(label_pixels(16), 11);
+        assert_eq!(label_pixels(32), 44);
+        assert_eq!(count.builder().sizes, IconSizes::OVERLAY);
+    }
+}
@@ -0,0 +1,162 @@
+//! Generators for placeholder icons with initials, e.g. for internal tools or tenants without a logo.
+
+use crate::hash::Fnv1a;
+use crate::transform::{SuperellipseMask, Transform};
+use crate::{font, IcoBuilder, IconSizes};
+use image::{Rgba, RgbaImage};
+
+/// A placeholder icon: one or two initials on a colored background.
+///
+/// The background color is derived from a seed, the name by default,
+/// so the same name always gets the same color and different names are easy to tell apart.
+///
+/// ```no_run
+/// # use ico_builder::placeholder::InitialsIcon;
+/// InitialsIcon::new("Build Monitor")
+///     .seed("tenant-4711")
+///     .builder()
+///     .build_file("build-monitor.ico")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitialsIcon {
+    initials: String,
+    background: Rgba<u8>,
+    foreground: Rgba<u8>,
+}
+
+impl InitialsIcon {
+    /// Creates a placeholder with the initials of the first two words of `name`, e.g. `BM` for
+    /// `Build Monitor`. Only ASCII letters and digits are shown, and `?` if there are none.
+    pub fn new(name: &str) -> InitialsIcon {
+        let initials: String = name
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter_map(|word| word.chars().find(char::is_ascii_alphanumeric))
+            .take(2)
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        InitialsIcon {
+            initials: if initials.is_empty() {
+                "?".to_owned()
+            } else {
+                initials
+            },
+            background: seed_color(name),
+            foreground: Rgba([0xff, 0xff, 0xff, 0xff]),
+        }
+    }
+
+    /// Derives the background color from `seed` instead of the name,
+    /// e.g. from a stable ID if names can change.
+    pub fn seed(&mut self, seed: &str) -> &mut InitialsIcon {
+        self.background = seed_color(seed);
+        self
+    }
+
+    /// Customizes the background color, instead of deriving it from the seed.
+    pub fn background(&mut self, background: Rgba<u8>) -> &mut InitialsIcon {
+        self.background = background;
+        self
+    }
+
+    /// Customizes the color of the initials. Defaults to white.
+    pub fn foreground(&mut self, foreground: Rgba<u8>) -> &mut InitialsIcon {
+        self.foreground = foreground;
+        self
+    }
+
+    /// Renders the placeholder at the given size.
+    pub fn render(&self, size: u32) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(size, size, self.background);
+        // The initials take up to 3/5 of the width and half of the height.
+        let scale = (size * 3 / 5 / font::text_width(&self.initials, 1).max(1))
+            .min(size / 2 / font::text_height(1))
+            .max(1);
+        let x = (i64::from(size) - i64::from(font::text_width(&self.initials, scale))) / 2;
+        let y = (i64::from(size) - i64::from(font::text_height(scale))) / 2;
+        font::draw_text(&mut image, &self.initials, (x, y), scale, self.foreground);
+        SuperellipseMask::new(4.0).apply(&mut image);
+        image
+    }
+
+    /// Creates a builder with the [`IconSizes::MINIMAL`] sizes and the placeholder rendered
+    /// at each of these sizes as sources. Further transformations can be added to the builder.
+    pub fn builder(&self) -> IcoBuilder {
+        let mut builder = IcoBuilder::default();
+        builder.sizes(IconSizes::MINIMAL);
+        for &size in IconSizes::MINIMAL.iter() {
+            builder.add_source_image(self.render(size));
+        }
+        builder
+    }
+}
+
+/// A saturated, medium dark color with a hue derived from the hash of `seed`,
+/// so that white initials are legible on it.
+fn seed_color(seed: &str) -> Rgba<u8> {
+    let hue = (Fnv1a::default().write(seed.as_bytes()).finish() % 360) as f32;
+    let (saturation, lightness) = (0.55, 0.45);
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f32).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f32| ((value + m) * 255.0).round() as u8;
+    Rgba([channel(r), channel(g), channel(b), 0xff])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initials_come_from_the_first_words() {
+        let initials = |name| InitialsIcon::new(name).initials;
+        assert_eq!(initials("Build Monitor"), "BM");
+        assert_eq!(initials("release-train_dashboard"), "RT");
+        assert_eq!(initials("  (admin) 2 panel"), "A2");
+        assert_eq!(initials("ümlaut"), "M");
+        assert_eq!(initials("—"), "?");
+    }
+
+    #[test]
+    fn colors_are_derived_from_the_seed() {
+        let mut icon = InitialsIcon::new("Build Monitor");
+        assert_eq!(icon.background, seed_color("Build Monitor"));
+        assert_eq!(
+            icon.seed("tenant-4711").background,
+            seed_color("tenant-4711")
+        );
+        assert_ne!(seed_color("tenant-4711"), seed_color("tenant-4712"));
+        let [r, g, b, a] = seed_color("tenant-4711").0;
+        assert_eq!(a, 0xff);
+        // Medium dark, so that white initials are legible.
+        assert!(r.max(g).max(b) < 0xe0 && r.min(g).min(b) > 0x20);
+    }
+
+    #[test]
+    fn renders_centered_initials() {
+        let icon = InitialsIcon::new("Build Monitor");
+        let image = icon.render(64);
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(*image.get_pixel(32, 2), icon.background);
+        // The corners are masked.
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        let foreground = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel == icon.foreground)
+            .map(|(x, _, _)| x);
+        let (left, right) = foreground.fold((u32::MAX, 0), |(left, right), x| {
+            (left.min(x), right.max(x))
+        });
+        // Centered up to rounding.
+        assert!((left + right).abs_diff(63) <= 1, "{left} {right}");
+        assert_eq!(icon.builder().sizes, IconSizes::MINIMAL);
+    }
+}
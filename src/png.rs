@@ -0,0 +1,295 @@
+//! Low-level editing of PNG chunks.
+
+use crate::{encode_png, Result};
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbaImage};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// The first bytes of every PNG.
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// The color type of PNGs with a palette, as stored in the IHDR chunk.
+const INDEXED_COLOR_TYPE: u8 = 3;
+
+/// Ancillary chunks that only hold metadata and don't affect how the image is displayed.
+const METADATA_CHUNKS: &[&[u8; 4]] = &[b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf", b"pHYs"];
+
+/// Removes metadata chunks like texts and timestamps from a PNG, to make it smaller and
+/// independent of the environment it was created in. Anything but a valid PNG is returned as is.
+pub(crate) fn strip_metadata_chunks(data: Vec<u8>) -> Vec<u8> {
+    let Some(mut rest) = data.strip_prefix(PNG_SIGNATURE) else {
+        return data;
+    };
+    let mut stripped = PNG_SIGNATURE.to_vec();
+    // Each chunk consists of its length, type, data and CRC.
+    while !rest.is_empty() {
+        let Some(chunk_type) = rest.get(4..8) else {
+            return data;
+        };
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let Some(chunk) = length.checked_add(12).and_then(|size| rest.get(..size)) else {
+            return data;
+        };
+        if !METADATA_CHUNKS
+            .iter()
+            .any(|metadata| *metadata == chunk_type)
+        {
+            stripped.extend_from_slice(chunk);
+        }
+        rest = &rest[chunk.len()..];
+    }
+    stripped
+}
+
+/// Inserts chunks right after the `IHDR` chunk, which has to come first.
+/// Anything but a PNG is returned as is.
+pub(crate) fn insert_chunks(mut data: Vec<u8>, chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    // The IHDR chunk has a fixed length of 13 bytes, plus 12 bytes of length, type and CRC.
+    let ihdr_end = PNG_SIGNATURE.len() + 25;
+    if !data.starts_with(PNG_SIGNATURE) || data.get(12..16) != Some(b"IHDR") {
+        return data;
+    }
+    let mut encoded = Vec::new();
+    for (chunk_type, chunk_data) in chunks {
+        encoded.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(*chunk_type);
+        encoded.extend_from_slice(chunk_data);
+        let crc = crc32(&[*chunk_type, chunk_data]);
+        encoded.extend_from_slice(&crc.to_be_bytes());
+    }
+    data.splice(ihdr_end..ihdr_end, encoded);
+    data
+}
+
+/// Encodes the frame as an 8-bit indexed PNG if it has at most 256 colors and that is smaller
+/// than an RGBA PNG. Otherwise encodes it as an RGBA PNG.
+pub(crate) fn encode_indexed_png(frame: &RgbaImage) -> Result<Vec<u8>> {
+    let rgba = encode_png(frame)?;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut indices = HashMap::new();
+    let mut pixels = Vec::with_capacity(frame.as_raw().len() / 4);
+    for pixel in frame.pixels() {
+        // The color of fully transparent pixels doesn't matter.
+        let color = if pixel[3] == 0 { [0; 4] } else { pixel.0 };
+        let index = *indices.entry(color).or_insert_with(|| {
+            palette.push(color);
+            palette.len() - 1
+        });
+        if palette.len() > 256 {
+            return Ok(rgba);
+        }
+        pixels.push(index);
+    }
+
+    // Translucent colors come first, so that the alpha values can stop at the last of them.
+    let mut order: Vec<_> = (0..palette.len()).collect();
+    order.sort_by_key(|&index| palette[index][3] == 0xff);
+    let mut remapped = vec![0; palette.len()];
+    for (new_index, &index) in order.iter().enumerate() {
+        remapped[index] = new_index as u8;
+    }
+    let indexed: Vec<u8> = pixels.iter().map(|&index| remapped[index]).collect();
+
+    // Indices are filtered and compressed exactly like grayscale values,
+    // so the PNG encoder only needs a different color type.
+    let mut encoded = Vec::new();
+    PngEncoder::new(Cursor::new(&mut encoded)).write_image(
+        &indexed,
+        frame.width(),
+        frame.height(),
+        ExtendedColorType::L8,
+    )?;
+    // IHDR: length, type, width, height, bit depth, color type, ..., CRC.
+    let ihdr = PNG_SIGNATURE.len()..PNG_SIGNATURE.len() + 25;
+    encoded[ihdr.start + 17] = INDEXED_COLOR_TYPE;
+    let crc = crc32(&[&encoded[ihdr.start + 4..ihdr.end - 4]]);
+    encoded[ihdr.end - 4..ihdr.end].copy_from_slice(&crc.to_be_bytes());
+
+    let colors: Vec<u8> = order
+        .iter()
+        .flat_map(|&index| palette[index][..3].to_vec())
+        .collect();
+    let alphas: Vec<u8> = order
+        .iter()
+        .map(|&index| palette[index][3])
+        .take_while(|&alpha| alpha != 0xff)
+        .collect();
+    let encoded = if alphas.is_empty() {
+        insert_chunks(encoded, &[(b"PLTE", &colors)])
+    } else {
+        insert_chunks(encoded, &[(b"PLTE", &colors), (b"tRNS", &alphas)])
+    };
+    Ok(if encoded.len() < rgba.len() {
+        encoded
+    } else {
+        rgba
+    })
+}
+
+/// Whether the PNG has a chunk of the given type.
+pub(crate) fn has_chunk(data: &[u8], chunk_type: &[u8; 4]) -> bool {
+    let Some(mut rest) = data.strip_prefix(PNG_SIGNATURE) else {
+        return false;
+    };
+    while let Some(header) = rest.get(..8) {
+        if &header[4..8] == chunk_type {
+            return true;
+        }
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let Some(next) = length.checked_add(12).and_then(|size| rest.get(size..)) else {
+            return false;
+        };
+        rest = next;
+    }
+    false
+}
+
+/// The CRC-32 of the concatenated parts, as used for PNG chunks.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in parts.iter().copied().flatten() {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// The types and data of the chunks of a PNG, checking their CRCs.
+    fn chunks(data: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut rest = data.strip_prefix(PNG_SIGNATURE).expect("a PNG");
+        let mut chunks = Vec::new();
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (chunk_type, chunk_data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc32(&[chunk_type, chunk_data]), crc);
+            chunks.push((chunk_type.try_into().unwrap(), chunk_data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    fn chunk_types(data: &[u8]) -> Vec<[u8; 4]> {
+        chunks(data)
+            .into_iter()
+            .map(|(chunk_type, _)| chunk_type)
+            .collect()
+    }
+
+    fn decode(data: &[u8]) -> RgbaImage {
+        image::load_from_memory_with_format(data, image::ImageFormat::Png)
+            .unwrap()
+            .to_rgba8()
+    }
+
+    #[test]
+    fn computes_chunk_crcs() {
+        assert_eq!(crc32(&[b"IEND"]), 0xae42_6082);
+        assert_eq!(crc32(&[b"IE", b"ND"]), 0xae42_6082);
+    }
+
+    #[test]
+    fn encodes_indexed_pngs_with_transparency() {
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 128, 255, 255],
+            [10, 20, 30, 128],
+            [1, 2, 3, 0],
+            [4, 5, 6, 0],
+        ];
+        let frame = RgbaImage::from_fn(32, 32, |x, y| Rgba(colors[((x / 4 + y / 8) % 5) as usize]));
+        let encoded = encode_indexed_png(&frame).unwrap();
+        let chunks = chunks(&encoded);
+        assert_eq!(chunks[0].1[9], INDEXED_COLOR_TYPE);
+        assert_eq!(&chunks[1].0, b"PLTE");
+        // Transparent pixels share one palette entry.
+        assert_eq!(chunks[1].1.len(), 4 * 3);
+        assert_eq!((&chunks[2].0, &chunks[2].1[..]), (b"tRNS", &[128, 0][..]));
+
+        let decoded = decode(&encoded);
+        for (decoded, original) in decoded.pixels().zip(frame.pixels()) {
+            if original[3] == 0 {
+                assert_eq!(decoded[3], 0);
+            } else {
+                assert_eq!(decoded, original);
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_opaque_indexed_pngs_without_trns() {
+        // 16 colors in a noisy pattern, which RGBA PNGs compress badly.
+        let frame = RgbaImage::from_fn(32, 32, |x, y| {
+            let index = ((x * 7 + y * 13 + x * y) % 16) as u8;
+            Rgba([index * 16, 255 - index * 16, index, 255])
+        });
+        let encoded = encode_indexed_png(&frame).unwrap();
+        assert_eq!(chunk_types(&encoded)[..2], [*b"IHDR", *b"PLTE"]);
+        assert!(!has_chunk(&encoded, b"tRNS"));
+        assert_eq!(decode(&encoded), frame);
+    }
+
+    #[test]
+    fn falls_back_to_rgba_for_many_colors() {
+        let frame = RgbaImage::from_fn(32, 32, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        let encoded = encode_indexed_png(&frame).unwrap();
+        assert_eq!(encoded, encode_png(&frame).unwrap());
+        assert_eq!(decode(&encoded), frame);
+    }
+
+    #[test]
+    fn inserts_chunks_after_ihdr() {
+        let frame = RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 4]));
+        let png = encode_png(&frame).unwrap();
+        let inserted = insert_chunks(
+            png.clone(),
+            &[
+                (b"sRGB", &[0]),
+                (b"gAMA", &45455u32.to_be_bytes()),
+                (b"iTXt", b"key\0\0\0\0\0text"),
+            ],
+        );
+        let chunks = chunks(&inserted);
+        assert_eq!(&chunks[0].0, b"IHDR");
+        assert_eq!(chunks[1], (*b"sRGB", vec![0]));
+        assert_eq!(chunks[2], (*b"gAMA", vec![0, 0, 0xb1, 0x8f]));
+        assert_eq!(chunks[3], (*b"iTXt", b"key\0\0\0\0\0text".to_vec()));
+        assert_eq!(decode(&inserted), frame);
+
+        assert_eq!(insert_chunks(b"BM".to_vec(), &[(b"sRGB", &[0])]), b"BM");
+    }
+
+    #[test]
+    fn strips_metadata_chunks() {
+        let png = encode_png(&RgbaImage::new(4, 4)).unwrap();
+        let with_metadata = insert_chunks(
+            png.clone(),
+            &[
+                (b"tEXt", b"Software\0test"),
+                (b"sRGB", &[0]),
+                (b"tIME", &[0; 7]),
+            ],
+        );
+        let stripped = strip_metadata_chunks(with_metadata);
+        assert!(has_chunk(&stripped, b"sRGB"));
+        assert!(!has_chunk(&stripped, b"tEXt"));
+        assert!(!has_chunk(&stripped, b"tIME"));
+        assert_eq!(stripped.len(), png.len() + 13);
+
+        // Truncated PNGs are returned as they are.
+        let truncated = png[..png.len() - 3].to_vec();
+        assert_eq!(strip_metadata_chunks(truncated.clone()), truncated);
+        assert!(!has_chunk(b"not a png", b"IHDR"));
+    }
+}
@@ -0,0 +1,286 @@
+//! A development server for previewing icons in the browser.
+//! Requires the `preview` feature.
+
+use crate::hash::Fnv1a;
+use crate::{BuildReport, Frame, IcoBuilder, Result, Source};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// How often the page checks whether the sources changed, in milliseconds.
+const POLL_INTERVAL: u32 = 1000;
+
+/// Serves an HTML page that shows every frame at its actual size and zoomed in,
+/// so that the legibility of small sizes can be judged without installing the app.
+/// The page reloads automatically when the sources change.
+///
+/// The server handles one request at a time and is only intended for local use.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::preview::PreviewServer;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_files(["app-icon-16x16.png", "app-icon-256x256.png"]);
+/// println!("Preview at http://localhost:8080");
+/// PreviewServer::new(icon).serve("localhost:8080")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PreviewServer {
+    icon: IcoBuilder,
+    zoom: u32,
+}
+
+/// The result of building the frames for a version of the sources.
+struct Build {
+    version: u64,
+    result: Result<(Vec<Frame>, BuildReport)>,
+}
+
+impl PreviewServer {
+    /// Creates a server that previews the frames of `icon`.
+    pub fn new(icon: IcoBuilder) -> PreviewServer {
+        PreviewServer { icon, zoom: 8 }
+    }
+
+    /// Customizes how many times the zoomed in frames are enlarged. Defaults to `8`.
+    pub fn zoom(&mut self, zoom: u32) -> &mut PreviewServer {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Listens on `address` and serves the preview until an I/O error occurs.
+    pub fn serve(&self, address: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let mut build = None;
+        for stream in listener.incoming() {
+            // Broken connections only affect a single request.
+            let _ = self.handle(stream?, &mut build);
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut stream: TcpStream, build: &mut Option<Build>) -> io::Result<()> {
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(&stream);
+        reader.read_line(&mut request_line)?;
+        // The headers are not needed, but have to be read before responding.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let path = request_line.split(' ').nth(1).unwrap_or("/");
+        let version = self.source_version();
+        if build.as_ref().map(|build| build.version) != Some(version) {
+            *build = Some(Build {
+                version,
+                result: self.icon.encode_frames(),
+            });
+        }
+        let build = build.as_ref().expect("the build was just created");
+
+        match path {
+            "/" => respond(
+                &mut stream,
+                "200 OK",
+                "text/html",
+                self.page(build).as_bytes(),
+            ),
+            "/version" => respond(
+                &mut stream,
+                "200 OK",
+                "text/plain",
+                format!("{version:016x}").as_bytes(),
+            ),
+            _ => match frame_for_path(build, path) {
+                Some(frame) => respond(&mut stream, "200 OK", "image/png", frame.data()),
+                None => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+            },
+        }
+    }
+
+    /// A hash of the source files, which changes when one of them changes.
+    fn source_version(&self) -> u64 {
+        let mut hash = Fnv1a::default();
+        for source in &self.icon.sources {
+            if let Source::File(path) = source {
+                match self.icon.fs.read(path) {
+                    Ok(contents) => hash.write(&contents),
+                    Err(error) => hash.write(error.to_string().as_bytes()),
+                };
+            }
+        }
+        hash.finish()
+    }
+
+    fn page(&self, build: &Build) -> String {
+        let mut body = String::new();
+        match &build.result {
+            Ok((frames, report)) => {
+                for frame in frames {
+                    let size = frame.size();
+                    let zoomed = size * self.zoom;
+                    let _ = write!(
+                        body,
+                        "<figure>\
+                           <img src=\"/frame-{size}.png?v={version:016x}\" width=\"{size}\" height=\"{size}\">\
+                           <img src=\"/frame-{size}.png?v={version:016x}\" width=\"{zoomed}\" height=\"{zoomed}\">\
+                           <figcaption>{size}px</figcaption>\
+                         </figure>",
+                        version = build.version
+                    );
+                }
+                if !report.diagnostics.is_empty() {
+                    body.push_str("<ul>");
+                    for diagnostic in &report.diagnostics {
+                        let _ = write!(body, "<li>{}</li>", escape(&diagnostic.to_string()));
+                    }
+                    body.push_str("</ul>");
+                }
+            }
+            Err(error) => {
+                let _ = write!(body, "<pre>{}</pre>", escape(&error.to_string()));
+            }
+        }
+        format!(
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>Icon preview</title>\n\
+             <style>\n\
+               body {{ font-family: sans-serif; background: #f0f0f0; }}\n\
+               figure {{ display: inline-flex; flex-direction: column; align-items: center; gap: 8px; }}\n\
+               img + img {{ image-rendering: pixelated; background: repeating-conic-gradient(#ddd 0 25%, #fff 0 50%) 0 0 / 16px 16px; }}\n\
+             </style>\n\
+             </head>\n\
+             <body>\n\
+             {body}\n\
+             <script>\n\
+               const version = '{version:016x}';\n\
+               setInterval(async () => {{\n\
+                 const response = await fetch('/version');\n\
+                 if (await response.text() !== version) location.reload();\n\
+               }}, {POLL_INTERVAL});\n\
+             </script>\n\
+             </body>\n\
+             </html>\n",
+            version = build.version
+        )
+    }
+}
+
+fn frame_for_path<'a>(build: &'a Build, path: &str) -> Option<&'a Frame> {
+    let path = path.split('?').next()?;
+    let size: u32 = path
+        .strip_prefix("/frame-")?
+        .strip_suffix(".png")?
+        .parse()
+        .ok()?;
+    let (frames, _) = build.result.as_ref().ok()?;
+    frames.iter().find(|frame| frame.size() == size)
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {length}\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n",
+        length = body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use image::RgbaImage;
+    use std::io::Read;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn request(server: &PreviewServer, build: &mut Option<Build>, path: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        write!(client, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        server.handle(stream, build).unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn serves_the_page_and_its_frames() {
+        let fs = Arc::new(MemoryFs::default());
+        fs.write(
+            Path::new("icon.png"),
+            &crate::encode_png(&RgbaImage::new(32, 32)).unwrap(),
+        )
+        .unwrap();
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .sizes(&[16, 32])
+            .add_source_file("icon.png");
+        let mut server = PreviewServer::new(icon);
+        server.zoom(4);
+        let mut build = None;
+
+        let page = request(&server, &mut build, "/");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n"));
+        assert!(page.contains("width=\"128\" height=\"128\""));
+        let version = build.as_ref().unwrap().version;
+        assert!(page.contains(&format!("/frame-16.png?v={version:016x}")));
+
+        let frame = request(
+            &server,
+            &mut build,
+            &format!("/frame-16.png?v={version:016x}"),
+        );
+        assert!(frame.contains("Content-Type: image/png\r\n"));
+        assert!(request(&server, &mut build, "/frame-24.png").starts_with("HTTP/1.1 404"));
+        assert!(request(&server, &mut build, "/version").ends_with(&format!("{version:016x}")));
+
+        fs.write(
+            Path::new("icon.png"),
+            &crate::encode_png(&RgbaImage::new(64, 64)).unwrap(),
+        )
+        .unwrap();
+        request(&server, &mut build, "/version");
+        assert_ne!(build.unwrap().version, version);
+    }
+
+    #[test]
+    fn errors_are_shown_instead_of_frames() {
+        let mut icon = IcoBuilder::default();
+        icon.fs(Arc::new(MemoryFs::default()))
+            .add_source_file("missing.png");
+        let server = PreviewServer::new(icon);
+        let mut build = None;
+        let page = request(&server, &mut build, "/");
+        assert!(page.contains("<pre>"));
+        assert!(!page.contains("<figure>"));
+        assert!(request(&server, &mut build, "/frame-16.png").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(escape("a & <b>"), "a &amp; &lt;b&gt;");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{env_overrides, Error, IcoBuilder, Result};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// Named variants of an [`IcoBuilder`] configuration, e.g. `release`, `nightly` and `ci-preview`,
+/// that share a base configuration. Each profile can change the sources, transformations,
+/// sizes or anything else, so variant builds don't need duplicated configuration.
+///
+/// ```no_run
+/// # use ico_builder::{IcoBuilder, IconSizes, Profiles};
+/// # use ico_builder::overlay::Badge;
+/// # use ico_builder::transform::Opacity;
+/// let mut base = IcoBuilder::default();
+/// base.add_source_file("app-icon-256x256.png");
+///
+/// let mut profiles = Profiles::new(base);
+/// profiles
+///     .profile("release", |icon| {
+///         icon.add_source_file("app-icon-16x16.png");
+///     })
+///     .profile("nightly", |icon| {
+///         icon.add_transform(Opacity::new(0.6));
+///     })
+///     .profile("ci-preview", |icon| {
+///         icon.sizes(IconSizes::new(&[32]));
+///     });
+///
+/// // Selected with the ICO_BUILDER_PROFILE environment variable.
+/// profiles.selected()?.build_file_cargo("app-icon.ico")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+///
+/// Build scripts can also [follow the Cargo profile](Profiles::follow_cargo_profile),
+/// so that debug builds get a development icon without any environment variables:
+/// ```no_run
+/// # use ico_builder::{IcoBuilder, Profiles};
+/// let mut profiles = Profiles::new(IcoBuilder::from_sources(["app-icon-256x256.png"]));
+/// profiles
+///     .profile("debug", |icon| {
+///         *icon = IcoBuilder::from_sources(["app-icon-dev-256x256.png"]);
+///     })
+///     .follow_cargo_profile(true)
+///     .build_file_cargo("app-icon.ico")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Profiles {
+    base: IcoBuilder,
+    profiles: Vec<(String, IcoBuilder)>,
+    default_profile: Option<String>,
+    follow_cargo_profile: bool,
+}
+
+impl Profiles {
+    /// Creates profiles that start out with the configuration of `base`.
+    pub fn new(base: IcoBuilder) -> Profiles {
+        Profiles {
+            base,
+            profiles: Vec::new(),
+            default_profile: None,
+            follow_cargo_profile: false,
+        }
+    }
+
+    /// Adds a profile, which `configure` derives from the base configuration.
+    /// Adding a profile with the same name again replaces it.
+    pub fn profile(
+        &mut self,
+        name: impl Into<String>,
+        configure: impl FnOnce(&mut IcoBuilder),
+    ) -> &mut Profiles {
+        let name = name.into();
+        let mut builder = self.base.clone();
+        configure(&mut builder);
+        self.profiles.retain(|(existing, _)| *existing != name);
+        self.profiles.push((name, builder));
+        self
+    }
+
+    /// Customizes the profile that [`Profiles::selected`] uses when no profile is selected
+    /// explicitly. Defaults to the base configuration.
+    pub fn default_profile(&mut self, name: impl Into<String>) -> &mut Profiles {
+        self.default_profile = Some(name.into());
+        self
+    }
+
+    /// Makes [`Profiles::selected`] select the profile named after the Cargo profile of
+    /// build scripts, i.e. `debug` or `release`, if there is such a profile.
+    /// `ICO_BUILDER_PROFILE` still takes precedence. Defaults to `false`.
+    pub fn follow_cargo_profile(&mut self, follow_cargo_profile: bool) -> &mut Profiles {
+        self.follow_cargo_profile = follow_cargo_profile;
+        self
+    }
+
+    /// The names of all profiles, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The configuration of the profile with the given name.
+    pub fn get(&self, name: &str) -> Result<&IcoBuilder> {
+        self.profiles
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, builder)| builder)
+            .ok_or_else(|| Error::UnknownProfile(name.to_owned()))
+    }
+
+    /// The configuration of the profile selected with the `ICO_BUILDER_PROFILE` environment variable,
+    /// or of the [Cargo profile](Profiles::follow_cargo_profile),
+    /// or of the [default profile](Profiles::default_profile) if neither is set.
+    pub fn selected(&self) -> Result<&IcoBuilder> {
+        let cargo_profile = env_overrides::cargo_profile()
+            .filter(|name| self.follow_cargo_profile && self.get(name).is_ok());
+        match env_overrides::profile()
+            .or(cargo_profile)
+            .or_else(|| self.default_profile.clone())
+        {
+            Some(name) => self.get(&name),
+            None => Ok(&self.base),
+        }
+    }
+
+    /// Builds the [selected](Profiles::selected) profile with [`IcoBuilder::build_file_cargo`].
+    pub fn build_file_cargo(&self, file_name: impl AsRef<OsStr>) -> Result<PathBuf> {
+        self.selected()?.build_file_cargo(file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IconSizes;
+
+    #[test]
+    fn profiles_derive_from_the_base() {
+        let mut base = IcoBuilder::default();
+        base.sizes(&[16, 32]);
+        let mut profiles = Profiles::new(base);
+        profiles
+            .profile("preview", |icon| {
+                icon.sizes(&[32]);
+            })
+            .profile("release", |icon| {
+                icon.allow_upscaling(true);
+            })
+            .profile("preview", |icon| {
+                icon.sizes(&[48]);
+            });
+
+        assert_eq!(profiles.names().collect::<Vec<_>>(), ["release", "preview"]);
+        assert_eq!(
+            profiles.get("preview").unwrap().sizes,
+            IconSizes::new(&[48])
+        );
+        let release = profiles.get("release").unwrap();
+        assert_eq!(release.sizes, IconSizes::new(&[16, 32]));
+        assert!(release.allow_upscaling);
+        assert!(matches!(
+            profiles.get("nightly"),
+            Err(Error::UnknownProfile(name)) if name == "nightly"
+        ));
+    }
+}
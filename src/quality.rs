@@ -0,0 +1,137 @@
+//! Perceptual quality metrics for comparing frames.
+
+use crate::Quality;
+use image::RgbaImage;
+
+const WINDOW_SIZE: u32 = 8;
+const WINDOW_STRIDE: u32 = 4;
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Compares two frames of the same size.
+pub(crate) fn measure(frame: &RgbaImage, reference: &RgbaImage) -> Quality {
+    Quality {
+        psnr: psnr(frame, reference),
+        ssim: ssim(frame, reference),
+    }
+}
+
+/// The color channels premultiplied with alpha, followed by alpha,
+/// so that the color of fully transparent pixels doesn't matter.
+fn premultiplied(pixel: &image::Rgba<u8>) -> [f64; 4] {
+    let alpha = f64::from(pixel[3]) / 255.0;
+    [
+        f64::from(pixel[0]) * alpha,
+        f64::from(pixel[1]) * alpha,
+        f64::from(pixel[2]) * alpha,
+        f64::from(pixel[3]),
+    ]
+}
+
+fn psnr(frame: &RgbaImage, reference: &RgbaImage) -> f64 {
+    let (sum, count) = frame
+        .pixels()
+        .zip(reference.pixels())
+        .flat_map(|(a, b)| {
+            let (a, b) = (premultiplied(a), premultiplied(b));
+            (0..4).map(move |i| (a[i] - b[i]).powi(2))
+        })
+        .fold((0.0, 0), |(sum, count), error| (sum + error, count + 1));
+    let mse = sum / f64::from(count.max(1));
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+/// Mean SSIM of the luminance over overlapping windows.
+fn ssim(frame: &RgbaImage, reference: &RgbaImage) -> f64 {
+    let size = frame.width();
+    let window = WINDOW_SIZE.min(size);
+    let luma = |image: &RgbaImage, x: u32, y: u32| {
+        let [r, g, b, _] = premultiplied(image.get_pixel(x, y));
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    };
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..=size - window).step_by(WINDOW_STRIDE as usize) {
+        for left in (0..=size - window).step_by(WINDOW_STRIDE as usize) {
+            let pairs: Vec<_> = (top..top + window)
+                .flat_map(|y| (left..left + window).map(move |x| (x, y)))
+                .map(|(x, y)| (luma(frame, x, y), luma(reference, x, y)))
+                .collect();
+            total += window_ssim(&pairs);
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+fn window_ssim(pairs: &[(f64, f64)]) -> f64 {
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+    let (mut variance_a, mut variance_b, mut covariance) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+        covariance += (a - mean_a) * (b - mean_b);
+    }
+    let (variance_a, variance_b, covariance) = (variance_a / n, variance_b / n, covariance / n);
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (variance_a + variance_b + C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn pattern(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])
+        })
+    }
+
+    #[test]
+    fn identical_frames_are_perfect() {
+        let frame = pattern(16);
+        let quality = measure(&frame, &frame);
+        assert_eq!(quality.psnr, f64::INFINITY);
+        assert!((quality.ssim - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hidden_colors_are_ignored() {
+        let frame = RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 0]));
+        let reference = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 0]));
+        assert_eq!(measure(&frame, &reference).psnr, f64::INFINITY);
+    }
+
+    #[test]
+    fn psnr_follows_the_mean_squared_error() {
+        let reference = pattern(16);
+        let mut frame = reference.clone();
+        for pixel in frame.pixels_mut() {
+            pixel[0] += 1;
+            pixel[1] += 1;
+            pixel[2] += 1;
+        }
+        // Three of four channels are off by one.
+        let expected = 10.0 * (255.0f64 * 255.0 / 0.75).log10();
+        assert!((psnr(&frame, &reference) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ssim_drops_with_lost_structure() {
+        let reference = pattern(16);
+        let blurred = image::imageops::blur(&reference, 2.0);
+        let flat = RgbaImage::from_pixel(16, 16, Rgba([128, 128, 128, 255]));
+        let (blurred, flat) = (ssim(&blurred, &reference), ssim(&flat, &reference));
+        assert!(blurred < 1.0 && flat < blurred, "{blurred} {flat}");
+        // Frames smaller than a window are compared as a single window.
+        assert!((ssim(&pattern(4), &pattern(4)) - 1.0).abs() < 1e-9);
+    }
+}
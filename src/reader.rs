@@ -0,0 +1,531 @@
+//! Reading existing ICO files.
+//!
+//! Malformed files result in an [`Error::InvalidIco`] instead of a panic, and memory use is
+//! bounded by the size of the file: entries share its contents, and [`IcoFile::to_bytes`] stores
+//! overlapping images once. Decoding the images relies on the ICO decoder of the
+//! `image` crate, with limits on the image size and memory. Both are covered by the fuzz
+//! targets in `fuzz/`.
+//! With the `arbitrary` feature, [`IcoFile`] implements `arbitrary::Arbitrary`
+//! for fuzzing code that processes ICO files.
+
+use crate::{Error, Result};
+use image::{ImageFormat, ImageReader, Limits, RgbaImage};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The size of the `ICONDIR` header.
+pub(crate) const HEADER_SIZE: usize = 6;
+/// The size of an `ICONDIRENTRY`.
+pub(crate) const ENTRY_SIZE: usize = 16;
+/// The resource type of icons, as opposed to cursors.
+pub(crate) const ICON_TYPE: u16 = 1;
+/// The largest image that ICO directory entries can describe.
+const MAX_DIMENSION: u32 = 256;
+/// The most memory that decoding a single entry may use, enough for a 256px RGBA image
+/// and the decoder's buffers.
+const MAX_DECODE_ALLOC: u64 = 4 * 1024 * 1024;
+
+/// A range of the contents of a file that entries point into.
+type DataBlock<'a> = (&'a Arc<[u8]>, Range<usize>);
+
+/// A parsed ICO file.
+///
+/// ```no_run
+/// # use ico_builder::reader::IcoFile;
+/// let ico = IcoFile::read("app-icon.ico")?;
+/// for entry in ico.entries() {
+///     println!("{}x{}, {} bits per pixel", entry.width(), entry.height(), entry.bit_count());
+/// }
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcoFile {
+    entries: Vec<IcoEntry>,
+}
+
+/// An image in an ICO file, as described by its `ICONDIRENTRY`.
+/// Entries share the contents of the file, so cloning them is cheap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcoEntry {
+    width: u32,
+    height: u32,
+    color_count: u8,
+    planes: u16,
+    bit_count: u16,
+    file: Arc<[u8]>,
+    range: Range<usize>,
+}
+
+impl IcoFile {
+    /// Reads and parses the ICO file at `path`.
+    pub fn read(path: impl AsRef<Path>) -> Result<IcoFile> {
+        IcoFile::parse(&std::fs::read(path)?)
+    }
+
+    /// Parses an ICO file. The images themselves are only checked when they are [decoded](IcoEntry::decode).
+    pub fn parse(data: &[u8]) -> Result<IcoFile> {
+        let header = data
+            .get(..HEADER_SIZE)
+            .ok_or_else(|| invalid("The file is too short for the header"))?;
+        if u16_at(header, 0) != 0 || u16_at(header, 2) != ICON_TYPE {
+            return Err(invalid("The file is not an icon"));
+        }
+        let count = usize::from(u16_at(header, 4));
+        let directory = data
+            .get(HEADER_SIZE..HEADER_SIZE + count * ENTRY_SIZE)
+            .ok_or_else(|| invalid("The file is too short for the directory"))?;
+
+        let file: Arc<[u8]> = data.into();
+        let entries = directory
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| IcoEntry::parse(entry, &file))
+            .collect::<Result<_>>()?;
+        Ok(IcoFile { entries })
+    }
+
+    pub(crate) fn from_entries(entries: Vec<IcoEntry>) -> IcoFile {
+        IcoFile { entries }
+    }
+
+    /// Encodes the file again, with the images stored one after another in directory order.
+    /// Images that overlap in the file they were parsed from are stored once, overlapping as
+    /// before, so the result is never larger than the parsed file and its directory.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (blocks, entry_blocks) = self.data_blocks();
+        let mut file = vec![0, 0, 1, 0];
+        file.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        let mut block_offsets = Vec::with_capacity(blocks.len());
+        let mut offset = HEADER_SIZE + self.entries.len() * ENTRY_SIZE;
+        for (_, range) in &blocks {
+            block_offsets.push(offset);
+            offset += range.len();
+        }
+        for (entry, block) in self.entries.iter().zip(entry_blocks) {
+            let offset = block_offsets[block] + entry.range.start - blocks[block].1.start;
+            // 256px is stored as 0.
+            file.extend_from_slice(&[entry.width as u8, entry.height as u8, entry.color_count, 0]);
+            file.extend_from_slice(&entry.planes.to_le_bytes());
+            file.extend_from_slice(&entry.bit_count.to_le_bytes());
+            file.extend_from_slice(&(entry.range.len() as u32).to_le_bytes());
+            file.extend_from_slice(&(offset as u32).to_le_bytes());
+        }
+        for (source, range) in blocks {
+            file.extend_from_slice(&source[range]);
+        }
+        file
+    }
+
+    /// The ranges of data to write, in the order of the first entry they contain, and the index
+    /// of the range that contains each entry. Overlapping and adjacent images of the same file
+    /// are merged into one range.
+    fn data_blocks(&self) -> (Vec<DataBlock<'_>>, Vec<usize>) {
+        // Files are numbered in directory order, so that the layout doesn't depend on addresses.
+        let mut file_indices = HashMap::new();
+        let mut order: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let next = file_indices.len();
+                let file = *file_indices.entry(Arc::as_ptr(&entry.file)).or_insert(next);
+                (file, entry.range.start, index)
+            })
+            .collect();
+        order.sort_unstable();
+
+        // The first entry, file number and range of each block.
+        let mut blocks: Vec<(usize, usize, Range<usize>)> = Vec::new();
+        let mut entry_blocks = vec![0; self.entries.len()];
+        for (file, start, index) in order {
+            let end = self.entries[index].range.end;
+            match blocks.last_mut() {
+                Some((first, block_file, range)) if *block_file == file && start <= range.end => {
+                    *first = (*first).min(index);
+                    range.end = range.end.max(end);
+                }
+                _ => blocks.push((index, file, start..end)),
+            }
+            entry_blocks[index] = blocks.len() - 1;
+        }
+
+        let mut by_first_entry: Vec<_> = (0..blocks.len()).collect();
+        by_first_entry.sort_unstable_by_key(|&block| blocks[block].0);
+        let mut positions = vec![0; blocks.len()];
+        for (position, &block) in by_first_entry.iter().enumerate() {
+            positions[block] = position;
+        }
+        let ranges = by_first_entry
+            .iter()
+            .map(|&block| {
+                let (first, _, range) = &blocks[block];
+                (&self.entries[*first].file, range.clone())
+            })
+            .collect();
+        let entry_blocks = entry_blocks
+            .into_iter()
+            .map(|block| positions[block])
+            .collect();
+        (ranges, entry_blocks)
+    }
+
+    /// The entries in directory order.
+    pub fn entries(&self) -> &[IcoEntry] {
+        &self.entries
+    }
+
+    /// The sizes of the square entries in directory order.
+    pub fn sizes(&self) -> Vec<u32> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.width == entry.height)
+            .map(|entry| entry.width)
+            .collect()
+    }
+}
+
+impl IcoEntry {
+    fn parse(entry: &[u8], file: &Arc<[u8]>) -> Result<IcoEntry> {
+        let size = u32_at(entry, 8) as usize;
+        let offset = u32_at(entry, 12) as usize;
+        let range = offset..offset.saturating_add(size);
+        if range.end > file.len() {
+            return Err(invalid("An entry points outside of the file"));
+        }
+        Ok(IcoEntry {
+            width: dimension(entry[0]),
+            height: dimension(entry[1]),
+            color_count: entry[2],
+            planes: u16_at(entry, 4),
+            bit_count: u16_at(entry, 6),
+            file: file.clone(),
+            range,
+        })
+    }
+
+    /// Creates an entry with its own copy of the image data.
+    pub(crate) fn new(
+        width: u32,
+        height: u32,
+        color_count: u8,
+        planes: u16,
+        bit_count: u16,
+        data: &[u8],
+    ) -> IcoEntry {
+        IcoEntry::shared(
+            width,
+            height,
+            color_count,
+            planes,
+            bit_count,
+            &data.into(),
+            0..data.len(),
+        )
+    }
+
+    /// Creates an entry whose image data is the `range` of `file`, which it shares with other entries.
+    pub(crate) fn shared(
+        width: u32,
+        height: u32,
+        color_count: u8,
+        planes: u16,
+        bit_count: u16,
+        file: &Arc<[u8]>,
+        range: Range<usize>,
+    ) -> IcoEntry {
+        IcoEntry {
+            width,
+            height,
+            color_count,
+            planes,
+            bit_count,
+            file: file.clone(),
+            range,
+        }
+    }
+
+    /// The width in pixels, as stored in the directory.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height in pixels, as stored in the directory.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of colors in the palette, or `0` if there is no palette.
+    pub fn color_count(&self) -> u8 {
+        self.color_count
+    }
+
+    /// The number of color planes, as stored in the directory.
+    pub fn planes(&self) -> u16 {
+        self.planes
+    }
+
+    /// The bits per pixel, as stored in the directory.
+    pub fn bit_count(&self) -> u16 {
+        self.bit_count
+    }
+
+    /// The encoded image, either a PNG or a BMP without file header.
+    pub fn data(&self) -> &[u8] {
+        &self.file[self.range.clone()]
+    }
+
+    /// Whether the image is encoded as a PNG.
+    pub fn is_png(&self) -> bool {
+        self.data().starts_with(b"\x89PNG\r\n\x1a\n")
+    }
+
+    /// Decodes the image, including the transparency mask of BMP images.
+    /// Images larger than 256px are rejected, as are images that need excessive memory to decode.
+    pub fn decode(&self) -> Result<RgbaImage> {
+        // The decoder of the `image` crate only reads whole ICO files,
+        // so the entry is wrapped in an ICO file of its own.
+        let data = self.data();
+        let offset = (HEADER_SIZE + ENTRY_SIZE) as u32;
+        let mut ico = Vec::with_capacity(offset as usize + data.len());
+        ico.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+        ico.extend_from_slice(&[self.width as u8, self.height as u8, self.color_count, 0]);
+        ico.extend_from_slice(&self.planes.to_le_bytes());
+        ico.extend_from_slice(&self.bit_count.to_le_bytes());
+        ico.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&offset.to_le_bytes());
+        ico.extend_from_slice(data);
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_DIMENSION);
+        limits.max_image_height = Some(MAX_DIMENSION);
+        limits.max_alloc = Some(MAX_DECODE_ALLOC);
+        let mut reader = ImageReader::with_format(Cursor::new(ico), ImageFormat::Ico);
+        reader.limits(limits);
+        Ok(reader.decode()?.to_rgba8())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for IcoFile {
+    /// Creates a structurally valid ICO file with arbitrary directory entries and image data.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let images: Vec<([u8; 3], u16, u16, &[u8])> = u.arbitrary()?;
+        let count = images.len().min(usize::from(u16::MAX));
+        let mut file = vec![0, 0, 1, 0];
+        file.extend_from_slice(&(count as u16).to_le_bytes());
+        let mut offset = HEADER_SIZE + count * ENTRY_SIZE;
+        for &([width, height, color_count], planes, bit_count, data) in &images[..count] {
+            file.extend_from_slice(&[width, height, color_count, 0]);
+            file.extend_from_slice(&planes.to_le_bytes());
+            file.extend_from_slice(&bit_count.to_le_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            file.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += data.len();
+        }
+        for &(.., data) in &images[..count] {
+            file.extend_from_slice(data);
+        }
+        IcoFile::parse(&file).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+/// A dimension of `0` in the directory means 256px.
+pub(crate) fn dimension(value: u8) -> u32 {
+    match value {
+        0 => 256,
+        value => value.into(),
+    }
+}
+
+// The offsets are always within the fixed-size header and entries, or checked by the caller.
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+pub(crate) fn invalid(reason: &str) -> Error {
+    Error::InvalidIco {
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_png;
+    use image::Rgba;
+
+    /// An ICO file with one directory entry per `(size, offset)` and `data` after the directory.
+    fn ico(entries: &[(u32, u32)], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0, 0, 1, 0];
+        file.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for &(size, offset) in entries {
+            file.extend_from_slice(&[16, 16, 0, 0, 1, 0, 32, 0]);
+            file.extend_from_slice(&size.to_le_bytes());
+            file.extend_from_slice(&offset.to_le_bytes());
+        }
+        file.extend_from_slice(data);
+        file
+    }
+
+    fn reason(result: Result<IcoFile>) -> String {
+        match result {
+            Err(Error::InvalidIco { reason }) => reason,
+            other => panic!("expected an invalid ICO, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        for length in 0..HEADER_SIZE {
+            assert_eq!(
+                reason(IcoFile::parse(&[0, 0, 1, 0, 1, 0][..length])),
+                "The file is too short for the header"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_file_types() {
+        assert_eq!(
+            reason(IcoFile::parse(&[0, 0, 2, 0, 0, 0])),
+            "The file is not an icon"
+        );
+        assert_eq!(
+            reason(IcoFile::parse(b"\x89PNG\r\n")),
+            "The file is not an icon"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_directory() {
+        let file = ico(&[(0, 22), (0, 22)], &[]);
+        for length in HEADER_SIZE..file.len() {
+            assert_eq!(
+                reason(IcoFile::parse(&file[..length])),
+                "The file is too short for the directory"
+            );
+        }
+        // The count claims more entries than there are.
+        let mut file = ico(&[], &[]);
+        file[4] = 0xff;
+        file[5] = 0xff;
+        assert_eq!(
+            reason(IcoFile::parse(&file)),
+            "The file is too short for the directory"
+        );
+    }
+
+    #[test]
+    fn parses_zero_entries() {
+        let file = IcoFile::parse(&ico(&[], &[])).unwrap();
+        assert!(file.entries().is_empty());
+        assert_eq!(file.to_bytes(), ico(&[], &[]));
+    }
+
+    #[test]
+    fn rejects_entries_past_the_end() {
+        let data = [1, 2, 3, 4];
+        let offset = (HEADER_SIZE + ENTRY_SIZE) as u32;
+        assert!(IcoFile::parse(&ico(&[(4, offset)], &data)).is_ok());
+        for (size, offset) in [
+            (5, offset),
+            (4, offset + 1),
+            (0, offset + 5),
+            (u32::MAX, offset),
+            (4, u32::MAX),
+            (u32::MAX, u32::MAX),
+        ] {
+            assert_eq!(
+                reason(IcoFile::parse(&ico(&[(size, offset)], &data))),
+                "An entry points outside of the file"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_overlapping_entries() {
+        let offset = (HEADER_SIZE + 2 * ENTRY_SIZE) as u32;
+        let file = IcoFile::parse(&ico(&[(4, offset), (2, offset + 2)], &[1, 2, 3, 4])).unwrap();
+        assert_eq!(file.entries()[0].data(), &[1, 2, 3, 4]);
+        assert_eq!(file.entries()[1].data(), &[3, 4]);
+        // Writing the file again keeps the overlap.
+        let bytes = file.to_bytes();
+        assert_eq!(bytes.len(), offset as usize + 4);
+        let rewritten = IcoFile::parse(&bytes).unwrap();
+        assert_eq!(rewritten.entries()[0].data(), &[1, 2, 3, 4]);
+        assert_eq!(rewritten.entries()[1].data(), &[3, 4]);
+    }
+
+    #[test]
+    fn overlapping_entries_are_written_once() {
+        // Every entry points at the whole file, including the directory.
+        let count = 4096;
+        let length = HEADER_SIZE + count * ENTRY_SIZE + 65536;
+        let file = ico(&vec![(length as u32, 0); count], &[7; 65536]);
+        let parsed = IcoFile::parse(&file).unwrap();
+        let bytes = parsed.to_bytes();
+        assert_eq!(bytes.len(), 2 * length - 65536);
+        let rewritten = IcoFile::parse(&bytes).unwrap();
+        assert_eq!(rewritten.entries().len(), count);
+        assert!(rewritten.entries().iter().all(|entry| entry.data() == file));
+    }
+
+    #[test]
+    fn separate_images_keep_directory_order() {
+        let offset = (HEADER_SIZE + 3 * ENTRY_SIZE) as u32;
+        let file = ico(
+            &[(2, offset + 4), (2, offset), (1, offset + 7)],
+            &[1, 2, 0, 0, 3, 4, 0, 5],
+        );
+        let bytes = IcoFile::parse(&file).unwrap().to_bytes();
+        assert_eq!(&bytes[offset as usize..], &[3, 4, 1, 2, 5]);
+        let rewritten = IcoFile::parse(&bytes).unwrap();
+        let data: Vec<_> = rewritten.entries().iter().map(IcoEntry::data).collect();
+        assert_eq!(data, [&[3, 4][..], &[1, 2], &[5]]);
+    }
+
+    #[test]
+    fn reads_256px_as_zero() {
+        let mut file = ico(&[(0, 22)], &[]);
+        file[6] = 0;
+        file[7] = 0;
+        let parsed = IcoFile::parse(&file).unwrap();
+        let entry = &parsed.entries()[0];
+        assert_eq!((entry.width(), entry.height()), (256, 256));
+        assert_eq!(IcoFile::parse(&file).unwrap().to_bytes(), file);
+    }
+
+    #[test]
+    fn decodes_png_entries() {
+        let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8, y as u8, 7, (x * y) as u8]));
+        let png = encode_png(&image).unwrap();
+        let file = ico(&[(png.len() as u32, 22)], &png);
+        let parsed = IcoFile::parse(&file).unwrap();
+        let entry = &parsed.entries()[0];
+        assert!(entry.is_png());
+        assert_eq!(entry.decode().unwrap(), image);
+    }
+
+    #[test]
+    fn rejects_corrupt_and_oversized_images() {
+        let png = encode_png(&RgbaImage::new(16, 16)).unwrap();
+        // Cut off in the signature, the header and the image data.
+        for length in [0, 8, 20, png.len() - 16] {
+            let file = ico(&[(length as u32, 22)], &png[..length]);
+            assert!(IcoFile::parse(&file).unwrap().entries()[0]
+                .decode()
+                .is_err());
+        }
+        let large = encode_png(&RgbaImage::new(300, 300)).unwrap();
+        let file = ico(&[(large.len() as u32, 22)], &large);
+        assert!(IcoFile::parse(&file).unwrap().entries()[0]
+            .decode()
+            .is_err());
+    }
+}
@@ -0,0 +1,307 @@
+//! Visual comparisons between two versions of an ICO file, so that icon changes
+//! in pull requests can be reviewed as images, e.g. by bots that post them as comments.
+
+use crate::contact_sheet::checkerboard;
+use crate::reader::IcoFile;
+use crate::verify::pixels_differ;
+use crate::{encode_png, font, Result};
+use image::imageops::{overlay, resize, FilterType};
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// The size that small frames are enlarged to in the rendered comparisons.
+const ZOOMED_SIZE: u32 = 128;
+/// The space around and between the panels.
+const PADDING: u32 = 16;
+const LABEL_SCALE: u32 = 2;
+const LABEL_COLOR: Rgba<u8> = Rgba([0x40, 0x40, 0x40, 0xff]);
+/// The color of changed pixels in the difference panel.
+const CHANGED_COLOR: Rgba<u8> = Rgba([0xff, 0x00, 0xff, 0xff]);
+
+/// The differences between the frames of a baseline and a current ICO file.
+/// Frames are compared by size; if a file has several frames of the same size, the first is used.
+///
+/// ```no_run
+/// # use ico_builder::reader::IcoFile;
+/// # use ico_builder::regression::VisualDiff;
+/// let diff = VisualDiff::new(&IcoFile::read("main/app-icon.ico")?, &IcoFile::read("app-icon.ico")?, 4)?;
+/// if diff.is_changed() {
+///     println!("{diff}");
+///     diff.write_report("icon-diff")?;
+/// }
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct VisualDiff {
+    sizes: Vec<SizeDiff>,
+}
+
+/// The difference between the frames of one size.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SizeDiff {
+    pub size: u32,
+    /// The frame of the baseline, or `None` if the size was added.
+    pub baseline: Option<RgbaImage>,
+    /// The current frame, or `None` if the size was removed.
+    pub current: Option<RgbaImage>,
+    /// The number of pixels that differ by more than the tolerance.
+    pub changed_pixels: u64,
+    tolerance: u8,
+}
+
+impl VisualDiff {
+    /// Compares the frames of `current` to those of `baseline`. Pixels are changed if a color
+    /// channel differs by more than `tolerance`, like in [`IcoBuilder::verify_file`](crate::IcoBuilder::verify_file).
+    pub fn new(baseline: &IcoFile, current: &IcoFile, tolerance: u8) -> Result<VisualDiff> {
+        let mut frames = BTreeMap::<u32, (Option<RgbaImage>, Option<RgbaImage>)>::new();
+        for entry in baseline.entries() {
+            let frames = frames.entry(entry.width()).or_default();
+            if frames.0.is_none() {
+                frames.0 = Some(entry.decode()?);
+            }
+        }
+        for entry in current.entries() {
+            let frames = frames.entry(entry.width()).or_default();
+            if frames.1.is_none() {
+                frames.1 = Some(entry.decode()?);
+            }
+        }
+        let sizes = frames
+            .into_iter()
+            .map(|(size, (baseline, current))| {
+                let changed_pixels = match (&baseline, &current) {
+                    (Some(baseline), Some(current))
+                        if baseline.dimensions() == current.dimensions() =>
+                    {
+                        baseline
+                            .pixels()
+                            .zip(current.pixels())
+                            .filter(|(baseline, current)| {
+                                pixels_differ(**baseline, **current, tolerance)
+                            })
+                            .count() as u64
+                    }
+                    (Some(frame), _) | (None, Some(frame)) => {
+                        u64::from(frame.width() * frame.height())
+                    }
+                    (None, None) => 0,
+                };
+                SizeDiff {
+                    size,
+                    baseline,
+                    current,
+                    changed_pixels,
+                    tolerance,
+                }
+            })
+            .collect();
+        Ok(VisualDiff { sizes })
+    }
+
+    /// The differences for each size that is in either file, in ascending order.
+    pub fn sizes(&self) -> &[SizeDiff] {
+        &self.sizes
+    }
+
+    /// Whether any size was added, removed or changed.
+    pub fn is_changed(&self) -> bool {
+        self.sizes.iter().any(SizeDiff::is_changed)
+    }
+
+    /// Writes a rendered comparison for each size as `{size}x{size}.png`
+    /// and an `index.html` page showing all of them to `output_dir`.
+    /// Returns the paths of the written files.
+    pub fn write_report(&self, output_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let output_dir = output_dir.as_ref();
+        std::fs::create_dir_all(output_dir)?;
+        let mut written = Vec::with_capacity(self.sizes.len() + 1);
+        let mut body = String::new();
+        for diff in &self.sizes {
+            let file_name = format!("{size}x{size}.png", size = diff.size);
+            let path = output_dir.join(&file_name);
+            std::fs::write(&path, encode_png(&diff.render())?)?;
+            written.push(path);
+            let _ = writeln!(
+                body,
+                "<figure>\
+                   <img src=\"{file_name}\">\
+                   <figcaption>{diff}</figcaption>\
+                 </figure>",
+            );
+        }
+        let path = output_dir.join("index.html");
+        std::fs::write(
+            &path,
+            format!(
+                "<!DOCTYPE html>\n\
+                 <html>\n\
+                 <head>\n\
+                 <meta charset=\"utf-8\">\n\
+                 <title>Icon changes</title>\n\
+                 <style>\n\
+                   body {{ font-family: sans-serif; }}\n\
+                   figure {{ display: flex; flex-direction: column; align-items: start; gap: 8px; }}\n\
+                 </style>\n\
+                 </head>\n\
+                 <body>\n\
+                 <p>Baseline, current and changed pixels for each size.</p>\n\
+                 {body}\
+                 </body>\n\
+                 </html>\n"
+            ),
+        )?;
+        written.push(path);
+        Ok(written)
+    }
+}
+
+impl SizeDiff {
+    /// Whether the size was added, removed or changed.
+    pub fn is_changed(&self) -> bool {
+        self.changed_pixels > 0
+    }
+
+    /// Renders the baseline frame, the current frame and the changed pixels next to each other.
+    /// Small frames are enlarged without smoothing, so that single pixels are visible.
+    pub fn render(&self) -> RgbaImage {
+        let zoom = (ZOOMED_SIZE / self.size).max(1);
+        let panel_size = self.size * zoom;
+        let zoomed = |frame: &RgbaImage| resize(frame, panel_size, panel_size, FilterType::Nearest);
+        let difference = match (&self.baseline, &self.current) {
+            (Some(baseline), Some(current)) => Some(self.difference(baseline, current)),
+            _ => None,
+        };
+        let panels = [
+            ("BASELINE", self.baseline.as_ref().map(zoomed)),
+            ("CURRENT", self.current.as_ref().map(zoomed)),
+            ("CHANGES", difference.as_ref().map(zoomed)),
+        ];
+
+        let label_width = panels
+            .iter()
+            .map(|(label, _)| font::text_width(label, LABEL_SCALE))
+            .max()
+            .unwrap_or(0);
+        let column_width = panel_size.max(label_width);
+        let width = PADDING + panels.len() as u32 * (column_width + PADDING);
+        let height = PADDING + panel_size + PADDING / 2 + font::text_height(LABEL_SCALE) + PADDING;
+        let mut image = checkerboard(width, height);
+        let mut x = PADDING;
+        for (label, panel) in &panels {
+            let panel_x = x + (column_width - panel_size) / 2;
+            if let Some(panel) = panel {
+                overlay(&mut image, panel, panel_x.into(), PADDING.into());
+            }
+            let label_x = x + (column_width - font::text_width(label, LABEL_SCALE)) / 2;
+            let label_y = PADDING + panel_size + PADDING / 2;
+            font::draw_text(
+                &mut image,
+                label,
+                (label_x.into(), label_y.into()),
+                LABEL_SCALE,
+                LABEL_COLOR,
+            );
+            x += column_width + PADDING;
+        }
+        image
+    }
+
+    /// The current frame faded out, with the changed pixels highlighted.
+    fn difference(&self, baseline: &RgbaImage, current: &RgbaImage) -> RgbaImage {
+        RgbaImage::from_fn(current.width(), current.height(), |x, y| {
+            let current = *current.get_pixel(x, y);
+            match baseline.get_pixel_checked(x, y) {
+                Some(&baseline) if !pixels_differ(baseline, current, self.tolerance) => {
+                    Rgba([current[0], current[1], current[2], current[3] / 4])
+                }
+                _ => CHANGED_COLOR,
+            }
+        })
+    }
+}
+
+/// A summary of the changed sizes, one per line, for logs and comments.
+impl fmt::Display for VisualDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changed: Vec<_> = self.sizes.iter().filter(|diff| diff.is_changed()).collect();
+        write!(f, "{} of {} sizes changed", changed.len(), self.sizes.len())?;
+        for diff in changed {
+            write!(f, "\n* {diff}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SizeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.size;
+        match (&self.baseline, &self.current) {
+            (None, _) => write!(f, "{size}x{size}: added"),
+            (_, None) => write!(f, "{size}x{size}: removed"),
+            _ if self.is_changed() => {
+                write!(f, "{size}x{size}: {} pixels changed", self.changed_pixels)
+            }
+            _ => write!(f, "{size}x{size}: unchanged"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IcoBuilder;
+
+    fn icon(sizes: &[u32], color: Rgba<u8>) -> IcoFile {
+        let (data, _) = IcoBuilder::default()
+            .sizes(sizes)
+            .add_source_image(RgbaImage::from_pixel(64, 64, color))
+            .encode_ico()
+            .unwrap();
+        IcoFile::parse(&data).unwrap()
+    }
+
+    #[test]
+    fn compares_frames_by_size() {
+        let baseline = icon(&[16, 32], Rgba([255, 0, 0, 255]));
+        let current = icon(&[32, 48], Rgba([252, 0, 0, 255]));
+
+        let within_tolerance = VisualDiff::new(&baseline, &current, 4).unwrap();
+        let summary: Vec<_> = within_tolerance
+            .sizes()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            summary,
+            ["16x16: removed", "32x32: unchanged", "48x48: added"]
+        );
+        assert!(within_tolerance.is_changed());
+
+        let diff = VisualDiff::new(&baseline, &current, 2).unwrap();
+        assert_eq!(diff.sizes()[1].changed_pixels, 32 * 32);
+        assert_eq!(
+            diff.to_string(),
+            "3 of 3 sizes changed\n* 16x16: removed\n* 32x32: 1024 pixels changed\n* 48x48: added"
+        );
+    }
+
+    #[test]
+    fn renders_changed_pixels() {
+        let baseline = icon(&[16], Rgba([255, 0, 0, 255]));
+        let current = icon(&[16], Rgba([0, 0, 255, 255]));
+        let diff = VisualDiff::new(&baseline, &current, 0).unwrap();
+        let diff = &diff.sizes()[0];
+        let difference = diff.difference(
+            diff.baseline.as_ref().unwrap(),
+            diff.current.as_ref().unwrap(),
+        );
+        assert!(difference.pixels().all(|&pixel| pixel == CHANGED_COLOR));
+        let rendered = diff.render();
+        // Three 128px panels with padding, and a label below them.
+        assert_eq!(rendered.width(), PADDING + 3 * (ZOOMED_SIZE + PADDING));
+        assert!(rendered.height() > ZOOMED_SIZE + 2 * PADDING);
+    }
+}
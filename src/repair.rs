@@ -0,0 +1,471 @@
+//! Repairing ICO files with defects in their directory, as written by some third-party converters.
+//!
+//! The image data of each entry is preserved; only the directory and the layout of the file
+//! are rewritten. Entries that overlap keep sharing their data, so the repaired file is never
+//! larger than the original and its directory. Defects in the images themselves can't be repaired.
+
+use crate::frame::bit_count;
+use crate::png::PNG_SIGNATURE;
+use crate::reader::{
+    dimension, invalid, u16_at, u32_at, IcoEntry, IcoFile, ENTRY_SIZE, HEADER_SIZE, ICON_TYPE,
+};
+use crate::Result;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The size of the `BITMAPINFOHEADER` that BMPs in ICO files start with.
+const BITMAPINFOHEADER_SIZE: usize = 40;
+
+/// A defect that was repaired. Entries are identified by their index in the directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fix {
+    /// The width and height in the directory didn't match the image.
+    Dimensions {
+        entry: usize,
+        stored: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// The bits per pixel in the directory didn't match the image.
+    BitCount {
+        entry: usize,
+        stored: u16,
+        actual: u16,
+    },
+    /// The number of palette colors in the directory didn't match the bits per pixel.
+    ColorCount {
+        entry: usize,
+        stored: u8,
+        actual: u8,
+    },
+    /// The number of color planes in the directory wasn't `1`.
+    Planes { entry: usize, stored: u16 },
+    /// The size of the image in the directory didn't match the image.
+    DataSize {
+        entry: usize,
+        stored: usize,
+        actual: usize,
+    },
+    /// The images weren't stored in directory order, or there were unused bytes between them.
+    Layout,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fix::Dimensions {
+                entry,
+                stored: (stored_width, stored_height),
+                actual: (width, height),
+            } => write!(
+                f,
+                "Entry #{entry} claimed to be {stored_width} × {stored_height} instead of {width} × {height}"
+            ),
+            Fix::BitCount {
+                entry,
+                stored,
+                actual,
+            } => write!(
+                f,
+                "Entry #{entry} claimed {stored} bits per pixel instead of {actual}"
+            ),
+            Fix::ColorCount {
+                entry,
+                stored,
+                actual,
+            } => write!(
+                f,
+                "Entry #{entry} claimed {stored} palette colors instead of {actual}"
+            ),
+            Fix::Planes { entry, stored } => {
+                write!(f, "Entry #{entry} claimed {stored} color planes instead of 1")
+            }
+            Fix::DataSize {
+                entry,
+                stored,
+                actual,
+            } => write!(
+                f,
+                "Entry #{entry} claimed a size of {stored} bytes instead of {actual}"
+            ),
+            Fix::Layout => write!(f, "The images were stored out of order or with padding"),
+        }
+    }
+}
+
+/// Repairs an ICO file, returning the repaired file and the defects that were fixed.
+/// Files without defects are returned unchanged and without fixes.
+///
+/// ```no_run
+/// # use ico_builder::repair::repair;
+/// let (ico, fixes) = repair(&std::fs::read("broken.ico")?)?;
+/// for fix in &fixes {
+///     println!("{fix}");
+/// }
+/// std::fs::write("fixed.ico", ico.to_bytes())?;
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn repair(data: &[u8]) -> Result<(IcoFile, Vec<Fix>)> {
+    let header = data
+        .get(..HEADER_SIZE)
+        .ok_or_else(|| invalid("The file is too short for the header"))?;
+    if u16_at(header, 0) != 0 || u16_at(header, 2) != ICON_TYPE {
+        return Err(invalid("The file is not an icon"));
+    }
+    let count = usize::from(u16_at(header, 4));
+    let directory = data
+        .get(HEADER_SIZE..HEADER_SIZE + count * ENTRY_SIZE)
+        .ok_or_else(|| invalid("The file is too short for the directory"))?;
+
+    // The entries share the data, so that entries pointing at the same bytes don't copy them.
+    let file: Arc<[u8]> = data.into();
+    let mut fixes = Vec::new();
+    let mut entries = Vec::with_capacity(count);
+    let mut expected_offset = HEADER_SIZE + count * ENTRY_SIZE;
+    let mut in_order = true;
+    for (index, entry) in directory.chunks_exact(ENTRY_SIZE).enumerate() {
+        let stored_size = u32_at(entry, 8) as usize;
+        let offset = u32_at(entry, 12) as usize;
+        let image = data
+            .get(offset..)
+            .ok_or_else(|| invalid(&format!("Entry #{index} points outside of the file")))?;
+        let size = match image_size(image) {
+            Some(size) => size,
+            None if stored_size <= image.len() => stored_size,
+            None => {
+                return Err(invalid(&format!(
+                    "Entry #{index} is truncated or in an unknown format"
+                )))
+            }
+        };
+        if size != stored_size {
+            fixes.push(Fix::DataSize {
+                entry: index,
+                stored: stored_size,
+                actual: size,
+            });
+        }
+        in_order &= offset == expected_offset;
+        expected_offset = offset.saturating_add(size);
+        let image = &image[..size];
+
+        let stored_dimensions = (dimension(entry[0]), dimension(entry[1]));
+        let dimensions = image_dimensions(image)
+            .filter(|&(width, height)| width <= 256 && height <= 256)
+            .unwrap_or(stored_dimensions);
+        if dimensions != stored_dimensions {
+            fixes.push(Fix::Dimensions {
+                entry: index,
+                stored: stored_dimensions,
+                actual: dimensions,
+            });
+        }
+
+        let stored_bit_count = u16_at(entry, 6);
+        let bits = bit_count(image).unwrap_or(stored_bit_count);
+        if bits != stored_bit_count {
+            fixes.push(Fix::BitCount {
+                entry: index,
+                stored: stored_bit_count,
+                actual: bits,
+            });
+        }
+
+        let stored_color_count = entry[2];
+        let color_count = match bits {
+            1 | 2 | 4 => 1 << bits,
+            _ => 0,
+        };
+        // Palettes with fewer colors than the bit count allows are valid.
+        if stored_color_count > color_count || (color_count == 0 && stored_color_count != 0) {
+            fixes.push(Fix::ColorCount {
+                entry: index,
+                stored: stored_color_count,
+                actual: color_count,
+            });
+        }
+
+        let planes = u16_at(entry, 4);
+        if planes > 1 {
+            fixes.push(Fix::Planes {
+                entry: index,
+                stored: planes,
+            });
+        }
+
+        entries.push(IcoEntry::shared(
+            dimensions.0,
+            dimensions.1,
+            stored_color_count.min(color_count),
+            planes.min(1),
+            bits,
+            &file,
+            offset..offset + size,
+        ));
+    }
+    if !in_order || expected_offset != data.len() {
+        fixes.push(Fix::Layout);
+    }
+    let repaired = IcoFile::from_entries(entries);
+    if fixes.is_empty() {
+        return Ok((IcoFile::parse(data)?, fixes));
+    }
+    Ok((repaired, fixes))
+}
+
+/// Repairs the ICO file at `path` in place. Returns the defects that were fixed.
+/// The file is only written if there were any.
+pub fn repair_file(path: impl AsRef<Path>) -> Result<Vec<Fix>> {
+    let path = path.as_ref();
+    let (ico, fixes) = repair(&std::fs::read(path)?)?;
+    if !fixes.is_empty() {
+        std::fs::write(path, ico.to_bytes())?;
+    }
+    Ok(fixes)
+}
+
+/// The actual size of a PNG or BMP at the start of `data`, or `None` if it's truncated
+/// or in another format.
+fn image_size(data: &[u8]) -> Option<usize> {
+    if data.starts_with(PNG_SIGNATURE) {
+        // Chunks consist of the length, type, data and CRC. The last chunk is IEND.
+        let mut offset = PNG_SIGNATURE.len();
+        loop {
+            let length = u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?);
+            let chunk_type = data.get(offset + 4..offset + 8)?;
+            offset = offset.checked_add(12 + length as usize)?;
+            if offset > data.len() {
+                return None;
+            }
+            if chunk_type == b"IEND" {
+                return Some(offset);
+            }
+        }
+    } else {
+        let header = data.get(..BITMAPINFOHEADER_SIZE)?;
+        if u32_at(header, 0) as usize != BITMAPINFOHEADER_SIZE {
+            return None;
+        }
+        let width = u32_at(header, 4) as usize;
+        // The height includes the transparency mask.
+        let height = u32_at(header, 8) as usize / 2;
+        let bits = usize::from(u16_at(header, 14));
+        let palette_size = match u32_at(header, 32) as usize {
+            0 if bits <= 8 => 1 << bits,
+            colors => colors,
+        };
+        // Untrusted dimensions can overflow.
+        let row_size = |bits: usize| Some(width.checked_mul(bits)?.div_ceil(32) * 4);
+        let size = row_size(bits)?
+            .checked_add(row_size(1)?)?
+            .checked_mul(height)?
+            .checked_add(palette_size.checked_mul(4)?)?
+            .checked_add(BITMAPINFOHEADER_SIZE)?;
+        (size <= data.len()).then_some(size)
+    }
+}
+
+/// The dimensions stored in the header of a PNG or BMP.
+fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if let Some(png) = data.strip_prefix(PNG_SIGNATURE) {
+        // The IHDR chunk always comes first: length, type, width, height.
+        let ihdr = png.get(..16)?;
+        Some((
+            u32::from_be_bytes(ihdr[8..12].try_into().ok()?),
+            u32::from_be_bytes(ihdr[12..16].try_into().ok()?),
+        ))
+    } else {
+        let header = data.get(..BITMAPINFOHEADER_SIZE)?;
+        Some((u32_at(header, 4), u32_at(header, 8) / 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bmp::encode_bmp;
+    use crate::encode_png;
+    use crate::Error;
+    use image::RgbaImage;
+
+    /// A directory entry: width, height, color count, planes, bit count, size and offset.
+    type Entry = (u8, u8, u8, u16, u16, usize, usize);
+
+    fn ico(entries: &[Entry], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![0, 0, 1, 0];
+        file.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for &(width, height, colors, planes, bits, size, offset) in entries {
+            file.extend_from_slice(&[width, height, colors, 0]);
+            file.extend_from_slice(&planes.to_le_bytes());
+            file.extend_from_slice(&bits.to_le_bytes());
+            file.extend_from_slice(&(size as u32).to_le_bytes());
+            file.extend_from_slice(&(offset as u32).to_le_bytes());
+        }
+        file.extend_from_slice(data);
+        file
+    }
+
+    fn png() -> Vec<u8> {
+        encode_png(&RgbaImage::new(16, 16)).unwrap()
+    }
+
+    fn bmp() -> Vec<u8> {
+        encode_bmp(&RgbaImage::new(32, 32), 4)
+    }
+
+    /// A valid file with a 16px PNG and a 32px 4-bit BMP.
+    fn valid() -> (Vec<Entry>, Vec<u8>) {
+        let (png, bmp) = (png(), bmp());
+        let offset = HEADER_SIZE + 2 * ENTRY_SIZE;
+        let entries = vec![
+            (16, 16, 0, 1, 32, png.len(), offset),
+            (32, 32, 16, 1, 4, bmp.len(), offset + png.len()),
+        ];
+        (entries, [png, bmp].concat())
+    }
+
+    fn fixes(entries: &[Entry], data: &[u8]) -> Vec<Fix> {
+        let (repaired, fixes) = repair(&ico(entries, data)).unwrap();
+        // The repaired file has no defects left.
+        assert_eq!(repair(&repaired.to_bytes()).unwrap().1, []);
+        fixes
+    }
+
+    #[test]
+    fn keeps_valid_files() {
+        let (entries, data) = valid();
+        let file = ico(&entries, &data);
+        let (repaired, fixes) = repair(&file).unwrap();
+        assert_eq!(fixes, []);
+        assert_eq!(repaired.to_bytes(), file);
+    }
+
+    #[test]
+    fn fixes_dimensions_and_bit_counts() {
+        let (mut entries, data) = valid();
+        entries[0].0 = 48;
+        entries[1].4 = 8;
+        assert_eq!(
+            fixes(&entries, &data),
+            [
+                Fix::Dimensions {
+                    entry: 0,
+                    stored: (48, 16),
+                    actual: (16, 16)
+                },
+                Fix::BitCount {
+                    entry: 1,
+                    stored: 8,
+                    actual: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fixes_color_counts_and_planes() {
+        let (mut entries, data) = valid();
+        entries[0].2 = 16;
+        entries[0].3 = 4;
+        entries[1].2 = 8;
+        assert_eq!(
+            fixes(&entries, &data),
+            [
+                Fix::ColorCount {
+                    entry: 0,
+                    stored: 16,
+                    actual: 0
+                },
+                Fix::Planes {
+                    entry: 0,
+                    stored: 4
+                },
+            ]
+        );
+        let (mut entries, data) = valid();
+        entries[1].2 = 255;
+        assert_eq!(
+            fixes(&entries, &data),
+            [Fix::ColorCount {
+                entry: 1,
+                stored: 255,
+                actual: 16
+            }]
+        );
+    }
+
+    #[test]
+    fn fixes_data_sizes_and_layout() {
+        let (mut entries, data) = valid();
+        entries[0].5 += 3;
+        entries[1].5 -= 10;
+        assert_eq!(
+            fixes(&entries, &data),
+            [
+                Fix::DataSize {
+                    entry: 0,
+                    stored: png().len() + 3,
+                    actual: png().len()
+                },
+                Fix::DataSize {
+                    entry: 1,
+                    stored: bmp().len() - 10,
+                    actual: bmp().len()
+                },
+            ]
+        );
+
+        // The images are swapped, with padding in between.
+        let (png, bmp) = (png(), bmp());
+        let offset = HEADER_SIZE + 2 * ENTRY_SIZE;
+        let entries = [
+            (16, 16, 0, 1, 32, png.len(), offset + bmp.len() + 5),
+            (32, 32, 16, 1, 4, bmp.len(), offset),
+        ];
+        let data = [&bmp[..], &[0; 5], &png].concat();
+        assert_eq!(fixes(&entries, &data), [Fix::Layout]);
+        let (repaired, _) = repair(&ico(&entries, &data)).unwrap();
+        assert_eq!(repaired.entries()[0].data(), png);
+        assert_eq!(repaired.entries()[1].data(), bmp);
+    }
+
+    #[test]
+    fn rejects_unrepairable_files() {
+        let reason = |file: &[u8]| match repair(file) {
+            Err(Error::InvalidIco { reason }) => reason,
+            other => panic!("expected an invalid ICO, got {other:?}"),
+        };
+        assert_eq!(reason(&[0, 0, 1]), "The file is too short for the header");
+        assert_eq!(reason(&[0, 0, 2, 0, 0, 0]), "The file is not an icon");
+        assert_eq!(
+            reason(&[0, 0, 1, 0, 2, 0]),
+            "The file is too short for the directory"
+        );
+
+        let (mut entries, data) = valid();
+        entries[1].6 = u32::MAX as usize;
+        assert_eq!(
+            reason(&ico(&entries, &data)),
+            "Entry #1 points outside of the file"
+        );
+        let (entries, data) = valid();
+        assert_eq!(
+            reason(&ico(&entries, &data[..data.len() - 1])),
+            "Entry #1 is truncated or in an unknown format"
+        );
+    }
+
+    #[test]
+    fn overlapping_entries_share_their_data() {
+        // Every entry claims the whole file, which is in no known image format.
+        let count = 4096;
+        let length = HEADER_SIZE + count * ENTRY_SIZE + 65536;
+        let entries = vec![(16, 16, 0, 1, 32, length, 0); count];
+        let file = ico(&entries, &[7; 65536]);
+        let (repaired, fixes) = repair(&file).unwrap();
+        assert!(fixes.contains(&Fix::Layout));
+        assert!(repaired.entries().iter().all(|entry| entry.data() == file));
+        assert_eq!(repaired.to_bytes().len(), 2 * length - 65536);
+    }
+}
@@ -0,0 +1,704 @@
+use crate::FilterType;
+use image::imageops::{self, resize};
+use image::RgbaImage;
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+
+/// Information about a successful build.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct BuildReport {
+    /// The frames included in the output, in order.
+    pub frames: Vec<FrameReport>,
+    /// Problems that didn't prevent the build from succeeding, but might need attention.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Information about a single frame.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct FrameReport {
+    pub size: u32,
+    /// Where the content of the frame came from.
+    pub origin: FrameOrigin,
+    /// How closely the frame matches a reference rendering.
+    /// Only available if [`IcoBuilder::quality_report`](crate::IcoBuilder::quality_report) is enabled.
+    pub quality: Option<Quality>,
+}
+
+/// Where the content of a frame came from.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum FrameOrigin {
+    /// The frame was resized from the smallest source that is at least as large as the frame,
+    /// or upscaled from the largest source if [upscaling](crate::IcoBuilder::allow_upscaling) is allowed.
+    Resized {
+        /// The index of the source, in the order the sources were added.
+        source: usize,
+        /// The path of the source, or `None` for in-memory images.
+        path: Option<PathBuf>,
+        source_size: u32,
+        /// The filter that resized the frame, never [`FilterType::Auto`].
+        filter: FilterType,
+        /// Whether [`FilterType::Auto`] sharpened the frame after resizing it.
+        sharpened: bool,
+        /// The transformations applied after resizing, in order, formatted with [`fmt::Debug`].
+        transforms: Vec<String>,
+        /// Whether the frame was read from the [cache](crate::IcoBuilder::cache_dir).
+        cached: bool,
+    },
+    /// The frame was added with [`IcoBuilder::add_encoded_frame`](crate::IcoBuilder::add_encoded_frame).
+    Encoded,
+}
+
+/// Perceptual quality of a frame compared to a reference rendering of the largest source
+/// at the same size, using the Lanczos3 filter and the same transformations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    /// Peak signal-to-noise ratio in decibels. Infinite if the frame is identical to the reference.
+    pub psnr: f64,
+    /// Mean structural similarity of the luminance, where `1.0` means identical.
+    pub ssim: f64,
+}
+
+/// A problem that didn't prevent the build from succeeding.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// The frame of `size` has the same content as the smaller (or earlier) frame of `duplicate_of`.
+    /// Such frames only make the ICO file bigger.
+    DuplicateFrame {
+        size: u32,
+        duplicate_of: u32,
+        /// Whether the frame was removed from the output.
+        /// See [`IcoBuilder::drop_duplicate_frames`](crate::IcoBuilder::drop_duplicate_frames).
+        removed: bool,
+    },
+    /// The frame of `size` was not a 32-bit RGBA PNG and was converted,
+    /// since Windows Explorer doesn't render such large frames correctly otherwise.
+    ConvertedLargeFrame { size: u32 },
+    /// The frame of `size` was downscaled from a source that is `ratio` times larger, which is more than
+    /// [`IcoBuilder::max_downscale_ratio`](crate::IcoBuilder::max_downscale_ratio) allows.
+    ExtremeDownscale {
+        size: u32,
+        source_size: u32,
+        ratio: f32,
+    },
+    /// The frame of `size` was upscaled from a source of `source_size`,
+    /// since [upscaling](crate::IcoBuilder::allow_upscaling) is allowed
+    /// or the [selection strategy](crate::SelectionStrategy) picked a smaller source.
+    Upscaled { size: u32, source_size: u32 },
+    /// The frame of `size` has so much fine detail that it is likely illegible.
+    /// This usually means that the source is too detailed for tiny sizes.
+    TooDetailed {
+        size: u32,
+        /// The fraction of pixels that are part of an edge.
+        edge_density: f32,
+    },
+    /// The frame of `size` couldn't be produced and was left out of the output,
+    /// since [`IcoBuilder::best_effort`](crate::IcoBuilder::best_effort) is enabled.
+    SkippedFrame {
+        size: u32,
+        /// Why the frame couldn't be produced, e.g. the message of the error.
+        reason: String,
+    },
+}
+
+/// How important a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Severity {
+    /// The output could be smaller or cleaner, but looks as intended.
+    Note,
+    /// The output probably doesn't look as intended.
+    Warning,
+}
+
+impl Severity {
+    /// The name of the severity, as used in the JSON and SARIF output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl Diagnostic {
+    /// A stable, machine-readable identifier of the kind of diagnostic, e.g. `extreme-downscale`.
+    /// Unlike the messages, codes never change between versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Diagnostic::DuplicateFrame { .. } => "duplicate-frame",
+            Diagnostic::ConvertedLargeFrame { .. } => "converted-large-frame",
+            Diagnostic::ExtremeDownscale { .. } => "extreme-downscale",
+            Diagnostic::Upscaled { .. } => "upscaled",
+            Diagnostic::TooDetailed { .. } => "too-detailed",
+            Diagnostic::SkippedFrame { .. } => "skipped-frame",
+        }
+    }
+
+    /// How important the diagnostic is.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::DuplicateFrame { .. } | Diagnostic::ConvertedLargeFrame { .. } => {
+                Severity::Note
+            }
+            Diagnostic::ExtremeDownscale { .. }
+            | Diagnostic::Upscaled { .. }
+            | Diagnostic::TooDetailed { .. }
+            | Diagnostic::SkippedFrame { .. } => Severity::Warning,
+        }
+    }
+
+    /// The size of the frame the diagnostic is about.
+    pub fn size(&self) -> u32 {
+        match *self {
+            Diagnostic::DuplicateFrame { size, .. }
+            | Diagnostic::ConvertedLargeFrame { size }
+            | Diagnostic::ExtremeDownscale { size, .. }
+            | Diagnostic::Upscaled { size, .. }
+            | Diagnostic::TooDetailed { size, .. }
+            | Diagnostic::SkippedFrame { size, .. } => size,
+        }
+    }
+}
+
+impl BuildReport {
+    /// A human-readable explanation of how each frame was produced,
+    /// followed by the diagnostics. Useful for finding out why the output looks wrong.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// let report = IcoBuilder::default()
+    ///     .add_source_files(["app-icon-32x32.png", "app-icon-256x256.png"])
+    ///     .build_file("app-icon.ico")?;
+    /// println!("{}", report.explain());
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn explain(&self) -> String {
+        let mut explanation = String::new();
+        for frame in &self.frames {
+            let _ = writeln!(explanation, "{}px: {}", frame.size, frame.origin);
+            if let FrameOrigin::Resized {
+                source_size,
+                sharpened,
+                transforms,
+                cached,
+                ..
+            } = &frame.origin
+            {
+                if *source_size < frame.size {
+                    let _ = writeln!(explanation, "  upscaled from a {source_size}px source",);
+                } else {
+                    let _ = writeln!(
+                        explanation,
+                        "  chosen as the smallest source of at least {}px",
+                        frame.size
+                    );
+                }
+                if *sharpened {
+                    explanation.push_str("  then sharpened\n");
+                }
+                for transform in transforms {
+                    let _ = writeln!(explanation, "  then transformed by {transform}");
+                }
+                if *cached {
+                    explanation.push_str("  read from the cache\n");
+                }
+            }
+            if let Some(Quality { psnr, ssim }) = frame.quality {
+                let _ = writeln!(explanation, "  PSNR {psnr:.1} dB, SSIM {ssim:.3}");
+            }
+        }
+        if self.diagnostics.is_empty() {
+            explanation.push_str("No diagnostics\n");
+        } else {
+            explanation.push_str("Diagnostics:\n");
+            for diagnostic in &self.diagnostics {
+                let _ = writeln!(explanation, "* {diagnostic}");
+            }
+        }
+        explanation
+    }
+
+    /// The diagnostics as JSON, for processing by CI systems:
+    ///
+    /// ```json
+    /// {
+    ///   "diagnostics": [
+    ///     {
+    ///       "code": "extreme-downscale",
+    ///       "severity": "warning",
+    ///       "size": 16,
+    ///       "message": "The 16px frame was downscaled 16.0× from a 256px source, ..."
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    pub fn diagnostics_json(&self) -> String {
+        let diagnostics: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                format!(
+                    "    {{\n      \
+                       \"code\": \"{code}\",\n      \
+                       \"severity\": \"{severity}\",\n      \
+                       \"size\": {size},\n      \
+                       \"message\": {message}\n    \
+                     }}",
+                    code = diagnostic.code(),
+                    severity = diagnostic.severity().as_str(),
+                    size = diagnostic.size(),
+                    message = json_string(&diagnostic.to_string()),
+                )
+            })
+            .collect();
+        if diagnostics.is_empty() {
+            return "{\n  \"diagnostics\": []\n}\n".to_owned();
+        }
+        format!(
+            "{{\n  \"diagnostics\": [\n{}\n  ]\n}}\n",
+            diagnostics.join(",\n")
+        )
+    }
+
+    /// The diagnostics as a [SARIF](https://sarifweb.azurewebsites.net/) 2.1.0 log,
+    /// which e.g. GitHub code scanning turns into annotations on pull requests.
+    /// All results are reported for `artifact_uri`, usually the path of the ICO file
+    /// relative to the root of the repository.
+    pub fn diagnostics_sarif(&self, artifact_uri: &str) -> String {
+        let mut codes: Vec<_> = self.diagnostics.iter().map(Diagnostic::code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        let rules: Vec<_> = codes
+            .iter()
+            .map(|code| format!("{{\"id\":\"{code}\"}}"))
+            .collect();
+        let results: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                format!(
+                    "{{\"ruleId\":\"{code}\",\"level\":\"{level}\",\
+                     \"message\":{{\"text\":{message}}},\
+                     \"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{uri}}}}}}}]}}",
+                    code = diagnostic.code(),
+                    level = diagnostic.severity().as_str(),
+                    message = json_string(&diagnostic.to_string()),
+                    uri = json_string(artifact_uri),
+                )
+            })
+            .collect();
+        format!(
+            "{{\"version\":\"2.1.0\",\
+             \"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\
+             \"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"ico-builder\",\
+             \"informationUri\":\"https://github.com/bash/ico-builder\",\
+             \"version\":\"{version}\",\"rules\":[{rules}]}}}},\
+             \"results\":[{results}]}}]}}\n",
+            version = env!("CARGO_PKG_VERSION"),
+            rules = rules.join(","),
+            results = results.join(","),
+        )
+    }
+}
+
+/// Quotes and escapes a string for JSON.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl fmt::Display for FrameOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameOrigin::Resized {
+                source,
+                path,
+                source_size,
+                filter,
+                ..
+            } => {
+                match path {
+                    Some(path) => write!(f, "resized from {}", path.display())?,
+                    None => write!(f, "resized from in-memory image")?,
+                }
+                write!(
+                    f,
+                    " (source #{source}, {source_size}px) using the {filter:?} filter"
+                )
+            }
+            FrameOrigin::Encoded => write!(f, "pre-encoded frame, included as is"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::ConvertedLargeFrame { size } => {
+                write!(f, "The {size}px frame was converted to a 32-bit RGBA PNG")
+            }
+            Diagnostic::ExtremeDownscale {
+                size,
+                source_size,
+                ratio,
+            } => write!(
+                f,
+                "The {size}px frame was downscaled {ratio:.1}× from a {source_size}px source, \
+                 consider adding a source closer to {size}px"
+            ),
+            Diagnostic::Upscaled { size, source_size } => write!(
+                f,
+                "The {size}px frame was upscaled from a {source_size}px source"
+            ),
+            Diagnostic::TooDetailed { size, edge_density } => write!(
+                f,
+                "The {size}px frame is likely illegible ({percent:.0}% of its pixels are edges), \
+                 consider adding a simplified source for small sizes",
+                percent = edge_density * 100.0
+            ),
+            Diagnostic::SkippedFrame { size, reason } => {
+                write!(f, "The {size}px frame was skipped: {reason}")
+            }
+            Diagnostic::DuplicateFrame {
+                size,
+                duplicate_of,
+                removed,
+            } => {
+                write!(
+                    f,
+                    "The {size}px frame has the same content as the {duplicate_of}px frame"
+                )?;
+                if *removed {
+                    write!(f, " and was removed")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reports frames that have the same content as a smaller (or earlier) frame
+/// and removes them if `remove` is set.
+pub(crate) fn check_duplicate_frames(
+    frames: &mut Vec<RgbaImage>,
+    remove: bool,
+    report: &mut BuildReport,
+) {
+    let duplicates: Vec<_> = (0..frames.len())
+        .filter_map(|index| Some((index, find_original(frames, index)?)))
+        .collect();
+
+    for &(index, original) in &duplicates {
+        report.diagnostics.push(Diagnostic::DuplicateFrame {
+            size: frames[index].width(),
+            duplicate_of: frames[original].width(),
+            removed: remove,
+        });
+    }
+
+    if remove {
+        for &(index, _) in duplicates.iter().rev() {
+            frames.remove(index);
+            report.frames.remove(index);
+        }
+    }
+}
+
+fn find_original(frames: &[RgbaImage], index: usize) -> Option<usize> {
+    let frame = &frames[index];
+    frames.iter().enumerate().position(|(other_index, other)| {
+        let is_before = other.width() < frame.width()
+            || (other.width() == frame.width() && other_index < index);
+        is_before && has_same_content(other, frame)
+    })
+}
+
+/// Whether the larger frame, scaled down to the size of the smaller frame
+/// without any filtering, is pixel-identical to the smaller frame.
+fn has_same_content(smaller: &RgbaImage, larger: &RgbaImage) -> bool {
+    if smaller.width() == larger.width() {
+        smaller == larger
+    } else {
+        let size = smaller.width();
+        resize(larger, size, size, imageops::FilterType::Nearest) == *smaller
+    }
+}
+
+/// Frames up to this size are checked for legibility.
+const LEGIBILITY_MAX_SIZE: u32 = 16;
+/// The minimum gradient magnitude (of luminance and alpha) for a pixel to count as an edge.
+const EDGE_THRESHOLD: f32 = 96.0;
+/// The fraction of edge pixels above which a frame is considered too detailed.
+const MAX_EDGE_DENSITY: f32 = 0.4;
+
+/// Estimates whether tiny frames are legible based on how many of their pixels are edges.
+pub(crate) fn check_legibility(frames: &[RgbaImage], report: &mut BuildReport) {
+    for frame in frames {
+        if frame.width() > LEGIBILITY_MAX_SIZE {
+            continue;
+        }
+        let edge_density = edge_density(frame);
+        if edge_density > MAX_EDGE_DENSITY {
+            report.diagnostics.push(Diagnostic::TooDetailed {
+                size: frame.width(),
+                edge_density,
+            });
+        }
+    }
+}
+
+/// The fraction of pixels whose luminance or alpha differs from one of their
+/// four neighbors by more than [`EDGE_THRESHOLD`].
+fn edge_density(frame: &RgbaImage) -> f32 {
+    let size = frame.width();
+    let channels = |x: u32, y: u32| {
+        let pixel = frame.get_pixel(x, y);
+        let alpha = f32::from(pixel[3]) / 255.0;
+        let luma = (0.2126 * f32::from(pixel[0])
+            + 0.7152 * f32::from(pixel[1])
+            + 0.0722 * f32::from(pixel[2]))
+            * alpha;
+        [luma, f32::from(pixel[3])]
+    };
+
+    let mut edges = 0;
+    for y in 0..size {
+        for x in 0..size {
+            let center = channels(x, y);
+            let neighbors = [
+                x.checked_sub(1).map(|x| (x, y)),
+                (x + 1 < size).then_some((x + 1, y)),
+                y.checked_sub(1).map(|y| (x, y)),
+                (y + 1 < size).then_some((x, y + 1)),
+            ];
+            let is_edge = neighbors.into_iter().flatten().any(|(nx, ny)| {
+                let neighbor = channels(nx, ny);
+                (0..2).any(|i| (center[i] - neighbor[i]).abs() > EDGE_THRESHOLD)
+            });
+            if is_edge {
+                edges += 1;
+            }
+        }
+    }
+    edges as f32 / (size * size) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// Frame reports for `frames`, since removing duplicates also removes their reports.
+    fn report_for(frames: &[RgbaImage]) -> BuildReport {
+        BuildReport {
+            frames: frames
+                .iter()
+                .map(|frame| FrameReport {
+                    size: frame.width(),
+                    origin: FrameOrigin::Encoded,
+                    quality: None,
+                })
+                .collect(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn finds_frames_with_the_content_of_smaller_frames() {
+        let checkerboard = |size: u32, cell: u32| {
+            RgbaImage::from_fn(size, size, |x, y| {
+                Rgba([
+                    0,
+                    0,
+                    0,
+                    if (x / cell) % 2 == (y / cell) % 2 {
+                        255
+                    } else {
+                        0
+                    },
+                ])
+            })
+        };
+        // The 32px frame is the 16px frame enlarged, the second 16px frame is a copy.
+        let mut frames = vec![
+            checkerboard(16, 4),
+            checkerboard(32, 8),
+            checkerboard(16, 4),
+            checkerboard(48, 4),
+        ];
+        let mut report = report_for(&frames);
+        check_duplicate_frames(&mut frames, true, &mut report);
+        assert_eq!(
+            report.diagnostics,
+            [
+                Diagnostic::DuplicateFrame {
+                    size: 32,
+                    duplicate_of: 16,
+                    removed: true
+                },
+                Diagnostic::DuplicateFrame {
+                    size: 16,
+                    duplicate_of: 16,
+                    removed: true
+                },
+            ]
+        );
+        let sizes: Vec<_> = frames.iter().map(RgbaImage::width).collect();
+        assert_eq!(sizes, [16, 48]);
+        let reported: Vec<_> = report.frames.iter().map(|frame| frame.size).collect();
+        assert_eq!(reported, sizes);
+    }
+
+    #[test]
+    fn reports_tiny_frames_with_many_edges() {
+        let noisy = RgbaImage::from_fn(16, 16, |x, y| {
+            let level = if (x + y) % 2 == 0 { 255 } else { 0 };
+            Rgba([level, level, level, 255])
+        });
+        let simple =
+            RgbaImage::from_fn(16, 16, |x, _| Rgba([0, 0, 0, if x < 8 { 255 } else { 0 }]));
+        let large = RgbaImage::from_fn(32, 32, |x, y| *noisy.get_pixel(x % 16, y % 16));
+        assert_eq!(edge_density(&noisy), 1.0);
+        // Only the two columns next to the edge of the shape.
+        assert_eq!(edge_density(&simple), 2.0 / 16.0);
+
+        let mut report = BuildReport::default();
+        check_legibility(&[simple, noisy, large], &mut report);
+        assert_eq!(
+            report.diagnostics,
+            [Diagnostic::TooDetailed {
+                size: 16,
+                edge_density: 1.0
+            }]
+        );
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("plain"), r#""plain""#);
+        assert_eq!(json_string("a \"b\"\\c\nd\te"), r#""a \"b\"\\c\nd\u0009e""#);
+        assert_eq!(json_string("16×"), "\"16×\"");
+    }
+
+    #[test]
+    fn exports_diagnostics() {
+        let report = BuildReport {
+            frames: Vec::new(),
+            diagnostics: vec![
+                Diagnostic::Upscaled {
+                    size: 32,
+                    source_size: 16,
+                },
+                Diagnostic::ConvertedLargeFrame { size: 256 },
+            ],
+        };
+        assert_eq!(
+            report.diagnostics_json(),
+            r#"{
+  "diagnostics": [
+    {
+      "code": "upscaled",
+      "severity": "warning",
+      "size": 32,
+      "message": "The 32px frame was upscaled from a 16px source"
+    },
+    {
+      "code": "converted-large-frame",
+      "severity": "note",
+      "size": 256,
+      "message": "The 256px frame was converted to a 32-bit RGBA PNG"
+    }
+  ]
+}
+"#
+        );
+        assert_eq!(
+            BuildReport::default().diagnostics_json(),
+            "{\n  \"diagnostics\": []\n}\n"
+        );
+
+        let sarif = report.diagnostics_sarif("assets/app \"icon\".ico");
+        assert!(sarif.contains(r#""rules":[{"id":"converted-large-frame"},{"id":"upscaled"}]"#));
+        assert!(sarif.contains(
+            r#"{"ruleId":"upscaled","level":"warning","message":{"text":"The 32px frame was upscaled from a 16px source"},"locations":[{"physicalLocation":{"artifactLocation":{"uri":"assets/app \"icon\".ico"}}}]}"#
+        ));
+        assert!(sarif.starts_with(r#"{"version":"2.1.0","#));
+    }
+
+    #[test]
+    fn explains_how_frames_were_produced() {
+        let resized = |size, source_size| FrameReport {
+            size,
+            origin: FrameOrigin::Resized {
+                source: 1,
+                path: Some(PathBuf::from("app-icon-256x256.png")),
+                source_size,
+                filter: FilterType::Lanczos3,
+                sharpened: true,
+                transforms: vec!["Opacity { .. }".to_owned()],
+                cached: true,
+            },
+            quality: Some(Quality {
+                psnr: 41.27,
+                ssim: 0.9876,
+            }),
+        };
+        let report = BuildReport {
+            frames: vec![
+                FrameReport {
+                    size: 16,
+                    origin: FrameOrigin::Encoded,
+                    quality: None,
+                },
+                resized(32, 256),
+                FrameReport {
+                    origin: FrameOrigin::Resized {
+                        source: 0,
+                        path: None,
+                        source_size: 16,
+                        filter: FilterType::Nearest,
+                        sharpened: false,
+                        transforms: Vec::new(),
+                        cached: false,
+                    },
+                    ..resized(48, 16)
+                },
+            ],
+            diagnostics: vec![Diagnostic::ConvertedLargeFrame { size: 256 }],
+        };
+        assert_eq!(
+            report.explain(),
+            "16px: pre-encoded frame, included as is
+32px: resized from app-icon-256x256.png (source #1, 256px) using the Lanczos3 filter
+  chosen as the smallest source of at least 32px
+  then sharpened
+  then transformed by Opacity { .. }
+  read from the cache
+  PSNR 41.3 dB, SSIM 0.988
+48px: resized from in-memory image (source #0, 16px) using the Nearest filter
+  upscaled from a 16px source
+  PSNR 41.3 dB, SSIM 0.988
+Diagnostics:
+* The 256px frame was converted to a 32-bit RGBA PNG
+"
+        );
+        assert_eq!(BuildReport::default().explain(), "No diagnostics\n");
+    }
+}
@@ -0,0 +1,809 @@
+//! Windows resource (`.res`) files containing icons.
+//!
+//! Resource files can be passed directly to the linker, so no resource compiler
+//! such as `rc.exe` or `windres` is needed to embed icons into an executable or DLL.
+
+use crate::filesystem::{Fs, StdFs};
+use crate::reader::IcoFile;
+use crate::{Error, IcoBuilder, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Arc;
+
+const RT_ICON: u16 = 3;
+const RT_GROUP_ICON: u16 = 14;
+const MOVEABLE: u16 = 0x0010;
+const PURE: u16 = 0x0020;
+const DISCARDABLE: u16 = 0x1000;
+const LANG_NEUTRAL: u16 = 0;
+
+const COFF_HEADER_SIZE: u32 = 20;
+const SECTION_HEADER_SIZE: u32 = 40;
+const DATA_ENTRY_SIZE: u32 = 16;
+/// The index of the `.rsrc$02` section symbol, after the `.rsrc$01` symbol and its auxiliary record.
+const DATA_SECTION_SYMBOL: u32 = 2;
+const SUBDIRECTORY: u32 = 0x8000_0000;
+const NAME_STRING: u32 = 0x8000_0000;
+const IMAGE_FILE_32BIT_MACHINE: u16 = 0x0100;
+const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x0000_0040;
+const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+const IMAGE_SYM_ABSOLUTE: u16 = 0xffff;
+const IMAGE_SYM_CLASS_STATIC: u8 = 3;
+
+/// The name of a resource, either a numeric ID or a string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceId {
+    Ordinal(u16),
+    /// String names are case-insensitive. They are stored in upper case, like `rc.exe` does.
+    Name(String),
+}
+
+impl From<u16> for ResourceId {
+    fn from(value: u16) -> Self {
+        ResourceId::Ordinal(value)
+    }
+}
+
+impl From<&str> for ResourceId {
+    fn from(value: &str) -> Self {
+        ResourceId::Name(value.to_owned())
+    }
+}
+
+impl From<String> for ResourceId {
+    fn from(value: String) -> Self {
+        ResourceId::Name(value)
+    }
+}
+
+/// A collection of several distinct icons (e.g. app, document, installer),
+/// each built from its own [`IcoBuilder`], that is written to a single resource file.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::resource::IconLibrary;
+/// let mut app = IcoBuilder::default();
+/// app.add_source_file("app-icon-256x256.png");
+/// let mut document = IcoBuilder::default();
+/// document.add_source_file("document-icon-256x256.png");
+///
+/// IconLibrary::default()
+///     .add_icon(1, app)
+///     .add_icon("DOCUMENT", document)
+///     .build_res_file("icons.res")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct IconLibrary {
+    icons: Vec<(ResourceId, IcoBuilder)>,
+    fs: Arc<dyn Fs>,
+}
+
+impl Default for IconLibrary {
+    fn default() -> Self {
+        IconLibrary {
+            icons: Default::default(),
+            fs: Arc::new(StdFs),
+        }
+    }
+}
+
+impl IconLibrary {
+    /// Adds an icon group with the given resource ID. Building fails with
+    /// [`Error::DuplicateResourceId`] if two groups have the same ID, ignoring the case of names.
+    pub fn add_icon(&mut self, id: impl Into<ResourceId>, icon: IcoBuilder) -> &mut IconLibrary {
+        self.icons.push((id.into(), icon));
+        self
+    }
+
+    /// Customizes the filesystem that the resource file is written to. Defaults to [`StdFs`].
+    /// Sources are read using the filesystem of each icon's builder.
+    pub fn fs(&mut self, fs: impl Fs + 'static) -> &mut IconLibrary {
+        self.fs = Arc::new(fs);
+        self
+    }
+
+    /// Builds all icons and writes them to a `.res` file at `output_file_path`.
+    pub fn build_res_file(&self, output_file_path: impl AsRef<Path>) -> Result<()> {
+        self.fs
+            .write(output_file_path.as_ref(), &write_res(&self.groups()?))?;
+        Ok(())
+    }
+
+    /// Builds all icons and writes them to a COFF object file at `output_file_path`,
+    /// which is what a resource compiler and `cvtres` produce. Unlike `.res` files, which
+    /// only the MSVC linker accepts, objects can also be passed to the GNU linker, so icons can be
+    /// embedded when cross-compiling to `*-pc-windows-gnu` or `*-pc-windows-msvc` from any platform.
+    ///
+    /// ```no_run
+    /// # use ico_builder::IcoBuilder;
+    /// # use ico_builder::resource::{IconLibrary, Machine};
+    /// // build.rs
+    /// let target_arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    /// let machine = Machine::from_target_arch(&target_arch).expect("unsupported architecture");
+    /// let object_path = std::path::Path::new(&std::env::var_os("OUT_DIR").unwrap()).join("icons.o");
+    /// IconLibrary::default()
+    ///     .add_icon(1, IcoBuilder::from_sources(["app-icon-256x256.png"]))
+    ///     .build_coff_file(&object_path, machine)?;
+    /// println!("cargo:rustc-link-arg-bins={}", object_path.display());
+    /// # Ok::<_, ico_builder::Error>(())
+    /// ```
+    pub fn build_coff_file(
+        &self,
+        output_file_path: impl AsRef<Path>,
+        machine: Machine,
+    ) -> Result<()> {
+        self.fs.write(
+            output_file_path.as_ref(),
+            &write_coff(&self.groups()?, machine),
+        )?;
+        Ok(())
+    }
+
+    fn groups(&self) -> Result<Vec<(ResourceId, IcoFile)>> {
+        let mut ids = BTreeSet::new();
+        if let Some((id, _)) = self
+            .icons
+            .iter()
+            .find(|(id, _)| !ids.insert(DirectoryKey::new(id)))
+        {
+            return Err(Error::DuplicateResourceId(id.clone()));
+        }
+        self.icons
+            .iter()
+            .map(|(id, icon)| Ok((id.clone(), IcoFile::parse(&icon.encode_ico()?.0)?)))
+            .collect()
+    }
+}
+
+/// The architecture of a COFF object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Machine {
+    X86,
+    X64,
+    Arm64,
+}
+
+impl Machine {
+    /// The machine for a Rust target architecture like `x86_64`,
+    /// as found in `CARGO_CFG_TARGET_ARCH` in build scripts.
+    pub fn from_target_arch(target_arch: &str) -> Option<Machine> {
+        match target_arch {
+            "x86" => Some(Machine::X86),
+            "x86_64" => Some(Machine::X64),
+            "aarch64" => Some(Machine::Arm64),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> u16 {
+        match self {
+            Machine::X86 => 0x014c,
+            Machine::X64 => 0x8664,
+            Machine::Arm64 => 0xaa64,
+        }
+    }
+
+    /// The relocation type for 32-bit addresses relative to the image base.
+    fn addr32nb(self) -> u16 {
+        match self {
+            Machine::X86 => 0x0007,
+            Machine::X64 => 0x0003,
+            Machine::Arm64 => 0x0002,
+        }
+    }
+}
+
+/// Serializes icon groups into the 32-bit resource file format.
+pub(crate) fn write_res(groups: &[(ResourceId, IcoFile)]) -> Vec<u8> {
+    let mut output = Vec::new();
+    // Every 32-bit resource file starts with an empty entry.
+    write_resource(
+        &mut output,
+        &Resource {
+            resource_type: ResourceId::Ordinal(0),
+            name: ResourceId::Ordinal(0),
+            memory_flags: 0,
+            data: Vec::new(),
+        },
+    );
+    for resource in resources(groups) {
+        write_resource(&mut output, &resource);
+    }
+    output
+}
+
+/// A resource of the icon groups, as stored in resource files and objects.
+struct Resource {
+    resource_type: ResourceId,
+    name: ResourceId,
+    memory_flags: u16,
+    data: Vec<u8>,
+}
+
+/// The icon and group resources for the icon groups.
+/// Icon resources are numbered sequentially across all groups, and the group entries
+/// have the same fields as the entries of the ICO files.
+fn resources(groups: &[(ResourceId, IcoFile)]) -> Vec<Resource> {
+    let mut resources = Vec::new();
+    let mut next_icon_id = 1;
+    for (group_id, icon) in groups {
+        let mut group = Vec::new();
+        write_u16(&mut group, 0);
+        write_u16(&mut group, 1);
+        write_u16(&mut group, icon.entries().len() as u16);
+        for entry in icon.entries() {
+            let icon_id = next_icon_id;
+            next_icon_id += 1;
+            resources.push(Resource {
+                resource_type: ResourceId::Ordinal(RT_ICON),
+                name: ResourceId::Ordinal(icon_id),
+                memory_flags: MOVEABLE | DISCARDABLE,
+                data: entry.data().to_owned(),
+            });
+
+            // Sizes of 256 are stored as 0.
+            group.push(entry.width() as u8);
+            group.push(entry.height() as u8);
+            group.push(entry.color_count());
+            group.push(0); // reserved
+            write_u16(&mut group, entry.planes().max(1)); // planes, which the ICO encoder leaves at 0
+            write_u16(&mut group, entry.bit_count());
+            write_u32(&mut group, entry.data().len() as u32);
+            write_u16(&mut group, icon_id);
+        }
+        resources.push(Resource {
+            resource_type: ResourceId::Ordinal(RT_GROUP_ICON),
+            name: group_id.clone(),
+            memory_flags: MOVEABLE | PURE | DISCARDABLE,
+            data: group,
+        });
+    }
+    resources
+}
+
+fn write_resource(output: &mut Vec<u8>, resource: &Resource) {
+    let mut header = Vec::new();
+    write_resource_id(&mut header, &resource.resource_type);
+    write_resource_id(&mut header, &resource.name);
+    pad_to_u32(&mut header);
+    write_u32(&mut header, 0); // data version
+    write_u16(&mut header, resource.memory_flags);
+    write_u16(&mut header, LANG_NEUTRAL);
+    write_u32(&mut header, 0); // version
+    write_u32(&mut header, 0); // characteristics
+
+    write_u32(output, resource.data.len() as u32);
+    write_u32(output, header.len() as u32 + 8);
+    output.extend_from_slice(&header);
+    output.extend_from_slice(&resource.data);
+    pad_to_u32(output);
+}
+
+/// Serializes icon groups into a COFF object with the `.rsrc$01` section containing
+/// the resource directory and the `.rsrc$02` section containing the resource data.
+pub(crate) fn write_coff(groups: &[(ResourceId, IcoFile)], machine: Machine) -> Vec<u8> {
+    let resources = resources(groups);
+    let mut tree = BTreeMap::<DirectoryKey, BTreeMap<DirectoryKey, &Resource>>::new();
+    for resource in &resources {
+        tree.entry(DirectoryKey::new(&resource.resource_type))
+            .or_default()
+            .insert(DirectoryKey::new(&resource.name), resource);
+    }
+
+    // The root table lists the types, which list the names, which list the languages.
+    // They are followed by the data entries and the strings of the names.
+    let resource_count: usize = tree.values().map(BTreeMap::len).sum();
+    let name_tables_offset = table_size(tree.len());
+    let language_tables_offset = name_tables_offset
+        + tree
+            .values()
+            .map(|names| table_size(names.len()))
+            .sum::<u32>();
+    let data_entries_offset = language_tables_offset + resource_count as u32 * table_size(1);
+    let strings_offset = data_entries_offset + resource_count as u32 * DATA_ENTRY_SIZE;
+
+    let mut directory = Vec::new();
+    let mut strings = Vec::new();
+    let mut string_offset = |key: &DirectoryKey| {
+        let offset = strings_offset + strings.len() as u32;
+        if let DirectoryKey::Name(name) = key {
+            write_u16(&mut strings, name.len() as u16);
+            for &unit in name {
+                write_u16(&mut strings, unit);
+            }
+        }
+        offset
+    };
+    write_table(&mut directory, tree.keys());
+    let mut next_table_offset = name_tables_offset;
+    for key in tree.keys() {
+        write_entry(
+            &mut directory,
+            key,
+            &mut string_offset,
+            SUBDIRECTORY | next_table_offset,
+        );
+        next_table_offset += table_size(tree[key].len());
+    }
+    let mut next_table_offset = language_tables_offset;
+    for names in tree.values() {
+        write_table(&mut directory, names.keys());
+        for key in names.keys() {
+            write_entry(
+                &mut directory,
+                key,
+                &mut string_offset,
+                SUBDIRECTORY | next_table_offset,
+            );
+            next_table_offset += table_size(1);
+        }
+    }
+    let language = DirectoryKey::Ordinal(LANG_NEUTRAL);
+    for index in 0..resource_count as u32 {
+        write_table(&mut directory, [&language]);
+        let data_entry_offset = data_entries_offset + index * DATA_ENTRY_SIZE;
+        write_entry(
+            &mut directory,
+            &language,
+            &mut string_offset,
+            data_entry_offset,
+        );
+    }
+
+    // The data entries point into the data section, which the linker resolves via relocations.
+    let mut data = Vec::new();
+    let mut relocations = Vec::new();
+    for (index, resource) in tree.values().flat_map(BTreeMap::values).enumerate() {
+        write_u32(&mut directory, data.len() as u32);
+        write_u32(&mut directory, resource.data.len() as u32);
+        write_u32(&mut directory, 0); // code page
+        write_u32(&mut directory, 0); // reserved
+        write_u32(
+            &mut relocations,
+            data_entries_offset + index as u32 * DATA_ENTRY_SIZE,
+        );
+        write_u32(&mut relocations, DATA_SECTION_SYMBOL);
+        write_u16(&mut relocations, machine.addr32nb());
+        data.extend_from_slice(&resource.data);
+        while data.len() % 8 != 0 {
+            data.push(0);
+        }
+    }
+    directory.append(&mut strings);
+    pad_to_u32(&mut directory);
+
+    let directory_offset = COFF_HEADER_SIZE + 2 * SECTION_HEADER_SIZE;
+    let relocations_offset = directory_offset + directory.len() as u32;
+    let data_offset = relocations_offset + relocations.len() as u32;
+    let symbols_offset = data_offset + data.len() as u32;
+
+    let mut output = Vec::new();
+    write_u16(&mut output, machine.code());
+    write_u16(&mut output, 2); // number of sections
+    write_u32(&mut output, 0); // time stamp, left out for reproducible builds
+    write_u32(&mut output, symbols_offset);
+    write_u32(&mut output, 4 + u32::from(machine == Machine::X86));
+    write_u16(&mut output, 0); // size of optional header
+    let characteristics = match machine {
+        Machine::X86 => IMAGE_FILE_32BIT_MACHINE,
+        _ => 0,
+    };
+    write_u16(&mut output, characteristics);
+    write_section_header(
+        &mut output,
+        b".rsrc$01",
+        directory.len() as u32,
+        directory_offset,
+        relocations_offset,
+        resource_count as u16,
+    );
+    write_section_header(
+        &mut output,
+        b".rsrc$02",
+        data.len() as u32,
+        data_offset,
+        0,
+        0,
+    );
+    output.append(&mut directory);
+    output.append(&mut relocations);
+    output.append(&mut data);
+
+    write_symbol(
+        &mut output,
+        b".rsrc$01",
+        0,
+        1,
+        Some((relocations_offset - directory_offset, resource_count as u16)),
+    );
+    write_symbol(
+        &mut output,
+        b".rsrc$02",
+        0,
+        2,
+        Some((symbols_offset - data_offset, 0)),
+    );
+    if machine == Machine::X86 {
+        // Marks the object as compatible with safe exception handlers, which `link.exe` requires.
+        write_symbol(&mut output, b"@feat.00", 1, IMAGE_SYM_ABSOLUTE, None);
+    }
+    write_u32(&mut output, 4); // size of the empty string table
+    output
+}
+
+/// A key in the resource directory. Names come before ordinals.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum DirectoryKey {
+    Name(Vec<u16>),
+    Ordinal(u16),
+}
+
+impl DirectoryKey {
+    fn new(id: &ResourceId) -> DirectoryKey {
+        match id {
+            ResourceId::Ordinal(ordinal) => DirectoryKey::Ordinal(*ordinal),
+            ResourceId::Name(name) => {
+                DirectoryKey::Name(name.to_uppercase().encode_utf16().collect())
+            }
+        }
+    }
+}
+
+fn table_size(entries: usize) -> u32 {
+    16 + 8 * entries as u32
+}
+
+fn write_table<'a>(output: &mut Vec<u8>, keys: impl IntoIterator<Item = &'a DirectoryKey>) {
+    let (mut names, mut ordinals) = (0, 0);
+    for key in keys {
+        match key {
+            DirectoryKey::Name(_) => names += 1,
+            DirectoryKey::Ordinal(_) => ordinals += 1,
+        }
+    }
+    write_u32(output, 0); // characteristics
+    write_u32(output, 0); // time stamp
+    write_u32(output, 0); // version
+    write_u16(output, names);
+    write_u16(output, ordinals);
+}
+
+fn write_entry(
+    output: &mut Vec<u8>,
+    key: &DirectoryKey,
+    string_offset: &mut impl FnMut(&DirectoryKey) -> u32,
+    offset: u32,
+) {
+    match key {
+        DirectoryKey::Name(_) => write_u32(output, NAME_STRING | string_offset(key)),
+        DirectoryKey::Ordinal(ordinal) => write_u32(output, u32::from(*ordinal)),
+    }
+    write_u32(output, offset);
+}
+
+fn write_section_header(
+    output: &mut Vec<u8>,
+    name: &[u8; 8],
+    size: u32,
+    data_offset: u32,
+    relocations_offset: u32,
+    relocation_count: u16,
+) {
+    output.extend_from_slice(name);
+    write_u32(output, 0); // virtual size
+    write_u32(output, 0); // virtual address
+    write_u32(output, size);
+    write_u32(output, data_offset);
+    write_u32(output, relocations_offset);
+    write_u32(output, 0); // line numbers
+    write_u16(output, relocation_count);
+    write_u16(output, 0); // line number count
+    write_u32(output, IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ);
+}
+
+/// Writes a static symbol, with an auxiliary record of the section's size and
+/// relocation count for section symbols.
+fn write_symbol(
+    output: &mut Vec<u8>,
+    name: &[u8; 8],
+    value: u32,
+    section: u16,
+    section_definition: Option<(u32, u16)>,
+) {
+    output.extend_from_slice(name);
+    write_u32(output, value);
+    write_u16(output, section);
+    write_u16(output, 0); // type
+    output.push(IMAGE_SYM_CLASS_STATIC);
+    output.push(section_definition.is_some().into());
+    if let Some((size, relocation_count)) = section_definition {
+        write_u32(output, size);
+        write_u16(output, relocation_count);
+        // Line numbers, checksum, section number and selection, which only matter for COMDATs.
+        output.extend_from_slice(&[0; 12]);
+    }
+}
+
+fn write_resource_id(output: &mut Vec<u8>, id: &ResourceId) {
+    match id {
+        ResourceId::Ordinal(ordinal) => {
+            write_u16(output, 0xFFFF);
+            write_u16(output, *ordinal);
+        }
+        ResourceId::Name(name) => {
+            for unit in name.to_uppercase().encode_utf16() {
+                write_u16(output, unit);
+            }
+            write_u16(output, 0);
+        }
+    }
+}
+
+fn pad_to_u32(output: &mut Vec<u8>) {
+    while output.len() % 4 != 0 {
+        output.push(0);
+    }
+}
+
+fn write_u16(output: &mut Vec<u8>, value: u16) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{u16_at, u32_at};
+
+    /// An icon with a single 16px entry whose image data is `data`.
+    fn icon(data: &[u8]) -> IcoFile {
+        let mut file = vec![0, 0, 1, 0, 1, 0, 16, 16, 0, 0, 1, 0, 32, 0];
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&22u32.to_le_bytes());
+        file.extend_from_slice(data);
+        IcoFile::parse(&file).unwrap()
+    }
+
+    /// The group resource of [`icon`] with 4 bytes of image data and the icon ID 1.
+    const GROUP: [u8; 20] = [
+        0, 0, 1, 0, 1, 0, // reserved, type, count
+        16, 16, 0, 0, 1, 0, 32, 0, 4, 0, 0, 0, 1, 0, // the entry
+    ];
+
+    #[test]
+    fn writes_res() {
+        let res = write_res(&[("app".into(), icon(&[1, 2, 3, 4]))]);
+        #[rustfmt::skip]
+        let expected: Vec<u8> = [
+            // The empty entry.
+            &[0, 0, 0, 0, 32, 0, 0, 0][..],
+            &[0xff, 0xff, 0, 0, 0xff, 0xff, 0, 0],
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // The icon: RT_ICON, ID 1, moveable and discardable.
+            &[4, 0, 0, 0, 32, 0, 0, 0],
+            &[0xff, 0xff, 3, 0, 0xff, 0xff, 1, 0],
+            &[0, 0, 0, 0, 0x10, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &[1, 2, 3, 4],
+            // The group: RT_GROUP_ICON, "APP", also pure.
+            &[20, 0, 0, 0, 36, 0, 0, 0],
+            &[0xff, 0xff, 14, 0, b'A', 0, b'P', 0, b'P', 0, 0, 0],
+            &[0, 0, 0, 0, 0x30, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &GROUP,
+        ]
+        .concat();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn writes_coff() {
+        let coff = write_coff(&[(1.into(), icon(&[1, 2, 3, 4]))], Machine::X64);
+        let subdirectory = |offset: u8| [offset, 0, 0, 0x80];
+        #[rustfmt::skip]
+        let expected: Vec<u8> = [
+            // The file header: AMD64, 2 sections, symbols at 312, 4 symbols.
+            &[0x64, 0x86, 2, 0, 0, 0, 0, 0, 0x38, 1, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0][..],
+            // .rsrc$01 of 160 bytes at 100, with 2 relocations at 260.
+            b".rsrc$01",
+            &[0, 0, 0, 0, 0, 0, 0, 0, 160, 0, 0, 0, 100, 0, 0, 0, 4, 1, 0, 0],
+            &[0, 0, 0, 0, 2, 0, 0, 0, 0x40, 0, 0, 0x40],
+            // .rsrc$02 of 32 bytes at 280.
+            b".rsrc$02",
+            &[0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0, 0, 0x18, 1, 0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0x40],
+            // The root table with the types RT_ICON and RT_GROUP_ICON.
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0],
+            &[3, 0, 0, 0], &subdirectory(32),
+            &[14, 0, 0, 0], &subdirectory(56),
+            // The name tables with the IDs 1.
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+            &[1, 0, 0, 0], &subdirectory(80),
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+            &[1, 0, 0, 0], &subdirectory(104),
+            // The language tables with the neutral language.
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+            &[0, 0, 0, 0, 128, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+            &[0, 0, 0, 0, 144, 0, 0, 0],
+            // The data entries, relative to .rsrc$02.
+            &[0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &[8, 0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // IMAGE_REL_AMD64_ADDR32NB relocations of the data entries against symbol 2.
+            &[128, 0, 0, 0, 2, 0, 0, 0, 3, 0],
+            &[144, 0, 0, 0, 2, 0, 0, 0, 3, 0],
+            // The data, aligned to 8 bytes.
+            &[1, 2, 3, 4, 0, 0, 0, 0],
+            &GROUP, &[0, 0, 0, 0],
+            // The static section symbols with their auxiliary records.
+            b".rsrc$01", &[0, 0, 0, 0, 1, 0, 0, 0, 3, 1],
+            &[160, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            b".rsrc$02", &[0, 0, 0, 0, 2, 0, 0, 0, 3, 1],
+            &[32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            // The empty string table.
+            &[4, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(coff, expected);
+    }
+
+    /// The data offset, size, relocations offset and relocation count of a section, by its name.
+    fn section(coff: &[u8], name: &[u8; 8]) -> (usize, usize, usize, usize) {
+        let count = usize::from(u16_at(coff, 2));
+        (0..count)
+            .map(|index| COFF_HEADER_SIZE as usize + index * SECTION_HEADER_SIZE as usize)
+            .find(|&header| &coff[header..header + 8] == name)
+            .map(|header| {
+                (
+                    u32_at(coff, header + 20) as usize,
+                    u32_at(coff, header + 16) as usize,
+                    u32_at(coff, header + 24) as usize,
+                    usize::from(u16_at(coff, header + 32)),
+                )
+            })
+            .expect("the section exists")
+    }
+
+    /// The string or ordinal of a directory entry.
+    fn entry_key(directory: &[u8], entry: usize) -> ResourceId {
+        let name = u32_at(directory, entry);
+        if name & NAME_STRING == 0 {
+            return ResourceId::Ordinal(name as u16);
+        }
+        let offset = (name & !NAME_STRING) as usize;
+        let length = usize::from(u16_at(directory, offset));
+        let units: Vec<u16> = (0..length)
+            .map(|index| u16_at(directory, offset + 2 + 2 * index))
+            .collect();
+        ResourceId::Name(String::from_utf16(&units).unwrap())
+    }
+
+    /// The entries of the table at `offset`, as their keys and offsets.
+    fn table(directory: &[u8], offset: usize) -> Vec<(ResourceId, u32)> {
+        let count = usize::from(u16_at(directory, offset + 12) + u16_at(directory, offset + 14));
+        (0..count)
+            .map(|index| {
+                let entry = offset + 16 + 8 * index;
+                (entry_key(directory, entry), u32_at(directory, entry + 4))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn coff_resources_can_be_read_back() {
+        let groups = [
+            ("Document".into(), icon(&[5; 13])),
+            (7.into(), icon(&[6; 2])),
+        ];
+        for machine in [Machine::X86, Machine::X64, Machine::Arm64] {
+            let coff = write_coff(&groups, machine);
+            assert_eq!(u16_at(&coff, 0), machine.code());
+            let (directory_offset, directory_size, relocations_offset, relocation_count) =
+                section(&coff, b".rsrc$01");
+            let (data_offset, data_size, _, _) = section(&coff, b".rsrc$02");
+            let directory = &coff[directory_offset..directory_offset + directory_size];
+            let data = &coff[data_offset..data_offset + data_size];
+            let relocations: Vec<usize> = (0..relocation_count)
+                .map(|index| {
+                    let relocation = relocations_offset + 10 * index;
+                    assert_eq!(u32_at(&coff, relocation + 4), DATA_SECTION_SYMBOL);
+                    assert_eq!(u16_at(&coff, relocation + 8), machine.addr32nb());
+                    u32_at(&coff, relocation) as usize
+                })
+                .collect();
+
+            // The symbol the relocations refer to is the section symbol of .rsrc$02.
+            let symbols_offset = u32_at(&coff, 8) as usize;
+            let symbol = symbols_offset + 18 * DATA_SECTION_SYMBOL as usize;
+            assert_eq!(&coff[symbol..symbol + 8], b".rsrc$02");
+            assert_eq!(u16_at(&coff, symbol + 12), 2);
+
+            let mut resources = Vec::new();
+            for (resource_type, names) in table(directory, 0) {
+                assert_ne!(names & SUBDIRECTORY, 0);
+                for (name, languages) in table(directory, (names & !SUBDIRECTORY) as usize) {
+                    assert_ne!(languages & SUBDIRECTORY, 0);
+                    let languages = table(directory, (languages & !SUBDIRECTORY) as usize);
+                    let [(ResourceId::Ordinal(LANG_NEUTRAL), data_entry)] = languages[..] else {
+                        panic!("expected a single neutral language, got {languages:?}");
+                    };
+                    let data_entry = data_entry as usize;
+                    assert!(relocations.contains(&data_entry));
+                    let offset = u32_at(directory, data_entry) as usize;
+                    let size = u32_at(directory, data_entry + 4) as usize;
+                    resources.push((resource_type.clone(), name, &data[offset..offset + size]));
+                }
+            }
+            assert_eq!(relocations.len(), resources.len());
+
+            let group = |size: u8, icon_id: u8| {
+                let mut group = GROUP.to_vec();
+                group[14] = size;
+                group[18] = icon_id;
+                group
+            };
+            let (document, seven) = (group(13, 1), group(2, 2));
+            assert_eq!(
+                resources,
+                [
+                    (
+                        ResourceId::Ordinal(RT_ICON),
+                        ResourceId::Ordinal(1),
+                        &[5; 13][..]
+                    ),
+                    (
+                        ResourceId::Ordinal(RT_ICON),
+                        ResourceId::Ordinal(2),
+                        &[6; 2]
+                    ),
+                    (
+                        ResourceId::Ordinal(RT_GROUP_ICON),
+                        ResourceId::Name("DOCUMENT".into()),
+                        &document
+                    ),
+                    (
+                        ResourceId::Ordinal(RT_GROUP_ICON),
+                        ResourceId::Ordinal(7),
+                        &seven
+                    ),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let mut icon = IcoBuilder::default();
+        icon.sizes(&[16])
+            .add_source_image(image::RgbaImage::new(16, 16));
+        let fs = Arc::new(crate::filesystem::MemoryFs::default());
+
+        let mut library = IconLibrary::default();
+        library
+            .fs(fs.clone())
+            .add_icon(1, icon.clone())
+            .add_icon("app", icon.clone());
+        library.build_res_file("icons.res").unwrap();
+        library.add_icon("APP", icon.clone());
+        let error = library
+            .build_coff_file("icons.o", Machine::X64)
+            .unwrap_err();
+        assert!(
+            matches!(&error, Error::DuplicateResourceId(ResourceId::Name(name)) if name == "APP")
+        );
+        assert_eq!(
+            error.to_string(),
+            "More than one icon has the resource name APP, ignoring case"
+        );
+
+        let mut library = IconLibrary::default();
+        library.fs(fs).add_icon(1, icon.clone()).add_icon(1, icon);
+        assert!(matches!(
+            library.build_res_file("icons.res"),
+            Err(Error::DuplicateResourceId(ResourceId::Ordinal(1)))
+        ));
+    }
+}
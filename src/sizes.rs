@@ -0,0 +1,460 @@
+use crate::reader::IcoEntry;
+use crate::{Error, Result};
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, RangeInclusive};
+use std::sync::{Arc, OnceLock};
+
+/// A list of icon sizes. The sizes are always sorted in ascending order and free of duplicates.
+///
+/// ```
+/// # use ico_builder::IconSizes;
+/// let sizes: IconSizes = [64, 16, 32, 16].into_iter().collect();
+/// assert_eq!(&*sizes, &[16, 32, 64]);
+/// assert_eq!(&*sizes.union(&IconSizes::MINIMAL), &[16, 24, 32, 48, 64, 256]);
+/// ```
+#[derive(Clone)]
+pub struct IconSizes(Sizes);
+
+#[derive(Clone)]
+enum Sizes {
+    Sorted(Cow<'static, [u32]>),
+    /// A slice passed to [`IconSizes::new`] that isn't sorted or has duplicates.
+    /// Constants can't allocate, so it is sorted when the sizes are first used.
+    Unsorted(&'static [u32], OnceLock<Vec<u32>>),
+}
+
+impl IconSizes {
+    /// The [bare minimum] recommended icon sizes: 16x16, 24x24, 32x32, 48x48, and 256x256.
+    ///
+    /// [bare minimum]: https://learn.microsoft.com/en-us/windows/apps/design/style/iconography/app-icon-construction#icon-scaling
+    pub const MINIMAL: Self = Self::new(&[16, 24, 32, 48, 256]);
+
+    /// Sizes for `favicon.ico`: 16x16, 32x32, and 48x48.
+    pub const FAVICON: Self = Self::new(&[16, 32, 48]);
+
+    /// Sizes for [taskbar overlay icons](crate::overlay) at 100% to 200% display scaling:
+    /// 16x16, 20x20, 24x24, and 32x32.
+    pub const OVERLAY: Self = Self::new(&[16, 20, 24, 32]);
+
+    /// Sizes Explorer uses for [file-type icons](crate::document) in its views at 100% to 200% display scaling:
+    /// 16x16, 20x20, 24x24, 32x32, 40x40, 48x48, 64x64, 96x96, and 256x256.
+    pub const DOCUMENT: Self = Self::new(&[16, 20, 24, 32, 40, 48, 64, 96, 256]);
+
+    /// Creates a list of sizes from a static slice, e.g. for constants.
+    /// Like all sizes, they are sorted and deduplicated.
+    pub const fn new(sizes: &'static [u32]) -> IconSizes {
+        let mut index = 1;
+        while index < sizes.len() {
+            if sizes[index - 1] >= sizes[index] {
+                return Self(Sizes::Unsorted(sizes, OnceLock::new()));
+            }
+            index += 1;
+        }
+        Self(Sizes::Sorted(Cow::Borrowed(sizes)))
+    }
+
+    /// The powers of two within `range`.
+    ///
+    /// ```
+    /// # use ico_builder::IconSizes;
+    /// assert_eq!(&*IconSizes::powers_of_two(16..=256), &[16, 32, 64, 128, 256]);
+    /// assert_eq!(&*IconSizes::powers_of_two(20..=100), &[32, 64]);
+    /// ```
+    pub fn powers_of_two(range: RangeInclusive<u32>) -> IconSizes {
+        (0..u32::BITS)
+            .map(|exponent| 1 << exponent)
+            .filter(|size| range.contains(size))
+            .collect()
+    }
+
+    /// A base size multiplied by each of the display scale factors, rounded to the nearest pixel.
+    /// Sizes that round to `0` are left out.
+    ///
+    /// ```
+    /// # use ico_builder::IconSizes;
+    /// let sizes = IconSizes::from_base_and_scales(32, &[1.0, 1.25, 1.5, 2.0]);
+    /// assert_eq!(&*sizes, &[32, 40, 48, 64]);
+    /// ```
+    pub fn from_base_and_scales(base: u32, scales: &[f32]) -> IconSizes {
+        scales
+            .iter()
+            .map(|scale| (base as f32 * scale).round() as u32)
+            .filter(|&size| size > 0)
+            .collect()
+    }
+
+    /// The sizes Windows scales `base_size` to at each of the display scale factors.
+    /// Like Windows, each scale factor is converted to a DPI relative to 96 DPI at 100%,
+    /// which the size is multiplied with and divided by 96, rounding halves up.
+    /// This avoids the floating point errors of [`IconSizes::from_base_and_scales`].
+    ///
+    /// ```
+    /// # use ico_builder::IconSizes;
+    /// let sizes = IconSizes::for_dpi_scales(16, &[1.0, 1.25, 1.5, 1.75, 2.0]);
+    /// assert_eq!(&*sizes, &[16, 20, 24, 28, 32]);
+    /// assert_eq!(&*IconSizes::for_dpi_scales(30, &[1.75]), &[53]);
+    /// ```
+    pub fn for_dpi_scales(base_size: u32, scales: &[f32]) -> IconSizes {
+        scales
+            .iter()
+            .map(|scale| (96.0 * scale).round() as u64)
+            .map(|dpi| ((u64::from(base_size) * dpi + 48) / 96) as u32)
+            .filter(|&size| size > 0)
+            .collect()
+    }
+
+    /// All sizes that are in `self`, `other`, or both.
+    pub fn union(&self, other: &IconSizes) -> IconSizes {
+        self.iter().chain(other.iter()).copied().collect()
+    }
+}
+
+impl Default for IconSizes {
+    fn default() -> Self {
+        IconSizes::MINIMAL
+    }
+}
+
+impl<'a, I> From<I> for IconSizes
+where
+    I: IntoIterator<Item = &'a u32>,
+{
+    fn from(value: I) -> Self {
+        value.into_iter().copied().collect()
+    }
+}
+
+impl FromIterator<u32> for IconSizes {
+    fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+        let mut sizes: Vec<_> = iter.into_iter().collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        IconSizes(Sizes::Sorted(sizes.into()))
+    }
+}
+
+impl IntoIterator for IconSizes {
+    type Item = u32;
+    type IntoIter = std::vec::IntoIter<u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self.0 {
+            Sizes::Sorted(sizes) => sizes.into_owned().into_iter(),
+            Sizes::Unsorted(sizes, _) => IconSizes::from(sizes).into_iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a IconSizes {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Deref for IconSizes {
+    type Target = [u32];
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            Sizes::Sorted(sizes) => sizes,
+            Sizes::Unsorted(sizes, sorted) => sorted.get_or_init(|| {
+                let mut sizes = sizes.to_vec();
+                sizes.sort_unstable();
+                sizes.dedup();
+                sizes
+            }),
+        }
+    }
+}
+
+impl PartialEq for IconSizes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for IconSizes {}
+
+impl Hash for IconSizes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl fmt::Debug for IconSizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IconSizes").field(&&**self).finish()
+    }
+}
+
+/// The oldest version of Windows that icons are built for, for [recommending sizes](SizeRecommendation).
+/// Windows Vista added 256px PNG frames, and the versions since show icons at the same sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum WindowsVersion {
+    Xp,
+    /// Windows Vista and all newer versions.
+    Vista,
+}
+
+/// Recommends the sizes and encodings of frames for the supported Windows versions and
+/// display scales, so that they can be chosen by intent instead of magic numbers.
+///
+/// Windows shows icons at 16px, 24px, 32px and 48px multiplied by the display scale,
+/// plus 256px for large views since Windows Vista.
+///
+/// ```
+/// # use ico_builder::{IconSizes, SizeRecommendation, WindowsVersion};
+/// // Windows Vista and newer, with up to 200% display scaling.
+/// let recommendation = SizeRecommendation::new(WindowsVersion::Vista).max_scale(200);
+/// assert_eq!(&*recommendation.sizes(), &[16, 20, 24, 30, 32, 36, 40, 48, 60, 64, 72, 96, 256]);
+/// assert_eq!(SizeRecommendation::new(WindowsVersion::Vista).sizes(), IconSizes::MINIMAL);
+/// assert_eq!(&*SizeRecommendation::new(WindowsVersion::Xp).sizes(), &[16, 24, 32, 48]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeRecommendation {
+    oldest_version: WindowsVersion,
+    max_scale: u32,
+}
+
+impl SizeRecommendation {
+    /// The sizes Windows shows icons at, at 100% display scale.
+    const BASE_SIZES: [u32; 4] = [16, 24, 32, 48];
+    /// The display scales Windows offers, in percent, that assets are commonly provided for.
+    const SCALES: [u32; 7] = [100, 125, 150, 200, 250, 300, 400];
+
+    /// Recommends sizes for `oldest_version` and newer at 100% display scale.
+    pub fn new(oldest_version: WindowsVersion) -> SizeRecommendation {
+        SizeRecommendation {
+            oldest_version,
+            max_scale: 100,
+        }
+    }
+
+    /// Customizes the highest supported display scale in percent, from `100` to `400`.
+    /// Defaults to `100`.
+    pub fn max_scale(mut self, max_scale: u32) -> Self {
+        self.max_scale = max_scale.clamp(100, 400);
+        self
+    }
+
+    /// The recommended sizes. Windows XP doesn't support frames above 48px,
+    /// so they are left out when it is supported.
+    pub fn sizes(&self) -> IconSizes {
+        let max_size = if self.xp_compatible() { 48 } else { 256 };
+        Self::SCALES
+            .iter()
+            .filter(|&&scale| scale <= self.max_scale)
+            .flat_map(|scale| Self::BASE_SIZES.map(|size| (size * scale).div_ceil(100)))
+            .chain((!self.xp_compatible()).then_some(256))
+            .filter(|&size| size <= max_size)
+            .collect()
+    }
+
+    /// Whether the frames should be encoded as BMPs for Windows XP,
+    /// see [`IcoBuilder::xp_compatible`](crate::IcoBuilder::xp_compatible).
+    pub fn xp_compatible(&self) -> bool {
+        self.oldest_version == WindowsVersion::Xp
+    }
+}
+
+/// The order of the entries in the ICO directory.
+/// Some legacy consumers and diff-based workflows depend on a specific order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryOrder {
+    /// Smallest size first.
+    #[default]
+    Ascending,
+    /// Largest size first.
+    Descending,
+    /// The listed sizes first, in the given order, followed by the remaining sizes in ascending order.
+    Explicit(Vec<u32>),
+}
+
+impl EntryOrder {
+    pub(crate) fn apply(&self, sizes: &IconSizes) -> Vec<u32> {
+        match self {
+            EntryOrder::Ascending => sizes.to_vec(),
+            EntryOrder::Descending => sizes.iter().rev().copied().collect(),
+            EntryOrder::Explicit(order) => {
+                let mut ordered: Vec<u32> = Vec::with_capacity(sizes.len());
+                for size in order.iter().chain(sizes.iter()) {
+                    if sizes.contains(size) && !ordered.contains(size) {
+                        ordered.push(*size);
+                    }
+                }
+                ordered
+            }
+        }
+    }
+}
+
+/// Which source each frame is resized from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SelectionStrategy {
+    /// The smallest source that is at least as large as the frame, which gives
+    /// the sharpest result for artwork designed for specific sizes.
+    #[default]
+    NextBigger,
+    /// The source closest in size to the frame, even if it is smaller.
+    /// Ties are resolved in favor of the larger source. Upscaled frames are
+    /// reported as a [`Diagnostic::Upscaled`](crate::Diagnostic::Upscaled).
+    NearestAnyDirection,
+    /// The largest source, so that all frames look consistent.
+    AlwaysLargest,
+    /// Only a source of exactly the frame's size, so that no frame is ever resized.
+    ExactOnly,
+}
+
+impl SelectionStrategy {
+    /// The selected icon along with its index. Unless the strategy picks a smaller icon itself,
+    /// the largest icon is used for sizes without a large enough icon if `allow_upscaling` is set.
+    pub(crate) fn select(
+        self,
+        icons: &[Arc<DynamicImage>],
+        size: u32,
+        allow_upscaling: bool,
+    ) -> Result<(usize, &DynamicImage)> {
+        let icons = icons.iter().map(Arc::as_ref).enumerate();
+        let selected = match self {
+            SelectionStrategy::NextBigger => icons
+                .clone()
+                .filter(|(_, icon)| icon.width() >= size)
+                .min_by_key(|(_, icon)| icon.width()),
+            SelectionStrategy::NearestAnyDirection => icons
+                .clone()
+                .min_by_key(|(_, icon)| (icon.width().abs_diff(size), Reverse(icon.width()))),
+            SelectionStrategy::AlwaysLargest => icons
+                .clone()
+                .max_by_key(|(_, icon)| icon.width())
+                .filter(|(_, icon)| icon.width() >= size),
+            SelectionStrategy::ExactOnly => icons.clone().find(|(_, icon)| icon.width() == size),
+        };
+        match selected {
+            Some(icon) => Ok(icon),
+            None if allow_upscaling && self != SelectionStrategy::ExactOnly => icons
+                .max_by_key(|(_, icon)| icon.width())
+                .ok_or(Error::MissingIconSize(size)),
+            None => Err(Error::MissingIconSize(size)),
+        }
+    }
+}
+
+/// Values for the fields of ICO directory entries that replace the derived ones,
+/// since some legacy consumers select frames based on these fields.
+/// By default, the bit count is derived from the encoded frame, and the color count and
+/// the number of color planes are `0`.
+///
+/// ```
+/// # use ico_builder::EntryOverrides;
+/// // Declare one color plane for all entries and 8 bits per pixel for the 16px entry.
+/// let all = EntryOverrides::all().planes(1);
+/// let small = EntryOverrides::for_size(16).bit_count(8);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryOverrides {
+    size: Option<u32>,
+    bit_count: Option<u16>,
+    color_count: Option<u8>,
+    planes: Option<u16>,
+}
+
+impl EntryOverrides {
+    /// Overrides that apply to all entries.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Overrides that only apply to entries of the given size.
+    pub fn for_size(size: u32) -> Self {
+        Self {
+            size: Some(size),
+            ..Self::default()
+        }
+    }
+
+    /// Declares the number of bits per pixel.
+    pub fn bit_count(mut self, bit_count: u16) -> Self {
+        self.bit_count = Some(bit_count);
+        self
+    }
+
+    /// Declares the number of colors in the palette, where `0` means no palette.
+    pub fn color_count(mut self, color_count: u8) -> Self {
+        self.color_count = Some(color_count);
+        self
+    }
+
+    /// Declares the number of color planes.
+    pub fn planes(mut self, planes: u16) -> Self {
+        self.planes = Some(planes);
+        self
+    }
+
+    /// Applies the overrides to an entry if it has the right size.
+    pub(crate) fn apply(&self, entry: &IcoEntry) -> IcoEntry {
+        if self.size.is_some_and(|size| size != entry.width()) {
+            return entry.clone();
+        }
+        IcoEntry::new(
+            entry.width(),
+            entry.height(),
+            self.color_count.unwrap_or(entry.color_count()),
+            self.planes.unwrap_or(entry.planes()),
+            self.bit_count.unwrap_or(entry.bit_count()),
+            entry.data(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsorted_sizes_are_sorted_and_deduplicated() {
+        assert_eq!(&*IconSizes::from(&[256, 16, 32, 16]), &[16, 32, 256]);
+        assert_eq!(IconSizes::from(&[48, 32, 16]), IconSizes::FAVICON);
+    }
+
+    #[test]
+    fn new_sorts_and_deduplicates() {
+        static UNSORTED: IconSizes = IconSizes::new(&[256, 16, 48, 16]);
+        assert_eq!(&*UNSORTED, &[16, 48, 256]);
+        assert_eq!(UNSORTED, IconSizes::from(&[16, 48, 256]));
+        assert_eq!(
+            UNSORTED.clone().into_iter().collect::<Vec<_>>(),
+            [16, 48, 256]
+        );
+        assert_eq!(format!("{UNSORTED:?}"), "IconSizes([16, 48, 256])");
+        assert_eq!(IconSizes::new(&[16, 32, 48]), IconSizes::FAVICON);
+    }
+
+    #[test]
+    fn recommends_sizes_by_windows_version_and_scale() {
+        let xp = SizeRecommendation::new(WindowsVersion::Xp).max_scale(150);
+        assert!(xp.xp_compatible());
+        assert_eq!(&*xp.sizes(), &[16, 20, 24, 30, 32, 36, 40, 48]);
+
+        let vista = SizeRecommendation::new(WindowsVersion::Vista).max_scale(1000);
+        assert!(!vista.xp_compatible());
+        assert_eq!(
+            vista,
+            SizeRecommendation::new(WindowsVersion::Vista).max_scale(400)
+        );
+        assert_eq!(vista.sizes().last(), Some(&256));
+        assert_eq!(
+            SizeRecommendation::new(WindowsVersion::Vista).max_scale(0),
+            SizeRecommendation::new(WindowsVersion::Vista)
+        );
+    }
+}
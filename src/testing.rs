@@ -0,0 +1,148 @@
+//! Helpers for testing icon build steps without committing binary fixtures.
+//! Requires the `testing` feature, which is intended for `dev-dependencies`:
+//! ```toml
+//! [dev-dependencies]
+//! ico-builder = { version = "...", features = ["testing"] }
+//! ```
+//!
+//! ```no_run
+//! # use ico_builder::IcoBuilder;
+//! # use ico_builder::testing::{assert_ico_has_sizes, gradient};
+//! IcoBuilder::default()
+//!     .add_source_image(gradient(256))
+//!     .build_file("target/test-icon.ico")?;
+//! assert_ico_has_sizes("target/test-icon.ico", &[16, 24, 32, 48, 256]);
+//! # Ok::<_, ico_builder::Error>(())
+//! ```
+
+use crate::reader::IcoFile;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+
+/// A square image with a diagonal gradient from red (top left) to blue (bottom right)
+/// and green increasing from top to bottom. Every size produces different pixels.
+pub fn gradient(size: u32) -> RgbaImage {
+    let max = size.saturating_sub(1).max(1) as f32;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let across = (x + y) as f32 / (2.0 * max);
+        Rgba([
+            ((1.0 - across) * 255.0).round() as u8,
+            (y as f32 / max * 255.0).round() as u8,
+            (across * 255.0).round() as u8,
+            0xff,
+        ])
+    })
+}
+
+/// A square image with black and white cells of `cell_size` pixels, starting with black.
+/// Useful for checking how fine detail survives resizing.
+///
+/// ## Panics
+/// Panics if `cell_size` is zero.
+pub fn checkerboard(size: u32, cell_size: u32) -> RgbaImage {
+    assert!(cell_size > 0, "The cell size must not be zero");
+    RgbaImage::from_fn(size, size, |x, y| {
+        if (x / cell_size + y / cell_size) % 2 == 0 {
+            Rgba([0x00, 0x00, 0x00, 0xff])
+        } else {
+            Rgba([0xff, 0xff, 0xff, 0xff])
+        }
+    })
+}
+
+/// Asserts that the ICO file at `path` contains square frames of exactly the given sizes,
+/// in any order.
+///
+/// ## Panics
+/// Panics if the file can't be read or the sizes differ.
+#[track_caller]
+pub fn assert_ico_has_sizes(path: impl AsRef<Path>, sizes: &[u32]) {
+    let path = path.as_ref();
+    let ico = IcoFile::read(path)
+        .unwrap_or_else(|error| panic!("Failed to read {}: {error}", path.display()));
+    let mut actual = ico.sizes();
+    actual.sort_unstable();
+    let mut expected = sizes.to_owned();
+    expected.sort_unstable();
+    assert_eq!(
+        actual,
+        expected,
+        "{} has different sizes than expected",
+        path.display()
+    );
+}
+
+/// Asserts that every frame of the ICO file at `path` can be decoded
+/// and has the size stated in the directory.
+///
+/// ## Panics
+/// Panics if the file can't be read or a frame is invalid.
+#[track_caller]
+pub fn assert_ico_frames_decode(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let ico = IcoFile::read(path)
+        .unwrap_or_else(|error| panic!("Failed to read {}: {error}", path.display()));
+    for entry in ico.entries() {
+        let frame = entry.decode().unwrap_or_else(|error| {
+            panic!(
+                "Failed to decode the {}px frame of {}: {error}",
+                entry.width(),
+                path.display()
+            )
+        });
+        assert_eq!(
+            frame.dimensions(),
+            (entry.width(), entry.height()),
+            "The {}px frame of {} has a different size than stated in the directory",
+            entry.width(),
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IcoBuilder;
+    use std::collections::HashSet;
+    use std::panic;
+    use std::path::PathBuf;
+
+    fn ico_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ico-builder-testing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn gradients_differ_at_every_pixel() {
+        let image = gradient(16);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0xff, 0x00, 0x00, 0xff]));
+        assert_eq!(*image.get_pixel(15, 15), Rgba([0x00, 0xff, 0xff, 0xff]));
+        assert_eq!(image.pixels().collect::<HashSet<_>>().len(), 16 * 16);
+        assert_eq!(gradient(1).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn checkerboards_start_with_black() {
+        let image = checkerboard(8, 2);
+        assert_eq!(*image.get_pixel(1, 1), Rgba([0x00, 0x00, 0x00, 0xff]));
+        assert_eq!(*image.get_pixel(2, 1), Rgba([0xff, 0xff, 0xff, 0xff]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([0x00, 0x00, 0x00, 0xff]));
+        assert!(panic::catch_unwind(|| checkerboard(8, 0)).is_err());
+    }
+
+    #[test]
+    fn assertions_check_the_written_icon() {
+        let path = ico_path("sizes.ico");
+        IcoBuilder::default()
+            .sizes(&[16, 32])
+            .add_source_image(gradient(32))
+            .build_file(&path)
+            .unwrap();
+        assert_ico_has_sizes(&path, &[32, 16]);
+        assert_ico_frames_decode(&path);
+        assert!(panic::catch_unwind(|| assert_ico_has_sizes(&path, &[16])).is_err());
+        assert!(panic::catch_unwind(|| assert_ico_frames_decode(ico_path("missing.ico"))).is_err());
+    }
+}
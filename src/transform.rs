@@ -0,0 +1,86 @@
+//! Transformations that are applied to each frame after resizing.
+
+use image::{Rgba, RgbaImage};
+use std::fmt;
+
+mod auto_contrast;
+pub use auto_contrast::*;
+mod background;
+pub use background::*;
+mod color_map;
+pub use color_map::*;
+mod color_vision;
+pub use color_vision::*;
+mod layer;
+pub use layer::*;
+mod lut;
+pub use lut::*;
+mod mask;
+pub use mask::*;
+mod offset;
+pub use offset::*;
+mod opacity;
+pub use opacity::*;
+mod outline;
+pub use outline::*;
+mod silhouette;
+pub use silhouette::*;
+mod version_badge;
+pub use version_badge::*;
+
+/// A transformation that is applied to every frame after it has been resized
+/// to its final size, but before it is encoded.
+///
+/// Since transformations operate on the resized frame, they can produce
+/// crisp results at every size.
+pub trait Transform: fmt::Debug + Send + Sync {
+    /// Transforms the frame in place. Frames are always square.
+    fn apply(&self, frame: &mut RgbaImage);
+}
+
+/// Composites `source` over `destination` using straight (non-premultiplied) alpha.
+pub(crate) fn blend_over(source: Rgba<u8>, destination: Rgba<u8>) -> Rgba<u8> {
+    let source_alpha = f32::from(source[3]) / 255.0;
+    let destination_alpha = f32::from(destination[3]) / 255.0 * (1.0 - source_alpha);
+    let alpha = source_alpha + destination_alpha;
+    if alpha == 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |i: usize| {
+        ((f32::from(source[i]) * source_alpha + f32::from(destination[i]) * destination_alpha)
+            / alpha)
+            .round() as u8
+    };
+    Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (alpha * 255.0).round() as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blends_with_straight_alpha() {
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        assert_eq!(blend_over(red, blue), red);
+        assert_eq!(blend_over(Rgba([255, 0, 0, 0]), blue), blue);
+        assert_eq!(
+            blend_over(Rgba([255, 0, 0, 128]), blue),
+            Rgba([128, 0, 127, 255])
+        );
+        // The color of a translucent source is kept over transparency.
+        assert_eq!(
+            blend_over(Rgba([255, 0, 0, 128]), Rgba([0, 0, 255, 0])),
+            Rgba([255, 0, 0, 128])
+        );
+        assert_eq!(
+            blend_over(Rgba([9, 9, 9, 0]), Rgba([9, 9, 9, 0])),
+            Rgba([0, 0, 0, 0])
+        );
+    }
+}
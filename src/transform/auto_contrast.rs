@@ -0,0 +1,118 @@
+use super::Transform;
+use image::RgbaImage;
+
+/// Stretches the levels of small frames to use the full brightness range,
+/// which improves the legibility of tiny frames generated from soft, low-contrast masters.
+///
+/// Frames larger than the threshold are left untouched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoContrast {
+    max_size: u32,
+    clip: f32,
+}
+
+impl AutoContrast {
+    /// Applies auto-levels to frames up to (and including) `max_size`.
+    pub fn up_to(max_size: u32) -> Self {
+        Self {
+            max_size,
+            clip: 0.01,
+        }
+    }
+
+    /// The fraction of the darkest and brightest pixels that are ignored
+    /// when determining the levels, so that a few outliers don't prevent stretching.
+    /// Defaults to `0.01`.
+    pub fn clip(mut self, clip: f32) -> Self {
+        self.clip = clip.clamp(0.0, 0.49);
+        self
+    }
+
+    fn levels(&self, frame: &RgbaImage) -> Option<(f32, f32)> {
+        let mut histogram = [0u64; 256];
+        for pixel in frame.pixels() {
+            histogram[usize::from(luminance(pixel.0))] += u64::from(pixel[3]);
+        }
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let clipped = (total as f32 * self.clip) as u64;
+        let low = percentile(histogram.iter().enumerate(), clipped)?;
+        let high = percentile(histogram.iter().enumerate().rev(), clipped)?;
+        (high > low).then_some((low as f32, high as f32))
+    }
+}
+
+impl Transform for AutoContrast {
+    fn apply(&self, frame: &mut RgbaImage) {
+        if frame.width() > self.max_size {
+            return;
+        }
+        let Some((low, high)) = self.levels(frame) else {
+            return;
+        };
+        for pixel in frame.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                let stretched = (f32::from(*channel) - low) / (high - low) * 255.0;
+                *channel = stretched.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+fn luminance([r, g, b, _]: [u8; 4]) -> u8 {
+    (0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b)).round() as u8
+}
+
+/// The first level at which more than `clipped` weight has been accumulated.
+fn percentile<'a>(levels: impl Iterator<Item = (usize, &'a u64)>, clipped: u64) -> Option<usize> {
+    let mut accumulated = 0;
+    for (level, weight) in levels {
+        accumulated += weight;
+        if accumulated > clipped {
+            return Some(level);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A frame with a soft gradient between gray levels 100 and 150.
+    fn soft(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, _| {
+            let level = (100 + x * 50 / (size - 1)) as u8;
+            Rgba([level, level, level, 255])
+        })
+    }
+
+    #[test]
+    fn stretches_small_frames() {
+        let mut frame = soft(16);
+        AutoContrast::up_to(16).clip(0.0).apply(&mut frame);
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*frame.get_pixel(15, 0), Rgba([255, 255, 255, 255]));
+
+        let mut large = soft(32);
+        AutoContrast::up_to(16).apply(&mut large);
+        assert_eq!(large, soft(32));
+    }
+
+    #[test]
+    fn levels_ignore_clipped_and_transparent_pixels() {
+        let mut frame = soft(16);
+        frame.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        frame.put_pixel(1, 0, Rgba([255, 255, 255, 0]));
+        assert_eq!(
+            AutoContrast::up_to(16).clip(0.01).levels(&frame),
+            Some((100.0, 150.0))
+        );
+        let flat = RgbaImage::from_pixel(4, 4, Rgba([80, 80, 80, 255]));
+        assert_eq!(AutoContrast::up_to(16).levels(&flat), None);
+        assert_eq!(AutoContrast::up_to(16).levels(&RgbaImage::new(4, 4)), None);
+    }
+}
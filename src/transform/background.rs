@@ -0,0 +1,150 @@
+use super::{blend_over, Transform};
+use image::{Rgba, RgbaImage};
+
+/// Fills the background behind transparent artwork with a solid color or a gradient.
+/// This turns flat logos into full app icon tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Background {
+    fill: Fill,
+    stops: Vec<(f32, Rgba<u8>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fill {
+    Linear { angle: f32 },
+    Radial,
+}
+
+impl Background {
+    /// A background with a single color.
+    pub fn solid(color: Rgba<u8>) -> Self {
+        Self {
+            fill: Fill::Radial,
+            stops: vec![(0.0, color)],
+        }
+    }
+
+    /// A linear gradient along the given `angle` in degrees.
+    /// An angle of `0.0` draws the gradient from left to right, `90.0` from top to bottom.
+    ///
+    /// Stops are `(offset, color)` pairs where the offset is between `0.0` and `1.0`.
+    ///
+    /// ## Panics
+    /// Panics if fewer than two stops are given.
+    pub fn linear_gradient(angle: f32, stops: impl IntoIterator<Item = (f32, Rgba<u8>)>) -> Self {
+        Self::gradient(Fill::Linear { angle }, stops)
+    }
+
+    /// A radial gradient from the center (offset `0.0`) to the corners (offset `1.0`).
+    /// See [`Background::linear_gradient`] for how stops are specified.
+    ///
+    /// ## Panics
+    /// Panics if fewer than two stops are given.
+    pub fn radial_gradient(stops: impl IntoIterator<Item = (f32, Rgba<u8>)>) -> Self {
+        Self::gradient(Fill::Radial, stops)
+    }
+
+    fn gradient(fill: Fill, stops: impl IntoIterator<Item = (f32, Rgba<u8>)>) -> Self {
+        let mut stops: Vec<_> = stops.into_iter().collect();
+        assert!(stops.len() >= 2, "A gradient needs at least two stops");
+        stops.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self { fill, stops }
+    }
+
+    fn color_at(&self, x: f32, y: f32) -> Rgba<u8> {
+        let offset = match self.fill {
+            Fill::Linear { angle } => {
+                let (dy, dx) = angle.to_radians().sin_cos();
+                0.5 + (x * dx + y * dy) / (dx.abs() + dy.abs())
+            }
+            Fill::Radial => (x * x + y * y).sqrt() / 0.5_f32.hypot(0.5),
+        };
+        interpolate(&self.stops, offset)
+    }
+}
+
+impl Transform for Background {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let size = frame.width() as f32;
+        for (x, y, pixel) in frame.enumerate_pixels_mut() {
+            let x = (x as f32 + 0.5) / size - 0.5;
+            let y = (y as f32 + 0.5) / size - 0.5;
+            *pixel = blend_over(*pixel, self.color_at(x, y));
+        }
+    }
+}
+
+fn interpolate(stops: &[(f32, Rgba<u8>)], offset: f32) -> Rgba<u8> {
+    let (first_offset, first) = stops[0];
+    if offset <= first_offset || stops.len() == 1 {
+        return first;
+    }
+    for window in stops.windows(2) {
+        let [(start, from), (end, to)] = [window[0], window[1]];
+        if offset <= end {
+            let t = if end > start {
+                (offset - start) / (end - start)
+            } else {
+                1.0
+            };
+            return Rgba(std::array::from_fn(|i| {
+                (f32::from(from[i]) + (f32::from(to[i]) - f32::from(from[i])) * t).round() as u8
+            }));
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn fills_behind_the_artwork() {
+        let mut frame = RgbaImage::new(4, 4);
+        frame.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        Background::solid(Rgba([0, 0, 255, 255])).apply(&mut frame);
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*frame.get_pixel(3, 3), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn linear_gradients_follow_the_angle() {
+        let mut frame = RgbaImage::new(8, 8);
+        Background::linear_gradient(90.0, [(1.0, WHITE), (0.0, BLACK)]).apply(&mut frame);
+        let top = frame.get_pixel(3, 0)[0];
+        let bottom = frame.get_pixel(3, 7)[0];
+        assert!(top < 32 && bottom > 223, "{top} {bottom}");
+        // Rows have one color.
+        assert!(frame.rows().all(|row| {
+            let row: Vec<_> = row.collect();
+            row.iter().all(|pixel| pixel == &row[0])
+        }));
+    }
+
+    #[test]
+    fn radial_gradients_reach_the_last_stop_at_the_corners() {
+        let background = Background::radial_gradient([(0.0, BLACK), (1.0, WHITE)]);
+        assert_eq!(background.color_at(0.0, 0.0), BLACK);
+        assert_eq!(background.color_at(0.5, -0.5), WHITE);
+        assert_eq!(background.color_at(0.25, 0.25), Rgba([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn interpolates_between_stops() {
+        let stops = [(0.25, BLACK), (0.5, WHITE), (0.5, BLACK)];
+        assert_eq!(interpolate(&stops, 0.0), BLACK);
+        assert_eq!(interpolate(&stops, 0.375), Rgba([128, 128, 128, 255]));
+        assert_eq!(interpolate(&stops, 0.5), WHITE);
+        assert_eq!(interpolate(&stops, 0.75), BLACK);
+    }
+
+    #[test]
+    #[should_panic = "A gradient needs at least two stops"]
+    fn gradients_need_two_stops() {
+        Background::radial_gradient([(0.0, BLACK)]);
+    }
+}
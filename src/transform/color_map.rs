@@ -0,0 +1,87 @@
+use super::Transform;
+use image::{Rgb, RgbaImage};
+
+/// Replaces specific colors of the artwork with other colors, e.g. to recolor
+/// a master icon for white-label builds. The alpha channel is preserved.
+///
+/// ```
+/// # use ico_builder::Rgb;
+/// # use ico_builder::transform::ColorMap;
+/// let rebrand = ColorMap::new()
+///     .tolerance(8)
+///     .map(Rgb([0, 120, 215]), Rgb([230, 0, 126]))
+///     .map(Rgb([255, 255, 255]), Rgb([30, 30, 30]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorMap {
+    tolerance: u8,
+    mappings: Vec<(Rgb<u8>, Rgb<u8>)>,
+}
+
+impl ColorMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps the color `from` to the color `to`.
+    /// If multiple mappings match a pixel, the first one wins.
+    pub fn map(mut self, from: Rgb<u8>, to: Rgb<u8>) -> Self {
+        self.mappings.push((from, to));
+        self
+    }
+
+    /// The maximum difference per channel for a pixel to match a source color.
+    /// Defaults to `0`, which only matches exact colors.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    fn matches(&self, a: Rgb<u8>, b: Rgb<u8>) -> bool {
+        a.0.iter()
+            .zip(b.0)
+            .all(|(a, b)| a.abs_diff(b) <= self.tolerance)
+    }
+}
+
+impl Transform for ColorMap {
+    fn apply(&self, frame: &mut RgbaImage) {
+        for pixel in frame.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            if let Some((_, to)) = self
+                .mappings
+                .iter()
+                .find(|(from, _)| self.matches(*from, Rgb([r, g, b])))
+            {
+                pixel.0 = [to[0], to[1], to[2], a];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn maps_matching_colors() {
+        let map = ColorMap::new()
+            .tolerance(8)
+            .map(Rgb([0, 120, 215]), Rgb([230, 0, 126]))
+            .map(Rgb([0, 125, 215]), Rgb([1, 2, 3]))
+            .map(Rgb([255, 255, 255]), Rgb([30, 30, 30]));
+        let mut frame = RgbaImage::from_fn(3, 1, |x, _| {
+            [
+                Rgba([8, 128, 207, 100]),
+                Rgba([250, 255, 255, 255]),
+                Rgba([9, 120, 215, 255]),
+            ][x as usize]
+        });
+        map.apply(&mut frame);
+        // The first matching mapping wins, and alpha is kept.
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([230, 0, 126, 100]));
+        assert_eq!(*frame.get_pixel(1, 0), Rgba([30, 30, 30, 255]));
+        assert_eq!(*frame.get_pixel(2, 0), Rgba([9, 120, 215, 255]));
+    }
+}
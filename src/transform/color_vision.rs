@@ -0,0 +1,164 @@
+use super::Transform;
+use image::{Rgba, RgbaImage};
+
+/// A form of color blindness that [`ColorVisionSimulation`] can simulate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ColorVisionDeficiency {
+    /// No functioning red cones.
+    Protanopia,
+    /// No functioning green cones, the most common form.
+    Deuteranopia,
+    /// No functioning blue cones.
+    Tritanopia,
+}
+
+impl ColorVisionDeficiency {
+    pub const ALL: [ColorVisionDeficiency; 3] = [
+        ColorVisionDeficiency::Protanopia,
+        ColorVisionDeficiency::Deuteranopia,
+        ColorVisionDeficiency::Tritanopia,
+    ];
+
+    /// The lowercase name, e.g. `protanopia`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorVisionDeficiency::Protanopia => "protanopia",
+            ColorVisionDeficiency::Deuteranopia => "deuteranopia",
+            ColorVisionDeficiency::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// The simulation matrices for full severity by Machado, Oliveira and Fernandes (2009),
+    /// which operate on linear RGB.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorVisionDeficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVisionDeficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVisionDeficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// Shows the artwork the way people with a color vision deficiency see it,
+/// e.g. to check that status colors remain distinguishable. The alpha channel is preserved.
+/// See [`IcoBuilder::color_vision_previews`](crate::IcoBuilder::color_vision_previews)
+/// for previews of all deficiencies.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::transform::{ColorVisionDeficiency, ColorVisionSimulation};
+/// IcoBuilder::default()
+///     .add_source_file("status-error-256x256.png")
+///     .add_transform(ColorVisionSimulation::new(ColorVisionDeficiency::Deuteranopia))
+///     .build_png_files("previews", "status-error-deuteranopia")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorVisionSimulation {
+    deficiency: ColorVisionDeficiency,
+}
+
+impl ColorVisionSimulation {
+    pub fn new(deficiency: ColorVisionDeficiency) -> Self {
+        Self { deficiency }
+    }
+}
+
+impl Transform for ColorVisionSimulation {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let matrix = self.deficiency.matrix();
+        for pixel in frame.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let linear = [to_linear(r), to_linear(g), to_linear(b)];
+            let channel = |row: [f32; 3]| {
+                to_srgb(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2])
+            };
+            *pixel = Rgba([
+                channel(matrix[0]),
+                channel(matrix[1]),
+                channel(matrix[2]),
+                a,
+            ]);
+        }
+    }
+}
+
+fn to_linear(value: u8) -> f32 {
+    let value = f32::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulate(deficiency: ColorVisionDeficiency, color: Rgba<u8>) -> Rgba<u8> {
+        let mut frame = RgbaImage::from_pixel(1, 1, color);
+        ColorVisionSimulation::new(deficiency).apply(&mut frame);
+        *frame.get_pixel(0, 0)
+    }
+
+    #[test]
+    fn grays_and_alpha_are_kept() {
+        for deficiency in ColorVisionDeficiency::ALL {
+            for level in [0, 128, 255] {
+                let gray = Rgba([level, level, level, 77]);
+                let simulated = simulate(deficiency, gray);
+                assert_eq!(simulated[3], 77);
+                for channel in &simulated.0[..3] {
+                    assert!(channel.abs_diff(level) <= 1, "{deficiency:?} {simulated:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn red_and_green_are_confused() {
+        let (red, green) = (Rgba([200, 40, 40, 255]), Rgba([40, 140, 40, 255]));
+        for deficiency in [
+            ColorVisionDeficiency::Protanopia,
+            ColorVisionDeficiency::Deuteranopia,
+        ] {
+            let (red, green) = (simulate(deficiency, red), simulate(deficiency, green));
+            // Both become shades of olive, with little difference between red and green.
+            assert!(red[0].abs_diff(red[1]) < 40, "{deficiency:?} {red:?}");
+            assert!(green[0].abs_diff(green[1]) < 40, "{deficiency:?} {green:?}");
+        }
+        let tritanopia = simulate(ColorVisionDeficiency::Tritanopia, red);
+        assert!(tritanopia[0] > tritanopia[1] + 100, "{tritanopia:?}");
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for value in 0..=255 {
+            assert_eq!(to_srgb(to_linear(value)), value);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::{blend_over, Transform};
+use crate::hash::Fnv1a;
+use image::imageops::{resize, FilterType};
+use image::{DynamicImage, RgbaImage};
+use std::fmt;
+use std::sync::Arc;
+
+/// Composites an image over the artwork, scaled to the size of the frame,
+/// e.g. a wordmark or a language-specific label.
+///
+/// The layer is identified by a hash of its pixels in its [`Debug`](fmt::Debug)
+/// representation, which keeps [cache](crate::IcoBuilder::cache_dir) keys short.
+#[derive(Clone, PartialEq)]
+pub struct Layer {
+    image: Arc<RgbaImage>,
+}
+
+impl Layer {
+    /// Creates a layer from an image, which should be square and at least as large as the largest frame.
+    pub fn new(image: impl Into<DynamicImage>) -> Self {
+        Self {
+            image: Arc::new(image.into().into_rgba8()),
+        }
+    }
+}
+
+impl fmt::Debug for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hash = Fnv1a::default().write(self.image.as_raw()).finish();
+        f.debug_struct("Layer")
+            .field("width", &self.image.width())
+            .field("height", &self.image.height())
+            .field("hash", &format_args!("{hash:016x}"))
+            .finish()
+    }
+}
+
+impl Transform for Layer {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let scaled;
+        let layer = if self.image.dimensions() == frame.dimensions() {
+            &*self.image
+        } else {
+            scaled = resize(
+                &*self.image,
+                frame.width(),
+                frame.height(),
+                FilterType::Lanczos3,
+            );
+            &scaled
+        };
+        for (pixel, &layer_pixel) in frame.pixels_mut().zip(layer.pixels()) {
+            *pixel = blend_over(layer_pixel, *pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn composites_the_scaled_layer() {
+        let mut image = RgbaImage::new(32, 32);
+        for x in 0..16 {
+            for y in 0..32 {
+                image.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        let layer = Layer::new(image);
+        let mut frame = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 255]));
+        layer.apply(&mut frame);
+        // Up to the ringing of the filter at the edge of the layer.
+        let (left, right) = (frame.get_pixel(1, 4), frame.get_pixel(6, 4));
+        assert!(left[0] > 250 && left[3] == 255, "{left:?}");
+        assert!(right[0] < 5 && right[2] > 250, "{right:?}");
+    }
+
+    #[test]
+    fn layers_are_identified_by_their_pixels() {
+        let debug = |color| format!("{:?}", Layer::new(RgbaImage::from_pixel(4, 4, color)));
+        let red = debug(Rgba([255, 0, 0, 255]));
+        assert!(
+            red.starts_with("Layer { width: 4, height: 4, hash: "),
+            "{red}"
+        );
+        assert_eq!(red, debug(Rgba([255, 0, 0, 255])));
+        assert_ne!(red, debug(Rgba([0, 255, 0, 255])));
+    }
+}
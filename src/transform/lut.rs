@@ -0,0 +1,250 @@
+use super::Transform;
+use crate::{Error, Result};
+use image::RgbaImage;
+use std::fs;
+use std::path::Path;
+
+/// Applies a 3D color lookup table to the frames.
+/// LUTs can be loaded from [`.cube`] files, so the same color grade can be shared
+/// with other tools in an asset pipeline.
+///
+/// [`.cube`]: https://web.archive.org/web/20220215173646/https://wwwimages2.adobe.com/content/dam/acom/en/products/speedgrade/cc/pdfs/cube-lut-specification-1.0.pdf
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Output colors, with the red index changing fastest.
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    /// Reads a LUT from a `.cube` file.
+    pub fn from_cube_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_cube(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a LUT in the `.cube` format.
+    pub fn from_cube(source: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let invalid = |reason: &str| Error::InvalidLut {
+                line: line_number,
+                reason: reason.to_owned(),
+            };
+            let line = line.trim();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => {}
+                Some(word) if word.starts_with('#') => {}
+                Some("TITLE") => {}
+                Some("LUT_1D_SIZE") => return Err(invalid("1D LUTs are not supported")),
+                Some("LUT_3D_SIZE") => {
+                    let value = words.next().and_then(|w| w.parse::<usize>().ok());
+                    match value {
+                        Some(value @ 2..=256) => size = Some(value),
+                        _ => return Err(invalid("LUT_3D_SIZE must be between 2 and 256")),
+                    }
+                }
+                Some("DOMAIN_MIN") => {
+                    domain_min = parse_triple(words).ok_or_else(|| invalid("Expected 3 numbers"))?
+                }
+                Some("DOMAIN_MAX") => {
+                    domain_max = parse_triple(words).ok_or_else(|| invalid("Expected 3 numbers"))?
+                }
+                Some(_) => {
+                    let entry = parse_triple(line.split_whitespace())
+                        .ok_or_else(|| invalid("Expected 3 numbers or a keyword"))?;
+                    table.push(entry);
+                }
+            }
+        }
+
+        let size = size.ok_or_else(|| Error::InvalidLut {
+            line: 0,
+            reason: "Missing LUT_3D_SIZE".to_owned(),
+        })?;
+        if table.len() != size.pow(3) {
+            return Err(Error::InvalidLut {
+                line: 0,
+                reason: format!("Expected {} entries, found {}", size.pow(3), table.len()),
+            });
+        }
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err(Error::InvalidLut {
+                line: 0,
+                reason: "DOMAIN_MAX must be greater than DOMAIN_MIN".to_owned(),
+            });
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + g * self.size + b * self.size * self.size]
+    }
+
+    /// Looks up a color (with channels in `0.0..=1.0`) using trilinear interpolation.
+    fn lookup(&self, color: [f32; 3]) -> [f32; 3] {
+        let max_index = (self.size - 1) as f32;
+        let position: [f32; 3] = std::array::from_fn(|i| {
+            let normalized =
+                (color[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]);
+            normalized.clamp(0.0, 1.0) * max_index
+        });
+        let lower = position.map(|p| (p.floor() as usize).min(self.size - 2));
+        let fraction: [f32; 3] = std::array::from_fn(|i| position[i] - lower[i] as f32);
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let weight: f32 = (0..3)
+                .map(|i| {
+                    if offset[i] == 1 {
+                        fraction[i]
+                    } else {
+                        1.0 - fraction[i]
+                    }
+                })
+                .product();
+            let value = self.entry(
+                lower[0] + offset[0],
+                lower[1] + offset[1],
+                lower[2] + offset[2],
+            );
+            for i in 0..3 {
+                result[i] += value[i] * weight;
+            }
+        }
+        result
+    }
+}
+
+fn parse_triple<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<[f32; 3]> {
+    let mut triple = [0.0; 3];
+    for value in &mut triple {
+        *value = words.next()?.parse().ok()?;
+    }
+    words.next().is_none().then_some(triple)
+}
+
+impl Transform for Lut3d {
+    fn apply(&self, frame: &mut RgbaImage) {
+        for pixel in frame.pixels_mut() {
+            let color = [pixel[0], pixel[1], pixel[2]].map(|c| f32::from(c) / 255.0);
+            let graded = self.lookup(color);
+            for i in 0..3 {
+                pixel[i] = (graded[i].clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A `.cube` file with size 2 and each output color computed from the input corner.
+    fn cube(header: &str, color: impl Fn([f32; 3]) -> [f32; 3]) -> String {
+        let mut source = format!("{header}\nLUT_3D_SIZE 2\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    let [r, g, b] = color([r, g, b].map(|c| c as f32));
+                    source.push_str(&format!("{r} {g} {b}\n"));
+                }
+            }
+        }
+        source
+    }
+
+    fn apply(lut: &Lut3d, color: [u8; 3]) -> [u8; 3] {
+        let mut frame = RgbaImage::from_pixel(1, 1, Rgba([color[0], color[1], color[2], 77]));
+        lut.apply(&mut frame);
+        let Rgba([r, g, b, a]) = *frame.get_pixel(0, 0);
+        assert_eq!(a, 77);
+        [r, g, b]
+    }
+
+    fn line_error(source: &str) -> (usize, String) {
+        match Lut3d::from_cube(source) {
+            Err(Error::InvalidLut { line, reason }) => (line, reason),
+            other => panic!("expected an invalid LUT, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn identity_lut_keeps_colors() {
+        let lut = Lut3d::from_cube(&cube("# comment\nTITLE \"identity\"", |c| c)).unwrap();
+        for color in [[0, 0, 0], [255, 255, 255], [12, 200, 99], [128, 1, 254]] {
+            assert_eq!(apply(&lut, color), color);
+        }
+    }
+
+    #[test]
+    fn interpolates_between_entries() {
+        let lut = Lut3d::from_cube(&cube("", |[r, g, b]| [1.0 - r, g * 0.5, b])).unwrap();
+        assert_eq!(apply(&lut, [0, 255, 51]), [255, 128, 51]);
+        assert_eq!(apply(&lut, [255, 102, 0]), [0, 51, 0]);
+    }
+
+    #[test]
+    fn scales_colors_to_the_domain() {
+        let lut =
+            Lut3d::from_cube(&cube("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 0.5 0.5 0.5", |c| c)).unwrap();
+        // Inputs above the domain are clamped to its maximum.
+        assert_eq!(apply(&lut, [51, 255, 0]), [102, 255, 0]);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        assert_eq!(
+            line_error("LUT_3D_SIZE 2\n\n0 0 0\n0 0\n"),
+            (4, "Expected 3 numbers or a keyword".to_owned())
+        );
+        assert_eq!(
+            line_error("# 1D\nLUT_1D_SIZE 16\n"),
+            (2, "1D LUTs are not supported".to_owned())
+        );
+        assert_eq!(
+            line_error("LUT_3D_SIZE 1\n"),
+            (1, "LUT_3D_SIZE must be between 2 and 256".to_owned())
+        );
+        assert_eq!(
+            line_error("TITLE \"x\"\nLUT_3D_SIZE two\n"),
+            (2, "LUT_3D_SIZE must be between 2 and 256".to_owned())
+        );
+        assert_eq!(
+            line_error("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0 0\n"),
+            (2, "Expected 3 numbers".to_owned())
+        );
+        assert_eq!(
+            line_error("LUT_3D_SIZE 2\n0 0 zero\n"),
+            (2, "Expected 3 numbers or a keyword".to_owned())
+        );
+    }
+
+    #[test]
+    fn validates_the_whole_file() {
+        assert_eq!(line_error("0 0 0\n"), (0, "Missing LUT_3D_SIZE".to_owned()));
+        assert_eq!(
+            line_error("LUT_3D_SIZE 2\n0 0 0\n"),
+            (0, "Expected 8 entries, found 1".to_owned())
+        );
+        assert_eq!(
+            line_error(&cube("DOMAIN_MIN 0 1 0\nDOMAIN_MAX 1 1 1", |c| c)),
+            (0, "DOMAIN_MAX must be greater than DOMAIN_MIN".to_owned())
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use super::Transform;
+use image::RgbaImage;
+
+/// Masks the frame with a [superellipse], also known as a "squircle".
+///
+/// The mask is evaluated separately for every frame size and is anti-aliased,
+/// so the edges stay crisp even at 16px.
+///
+/// [superellipse]: https://en.wikipedia.org/wiki/Superellipse
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SuperellipseMask {
+    exponent: f32,
+}
+
+impl SuperellipseMask {
+    /// Creates a mask with the given exponent. An exponent of `2.0` produces
+    /// a circle, larger exponents approach a square.
+    ///
+    /// ## Panics
+    /// Panics if the exponent is not positive.
+    pub fn new(exponent: f32) -> Self {
+        assert!(exponent > 0.0, "Exponent must be positive");
+        Self { exponent }
+    }
+}
+
+impl Default for SuperellipseMask {
+    /// A squircle with an exponent of `5.0`.
+    fn default() -> Self {
+        Self::new(5.0)
+    }
+}
+
+const SUBSAMPLES: u32 = 4;
+
+impl Transform for SuperellipseMask {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let size = frame.width() as f32;
+        for (x, y, pixel) in frame.enumerate_pixels_mut() {
+            let coverage = self.coverage(x, y, size);
+            pixel[3] = (f32::from(pixel[3]) * coverage).round() as u8;
+        }
+    }
+}
+
+impl SuperellipseMask {
+    fn coverage(&self, x: u32, y: u32, size: f32) -> f32 {
+        let mut inside = 0;
+        for sy in 0..SUBSAMPLES {
+            for sx in 0..SUBSAMPLES {
+                let px = x as f32 + (sx as f32 + 0.5) / SUBSAMPLES as f32;
+                let py = y as f32 + (sy as f32 + 0.5) / SUBSAMPLES as f32;
+                if self.contains(px / size * 2.0 - 1.0, py / size * 2.0 - 1.0) {
+                    inside += 1;
+                }
+            }
+        }
+        inside as f32 / (SUBSAMPLES * SUBSAMPLES) as f32
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x.abs().powf(self.exponent) + y.abs().powf(self.exponent) <= 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn masks_the_corners() {
+        let mut frame = RgbaImage::from_pixel(16, 16, Rgba([255, 255, 255, 200]));
+        SuperellipseMask::default().apply(&mut frame);
+        assert_eq!(frame.get_pixel(8, 8)[3], 200);
+        assert_eq!(frame.get_pixel(8, 0)[3], 200);
+        assert_eq!(frame.get_pixel(0, 0)[3], 0);
+        // The edges are anti-aliased.
+        assert!(frame.pixels().any(|pixel| (1..200).contains(&pixel[3])));
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            assert_eq!(pixel[3], frame.get_pixel(15 - x, y)[3]);
+            assert_eq!(pixel[3], frame.get_pixel(y, x)[3]);
+        }
+    }
+
+    #[test]
+    fn larger_exponents_approach_a_square() {
+        let circle = SuperellipseMask::new(2.0).coverage(1, 1, 16.0);
+        let squircle = SuperellipseMask::new(5.0).coverage(1, 1, 16.0);
+        assert!(circle < squircle);
+        assert_eq!(SuperellipseMask::new(50.0).coverage(0, 0, 16.0), 1.0);
+    }
+
+    #[test]
+    #[should_panic = "Exponent must be positive"]
+    fn exponent_must_be_positive() {
+        SuperellipseMask::new(0.0);
+    }
+}
@@ -0,0 +1,74 @@
+use super::Transform;
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeMap;
+
+/// Moves the artwork by a few pixels for specific frame sizes,
+/// a common hand-tuning step for optical centering.
+/// Pixels moved out of the frame are cropped and the uncovered area is transparent.
+///
+/// ```
+/// # use ico_builder::transform::Offset;
+/// // Nudge the 16px frame one pixel up and to the left.
+/// let offset = Offset::new().size(16, -1, -1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Offset {
+    sizes: BTreeMap<u32, (i32, i32)>,
+}
+
+impl Offset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves frames of the given size by `dx` pixels to the right and `dy` pixels down.
+    pub fn size(mut self, size: u32, dx: i32, dy: i32) -> Self {
+        self.sizes.insert(size, (dx, dy));
+        self
+    }
+}
+
+impl Transform for Offset {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let Some(&(dx, dy)) = self.sizes.get(&frame.width()) else {
+            return;
+        };
+        let source = frame.clone();
+        for (x, y, pixel) in frame.enumerate_pixels_mut() {
+            let source_x = i64::from(x) - i64::from(dx);
+            let source_y = i64::from(y) - i64::from(dy);
+            *pixel = u32::try_from(source_x)
+                .ok()
+                .zip(u32::try_from(source_y).ok())
+                .and_then(|(x, y)| source.get_pixel_checked(x, y))
+                .copied()
+                .unwrap_or(Rgba([0, 0, 0, 0]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_frames_of_the_size() {
+        let red = Rgba([255, 0, 0, 255]);
+        let offset = Offset::new().size(3, -1, 1);
+        let mut frame = RgbaImage::new(3, 3);
+        frame.put_pixel(1, 0, red);
+        frame.put_pixel(0, 2, red);
+        offset.apply(&mut frame);
+        let moved: Vec<_> = frame
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel == red)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        // The pixel moved out of the frame is cropped.
+        assert_eq!(moved, [(0, 1)]);
+
+        let mut other_size = RgbaImage::from_pixel(4, 4, red);
+        offset.apply(&mut other_size);
+        assert!(other_size.pixels().all(|pixel| *pixel == red));
+    }
+}
@@ -0,0 +1,70 @@
+use super::Transform;
+use image::RgbaImage;
+use std::collections::BTreeMap;
+
+/// Multiplies the alpha channel of frames by an opacity factor,
+/// which can be customized per frame size.
+///
+/// ```
+/// # use ico_builder::transform::Opacity;
+/// // The 16px overlay version is 80% opaque, all other sizes are left as is.
+/// let opacity = Opacity::new(1.0).size(16, 0.8);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opacity {
+    default: f32,
+    sizes: BTreeMap<u32, f32>,
+}
+
+impl Opacity {
+    /// Creates a transform that applies the given opacity to all frames.
+    /// The opacity is clamped to `0.0..=1.0`.
+    pub fn new(opacity: f32) -> Self {
+        Self {
+            default: opacity.clamp(0.0, 1.0),
+            sizes: Default::default(),
+        }
+    }
+
+    /// Overrides the opacity for frames of the given size.
+    pub fn size(mut self, size: u32, opacity: f32) -> Self {
+        self.sizes.insert(size, opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    fn opacity_for(&self, size: u32) -> f32 {
+        self.sizes.get(&size).copied().unwrap_or(self.default)
+    }
+}
+
+impl Transform for Opacity {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let opacity = self.opacity_for(frame.width());
+        if opacity == 1.0 {
+            return;
+        }
+        for pixel in frame.pixels_mut() {
+            pixel[3] = (f32::from(pixel[3]) * opacity).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn applies_the_opacity_of_the_size() {
+        let opacity = Opacity::new(0.5).size(16, 2.0).size(32, 0.25);
+        let apply = |size| {
+            let mut frame = RgbaImage::from_pixel(size, size, Rgba([10, 20, 30, 200]));
+            opacity.apply(&mut frame);
+            *frame.get_pixel(0, 0)
+        };
+        assert_eq!(apply(16), Rgba([10, 20, 30, 200]));
+        assert_eq!(apply(32), Rgba([10, 20, 30, 50]));
+        assert_eq!(apply(48), Rgba([10, 20, 30, 100]));
+        assert_eq!(Opacity::new(-1.0).opacity_for(16), 0.0);
+    }
+}
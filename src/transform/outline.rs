@@ -0,0 +1,101 @@
+use super::Transform;
+use image::{Rgba, RgbaImage};
+
+/// Keeps only the outline of the artwork, drawn in a single color.
+/// Useful for "inactive" or wireframe variants of an icon.
+///
+/// The outline is placed on the inside of the artwork's edge, so it never
+/// grows beyond the original shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    thickness: u32,
+    color: Rgba<u8>,
+}
+
+impl Outline {
+    /// Creates an outline that is `thickness` pixels wide. The thickness is the same for all
+    /// frame sizes.
+    ///
+    /// ## Panics
+    /// Panics if the thickness is zero.
+    pub fn new(thickness: u32, color: Rgba<u8>) -> Self {
+        assert!(thickness > 0, "Thickness must be at least one pixel");
+        Self { thickness, color }
+    }
+}
+
+impl Transform for Outline {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let source = frame.clone();
+        let radius = self.thickness as i64;
+        let [r, g, b, a] = self.color.0;
+        for (x, y, pixel) in frame.enumerate_pixels_mut() {
+            let alpha = source.get_pixel(x, y)[3];
+            let eroded = if alpha == 0 {
+                0
+            } else {
+                min_alpha_within(&source, x, y, radius)
+            };
+            let edge = u16::from(alpha - eroded);
+            *pixel = Rgba([r, g, b, (edge * u16::from(a) / 255) as u8]);
+        }
+    }
+}
+
+/// The minimum alpha in a disk of the given radius around `(x, y)`,
+/// where pixels outside the frame count as transparent.
+fn min_alpha_within(image: &RgbaImage, x: u32, y: u32, radius: i64) -> u8 {
+    let (width, height) = (i64::from(image.width()), i64::from(image.height()));
+    let mut min = u8::MAX;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                return 0;
+            }
+            min = min.min(image.get_pixel(nx as u32, ny as u32)[3]);
+        }
+    }
+    min
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_inner_edge() {
+        let mut frame = RgbaImage::new(7, 7);
+        for y in 1..6 {
+            for x in 1..6 {
+                frame.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+        let color = Rgba([255, 0, 0, 128]);
+        Outline::new(1, color).apply(&mut frame);
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            let on_edge = (1..6).contains(&x)
+                && (1..6).contains(&y)
+                && [x, y].iter().any(|&v| v == 1 || v == 5);
+            let expected = if on_edge { color } else { Rgba([255, 0, 0, 0]) };
+            assert_eq!(*pixel, expected, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn the_frame_border_counts_as_transparent() {
+        let mut frame = RgbaImage::from_pixel(5, 5, Rgba([0, 0, 0, 255]));
+        Outline::new(2, Rgba([255, 255, 255, 255])).apply(&mut frame);
+        assert_eq!(frame.get_pixel(1, 2)[3], 255);
+        assert_eq!(frame.get_pixel(2, 2)[3], 0);
+    }
+
+    #[test]
+    #[should_panic = "Thickness must be at least one pixel"]
+    fn thickness_must_be_positive() {
+        Outline::new(0, Rgba([0, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,40 @@
+use super::Transform;
+use image::{Rgba, RgbaImage};
+
+/// Turns the artwork into a single-color silhouette while preserving its alpha channel.
+/// Useful for taskbar badges and template icons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Silhouette {
+    color: Rgba<u8>,
+}
+
+impl Silhouette {
+    /// Creates a silhouette in the given color. The alpha of the color
+    /// is multiplied with the alpha of the artwork.
+    pub fn new(color: Rgba<u8>) -> Self {
+        Self { color }
+    }
+}
+
+impl Transform for Silhouette {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let [r, g, b, a] = self.color.0;
+        for pixel in frame.pixels_mut() {
+            let alpha = (u16::from(pixel[3]) * u16::from(a) / 255) as u8;
+            *pixel = Rgba([r, g, b, alpha]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_shape() {
+        let mut frame = RgbaImage::from_fn(2, 1, |x, _| Rgba([200, 100, 0, [255, 64][x as usize]]));
+        Silhouette::new(Rgba([0, 0, 0, 128])).apply(&mut frame);
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([0, 0, 0, 128]));
+        assert_eq!(*frame.get_pixel(1, 0), Rgba([0, 0, 0, 32]));
+    }
+}
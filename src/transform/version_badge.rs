@@ -0,0 +1,190 @@
+use super::{blend_over, Transform};
+use crate::{font, Result};
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::process::Command;
+use std::{env, io};
+
+/// Draws a version label in a band along the bottom of larger frames, so that internal builds
+/// are visually distinguishable from releases. Letters are shown in upper case.
+/// See [`IcoBuilder::add_version_badge_cargo`](crate::IcoBuilder::add_version_badge_cargo)
+/// for only adding the badge to non-release builds.
+///
+/// ```
+/// # use ico_builder::Rgba;
+/// # use ico_builder::transform::VersionBadge;
+/// let badge = VersionBadge::new("1.4.0-rc.1")
+///     .min_size(32)
+///     .colors(Rgba([200, 0, 0, 220]), Rgba([255, 255, 255, 255]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionBadge {
+    label: String,
+    min_size: u32,
+    background: Rgba<u8>,
+    foreground: Rgba<u8>,
+}
+
+impl VersionBadge {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into().to_ascii_uppercase(),
+            min_size: 48,
+            background: Rgba([0, 0, 0, 0xc0]),
+            foreground: Rgba([0xff, 0xff, 0xff, 0xff]),
+        }
+    }
+
+    /// A badge with the version of the package being built, from `CARGO_PKG_VERSION`.
+    /// Returns `None` outside of Cargo builds.
+    pub fn package_version() -> Option<Self> {
+        env::var("CARGO_PKG_VERSION").ok().map(Self::new)
+    }
+
+    /// A badge with the abbreviated hash of the checked out git commit.
+    /// Fails if `git` is not installed or the working directory is not in a git repository.
+    ///
+    /// In build scripts, this tells Cargo to re-run the build script when `HEAD` or the branch
+    /// it points to changes, so that the badge doesn't show the hash of an older commit.
+    pub fn git_hash() -> Result<Self> {
+        let hash = git(&["rev-parse", "--short", "HEAD"])?;
+        if env::var_os("OUT_DIR").is_some() {
+            for path in git_head_paths()? {
+                println!("cargo:rerun-if-changed={path}");
+            }
+        }
+        Ok(Self::new(hash))
+    }
+
+    /// Customizes the smallest frame size that shows the badge,
+    /// since small frames have no space for a label. Defaults to `48`.
+    pub fn min_size(mut self, min_size: u32) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Customizes the colors of the band and of the label.
+    /// Defaults to white on translucent black.
+    pub fn colors(mut self, background: Rgba<u8>, foreground: Rgba<u8>) -> Self {
+        self.background = background;
+        self.foreground = foreground;
+        self
+    }
+}
+
+/// Runs `git` with `args` and returns its trimmed output.
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(
+            io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_owned()).into(),
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The files that change when a different commit is checked out: `HEAD`, and the branch that it
+/// points to, as a loose ref and in `packed-refs` if these exist. Cargo considers missing files
+/// to always be changed, so they would re-run the build script on every build.
+fn git_head_paths() -> Result<Vec<String>> {
+    let mut paths = vec![git(&["rev-parse", "--git-path", "HEAD"])?];
+    // Fails on a detached `HEAD`, which contains the hash itself.
+    if let Ok(branch) = git(&["symbolic-ref", "-q", "HEAD"]) {
+        for path in [&branch, "packed-refs"] {
+            let path = git(&["rev-parse", "--git-path", path])?;
+            if Path::new(&path).exists() {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+impl Transform for VersionBadge {
+    fn apply(&self, frame: &mut RgbaImage) {
+        let size = frame.width();
+        if size < self.min_size || self.label.is_empty() {
+            return;
+        }
+        // The largest scale at which the label fits, with a margin of one scaled pixel.
+        let scale = (1..=(size / 64).max(1))
+            .rev()
+            .find(|&scale| font::text_width(&self.label, scale) + 2 * scale <= size)
+            .unwrap_or(1);
+        let band_height = font::text_height(scale) + 2 * scale;
+        let band_y = frame.height().saturating_sub(band_height);
+        for y in band_y..frame.height() {
+            for x in 0..size {
+                let pixel = frame.get_pixel_mut(x, y);
+                *pixel = blend_over(self.background, *pixel);
+            }
+        }
+        let text_x = (i64::from(size) - i64::from(font::text_width(&self.label, scale))) / 2;
+        font::draw_text(
+            frame,
+            &self.label,
+            (text_x, i64::from(band_y + scale)),
+            scale,
+            self.foreground,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_the_checked_out_head() {
+        // Packaged sources are not in a git repository.
+        let Ok(paths) = git_head_paths() else {
+            return;
+        };
+        assert!(paths[0].ends_with("HEAD"));
+        assert!(paths.iter().all(|path| Path::new(path).is_file()));
+        assert!(paths[1..]
+            .iter()
+            .all(|path| path.contains("refs/") || path.ends_with("packed-refs")));
+    }
+
+    #[test]
+    fn draws_a_band_with_the_label() {
+        let (background, foreground) = (Rgba([0, 0, 0xff, 0xff]), Rgba([0xff, 0, 0, 0xff]));
+        let badge = VersionBadge::new("rc1").colors(background, foreground);
+        let white = Rgba([0xff, 0xff, 0xff, 0xff]);
+        let mut frame = RgbaImage::from_pixel(64, 64, white);
+        badge.apply(&mut frame);
+
+        let band_y = 64 - font::text_height(1) - 2;
+        assert!(frame
+            .rows()
+            .take(band_y as usize)
+            .flatten()
+            .all(|pixel| *pixel == white));
+        let band: Vec<_> = frame.rows().skip(band_y as usize).flatten().collect();
+        assert!(band
+            .iter()
+            .all(|pixel| **pixel == background || **pixel == foreground));
+        assert!(band.contains(&&foreground));
+        // The label is centered.
+        let label_columns: Vec<_> = (0..64)
+            .filter(|&x| (band_y..64).any(|y| *frame.get_pixel(x, y) == foreground))
+            .collect();
+        let (left, right) = (
+            label_columns[0],
+            63 - label_columns[label_columns.len() - 1],
+        );
+        assert!(left.abs_diff(right) <= 1, "{left} vs. {right}");
+    }
+
+    #[test]
+    fn small_frames_and_empty_labels_are_unchanged() {
+        let frame = RgbaImage::from_pixel(32, 32, Rgba([0xff, 0xff, 0xff, 0xff]));
+        let mut small = frame.clone();
+        VersionBadge::new("1.0").apply(&mut small);
+        assert_eq!(small, frame);
+        let mut empty = frame.clone();
+        VersionBadge::new("").min_size(16).apply(&mut empty);
+        assert_eq!(empty, frame);
+    }
+}
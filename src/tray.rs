@@ -0,0 +1,95 @@
+//! Icons for the system tray and menu bar.
+
+use crate::transform::Silhouette;
+use crate::{IcoBuilder, Result};
+use image::{Rgba, RgbaImage};
+
+/// The sizes of tray icons at display scale factors of 100%, 125%, 150% and 200%.
+const TRAY_SIZES: &[u32] = &[16, 20, 24, 32];
+
+/// The size of tray icons at a scale factor of 100%.
+const BASE_SIZE: u32 = 16;
+
+/// RGBA frames at 16, 20, 24 and 32px for use with tray crates like `tray-icon`,
+/// which usually take raw RGBA pixels, e.g. via `Icon::from_rgba(frame.to_vec(), width, height)`.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::tray::TrayIcon;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-32x32.png");
+/// let tray = TrayIcon::new(&icon)?;
+/// let frame = tray.for_scale_factor(1.5);
+/// assert_eq!(frame.width(), 24);
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrayIcon {
+    frames: Vec<RgbaImage>,
+}
+
+impl TrayIcon {
+    /// Renders the tray icon from the sources of an [`IcoBuilder`].
+    pub fn new(icon: &IcoBuilder) -> Result<TrayIcon> {
+        Ok(TrayIcon {
+            frames: icon.render(TRAY_SIZES)?,
+        })
+    }
+
+    /// Renders a template icon for the macOS menu bar, which is a black [`Silhouette`]
+    /// of the artwork that macOS recolors to match the appearance of the menu bar.
+    pub fn template(icon: &IcoBuilder) -> Result<TrayIcon> {
+        let mut icon = icon.clone();
+        icon.add_transform(Silhouette::new(Rgba([0x00, 0x00, 0x00, 0xff])));
+        TrayIcon::new(&icon)
+    }
+
+    /// The frames, ordered from smallest to largest.
+    pub fn frames(&self) -> &[RgbaImage] {
+        &self.frames
+    }
+
+    /// Selects the frame for a display scale factor, e.g. `1.25` for 125%.
+    /// This is the smallest frame that is at least as large as the scaled size of 16px,
+    /// or the largest frame for scale factors above 200%.
+    pub fn for_scale_factor(&self, scale_factor: f64) -> &RgbaImage {
+        let size = (f64::from(BASE_SIZE) * scale_factor).round() as u32;
+        self.frames
+            .iter()
+            .find(|frame| frame.width() >= size)
+            .unwrap_or_else(|| self.frames.last().expect("tray icons have frames"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon() -> IcoBuilder {
+        let mut icon = IcoBuilder::default();
+        icon.add_source_image(RgbaImage::from_pixel(32, 32, Rgba([0, 120, 215, 255])));
+        icon
+    }
+
+    #[test]
+    fn selects_frames_by_scale_factor() {
+        let tray = TrayIcon::new(&icon()).unwrap();
+        let sizes: Vec<_> = tray.frames().iter().map(RgbaImage::width).collect();
+        assert_eq!(sizes, TRAY_SIZES);
+        let size = |scale_factor| tray.for_scale_factor(scale_factor).width();
+        assert_eq!(size(0.5), 16);
+        assert_eq!(size(1.0), 16);
+        assert_eq!(size(1.1), 20);
+        assert_eq!(size(1.25), 20);
+        assert_eq!(size(1.75), 32);
+        assert_eq!(size(3.0), 32);
+    }
+
+    #[test]
+    fn templates_are_black_silhouettes() {
+        let tray = TrayIcon::template(&icon()).unwrap();
+        for frame in tray.frames() {
+            assert!(frame.pixels().all(|pixel| *pixel == Rgba([0, 0, 0, 255])));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Comparing ICO files by their decoded frames.
+
+use crate::reader::{IcoEntry, IcoFile};
+use image::{Rgba, RgbaImage};
+
+/// Checks that `actual` has the same frames as `expected`. Fully transparent pixels are equal
+/// regardless of their color, since encoders may store anything there.
+///
+/// Returns why the files differ otherwise.
+pub(crate) fn compare(expected: &IcoFile, actual: &IcoFile, tolerance: u8) -> Result<(), String> {
+    let expected_sizes = describe_sizes(expected.entries());
+    let actual_sizes = describe_sizes(actual.entries());
+    if expected_sizes != actual_sizes {
+        return Err(format!(
+            "it has frames of {actual_sizes} instead of {expected_sizes}"
+        ));
+    }
+    for (expected, actual) in expected.entries().iter().zip(actual.entries()) {
+        let size = expected.width();
+        let decode = |entry: &IcoEntry| -> Result<RgbaImage, String> {
+            entry
+                .decode()
+                .map_err(|error| format!("the {size}px frame can't be decoded: {error}"))
+        };
+        let (expected, actual) = (decode(expected)?, decode(actual)?);
+        for ((x, y, expected), actual) in expected.enumerate_pixels().zip(actual.pixels()) {
+            if pixels_differ(*expected, *actual, tolerance) {
+                return Err(format!(
+                    "the {size}px frame differs at ({x}, {y}): expected {:?}, found {:?}",
+                    expected.0, actual.0
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a color channel differs by more than `tolerance`.
+/// Fully transparent pixels are equal regardless of their color.
+pub(crate) fn pixels_differ(expected: Rgba<u8>, actual: Rgba<u8>, tolerance: u8) -> bool {
+    if expected[3] == 0 && actual[3] == 0 {
+        return false;
+    }
+    (0..4).any(|i| expected[i].abs_diff(actual[i]) > tolerance)
+}
+
+fn describe_sizes(entries: &[IcoEntry]) -> String {
+    let sizes: Vec<_> = entries
+        .iter()
+        .map(|entry| format!("{}x{}", entry.width(), entry.height()))
+        .collect();
+    sizes.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IcoBuilder;
+    use image::Rgba;
+
+    fn icon(sizes: &[u32], color: Rgba<u8>) -> IcoFile {
+        let (data, _) = IcoBuilder::default()
+            .sizes(sizes)
+            .add_source_image(RgbaImage::from_pixel(32, 32, color))
+            .encode_ico()
+            .unwrap();
+        IcoFile::parse(&data).unwrap()
+    }
+
+    #[test]
+    fn compares_sizes_and_pixels() {
+        let expected = icon(&[16, 32], Rgba([100, 0, 0, 255]));
+        assert_eq!(compare(&expected, &expected, 0), Ok(()));
+        assert_eq!(
+            compare(&expected, &icon(&[16], Rgba([100, 0, 0, 255])), 0),
+            Err("it has frames of 16x16 instead of 16x16, 32x32".to_owned())
+        );
+        let actual = icon(&[16, 32], Rgba([103, 0, 0, 255]));
+        assert_eq!(compare(&expected, &actual, 3), Ok(()));
+        assert_eq!(
+            compare(&expected, &actual, 2),
+            Err(
+                "the 16px frame differs at (0, 0): expected [100, 0, 0, 255], found [103, 0, 0, 255]"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn hidden_colors_are_equal() {
+        let red = icon(&[16], Rgba([255, 0, 0, 0]));
+        assert_eq!(compare(&red, &icon(&[16], Rgba([0, 0, 255, 0])), 0), Ok(()));
+        let visible = icon(&[16], Rgba([0, 0, 0, 1]));
+        assert!(compare(&red, &visible, 0).is_err());
+        assert_eq!(
+            compare(&icon(&[16], Rgba([0, 0, 0, 0])), &visible, 1),
+            Ok(())
+        );
+    }
+}
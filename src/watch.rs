@@ -0,0 +1,113 @@
+//! Watching source files for changes. Requires the `watch` feature.
+
+use crate::Result;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for further changes after a change,
+/// since editors and exporters often write files in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Calls `on_change` whenever one of the files changes, until it returns [`ControlFlow::Break`].
+pub(crate) fn watch_files(
+    paths: &[PathBuf],
+    mut on_change: impl FnMut() -> ControlFlow<()>,
+) -> Result<()> {
+    let paths: BTreeSet<_> = paths.iter().map(|path| normalize(path)).collect();
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    // The directories are watched, since editors often replace files instead of writing to them.
+    let dirs: BTreeSet<_> = paths.iter().filter_map(|path| path.parent()).collect();
+    for dir in dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+    }
+
+    let is_change = |event: &Event| {
+        matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && event
+            .paths
+            .iter()
+            .any(|path| paths.contains(&normalize(path)))
+    };
+    for event in &receiver {
+        if !is_change(&event.map_err(io::Error::other)?) {
+            continue;
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+        if on_change().is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// An absolute path with a canonical parent directory, which works for files that were removed.
+fn normalize(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(file_name)) => parent.join(file_name),
+        _ => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::{fs, thread};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ico-builder-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn removed_files_keep_a_canonical_parent() {
+        let dir = temp_dir("normalize");
+        let path = dir.join("removed.png");
+        assert_eq!(
+            normalize(&dir.join(".").join("removed.png")),
+            normalize(&path)
+        );
+        assert!(normalize(&path).is_absolute());
+        assert!(normalize(Path::new("removed.png")).is_absolute());
+    }
+
+    #[test]
+    fn changes_to_watched_files_are_reported() {
+        let dir = temp_dir("watch");
+        let (watched, other) = (dir.join("icon.png"), dir.join("other.png"));
+        fs::write(&watched, b"").unwrap();
+        let changed = AtomicBool::new(false);
+        thread::scope(|scope| {
+            // Writes until the change is seen, since the watcher starts asynchronously,
+            // pausing longer than the debounce so that it ends.
+            scope.spawn(|| {
+                while !changed.load(Ordering::SeqCst) {
+                    fs::write(&other, b"other").unwrap();
+                    fs::write(&watched, b"icon").unwrap();
+                    thread::sleep(DEBOUNCE * 3);
+                }
+            });
+            watch_files(std::slice::from_ref(&watched), || {
+                changed.store(true, Ordering::SeqCst);
+                ControlFlow::Break(())
+            })
+            .unwrap();
+        });
+        assert!(changed.load(Ordering::SeqCst));
+    }
+}
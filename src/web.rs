@@ -0,0 +1,461 @@
+//! Favicons and other icon assets for websites.
+
+use crate::export::center_on_canvas;
+use crate::hash::Fnv1a;
+use crate::transform::{Background, Transform};
+use crate::{encode_png, IcoBuilder, IconSizes, Result};
+use image::{Rgba, RgbaImage};
+use std::f32::consts::SQRT_2;
+use std::path::{Path, PathBuf};
+
+/// PNG favicons as `(file name, size)`.
+const FAVICONS: &[(&str, u32)] = &[
+    ("favicon-16x16.png", 16),
+    ("favicon-32x32.png", 32),
+    ("apple-touch-icon.png", 180),
+    ("android-chrome-192x192.png", 192),
+    ("android-chrome-512x512.png", 512),
+];
+
+/// Icons listed in `site.webmanifest` as `(file name, size)`.
+const MANIFEST_ICONS: &[(&str, u32)] = &[
+    ("android-chrome-192x192.png", 192),
+    ("android-chrome-512x512.png", 512),
+];
+
+/// Maskable icons for `site.webmanifest` as `(file name, size)`.
+const MASKABLE_ICONS: &[(&str, u32)] = &[
+    ("maskable-icon-192x192.png", 192),
+    ("maskable-icon-512x512.png", 512),
+];
+
+/// The diameter of the circle that is always visible in maskable icons, relative to their size.
+const MASKABLE_SAFE_ZONE: f32 = 0.8;
+
+/// Windows pinned site tiles as `(file name, browserconfig element, width, height)`.
+/// The image sizes are larger than the names suggest to account for display scaling.
+const TILES: &[(&str, Option<&str>, u32, u32)] = &[
+    ("mstile-70x70.png", Some("square70x70logo"), 128, 128),
+    ("mstile-144x144.png", None, 144, 144),
+    ("mstile-150x150.png", Some("square150x150logo"), 270, 270),
+    ("mstile-310x150.png", Some("wide310x150logo"), 558, 270),
+    ("mstile-310x310.png", Some("square310x310logo"), 558, 558),
+];
+
+/// The size of the pixel grid that the Safari pinned tab icon is traced on.
+const MASK_ICON_SIZE: u32 = 64;
+
+/// Exports a set of web icons from the sources of an [`IcoBuilder`]:
+/// * `favicon.ico` with the [`IconSizes::FAVICON`] sizes
+/// * PNG favicons, including `apple-touch-icon.png`
+/// * `mstile-*.png` tiles and a `browserconfig.xml` for Windows pinned sites
+/// * `site.webmanifest` for progressive web apps, optionally with [maskable](WebExport::maskable) icons
+/// * optionally, a monochrome [`safari-pinned-tab.svg`](WebExport::mask_icon)
+/// * `head.html` with the `<link>` and `<meta>` elements for the `<head>` of web pages
+///
+/// File names can contain [content hashes](WebExport::hashed_file_names) for cache busting.
+///
+/// ```no_run
+/// # use ico_builder::IcoBuilder;
+/// # use ico_builder::web::WebExport;
+/// let mut icon = IcoBuilder::default();
+/// icon.add_source_file("app-icon-512x512.png");
+/// WebExport::default().build(&icon, "public")?;
+/// # Ok::<_, ico_builder::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebExport {
+    tile_color: Rgba<u8>,
+    base_path: String,
+    mask_icon: bool,
+    maskable_background: Option<Rgba<u8>>,
+    hashed_file_names: bool,
+}
+
+impl Default for WebExport {
+    fn default() -> Self {
+        WebExport {
+            tile_color: Rgba([0x2b, 0x57, 0x97, 0xff]),
+            base_path: "/".to_owned(),
+            mask_icon: false,
+            maskable_background: None,
+            hashed_file_names: false,
+        }
+    }
+}
+
+impl WebExport {
+    /// The background color of Windows tiles, written to `browserconfig.xml`.
+    pub fn tile_color(&mut self, tile_color: Rgba<u8>) -> &mut WebExport {
+        self.tile_color = tile_color;
+        self
+    }
+
+    /// The URL path under which the icons are served. Defaults to `/`.
+    pub fn base_path(&mut self, base_path: impl Into<String>) -> &mut WebExport {
+        self.base_path = base_path.into();
+        if !self.base_path.ends_with('/') {
+            self.base_path.push('/');
+        }
+        self
+    }
+
+    /// Enables writing `safari-pinned-tab.svg`, a single-color version of the icon
+    /// for pinned tabs in Safari. Its color is set by the `<link rel="mask-icon">` element,
+    /// which uses the [tile color](WebExport::tile_color) in `head.html`.
+    ///
+    /// Since only raster sources are supported, the SVG is traced from the alpha channel
+    /// of a 64px frame, so fine details and anti-aliasing are lost. Defaults to `false`.
+    pub fn mask_icon(&mut self, mask_icon: bool) -> &mut WebExport {
+        self.mask_icon = mask_icon;
+        self
+    }
+
+    /// Enables writing maskable icons, which Android launchers and other platforms crop to
+    /// arbitrary shapes. The artwork is scaled to fit into the safe zone, a circle with 80%
+    /// of the icon's diameter, and placed on an opaque `background`.
+    /// The icons are listed in `site.webmanifest` with `"purpose": "maskable"`.
+    pub fn maskable(&mut self, background: Rgba<u8>) -> &mut WebExport {
+        self.maskable_background = Some(Rgba([background[0], background[1], background[2], 0xff]));
+        self
+    }
+
+    /// Enables content-hashed file names like `favicon.3fa9c2d1.ico` for all files except
+    /// `head.html`, so that they can be served with long-lived cache headers. References in
+    /// `site.webmanifest`, `browserconfig.xml` and `head.html` use the hashed names.
+    /// Defaults to `false`.
+    pub fn hashed_file_names(&mut self, hashed_file_names: bool) -> &mut WebExport {
+        self.hashed_file_names = hashed_file_names;
+        self
+    }
+
+    /// Writes all icons to `output_dir` and returns the paths of the written files.
+    pub fn build(&self, icon: &IcoBuilder, output_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let mut files = WebFiles::new(self.hashed_file_names);
+
+        let (favicon, _) = icon.clone().sizes(IconSizes::FAVICON).encode_ico()?;
+        files.add("favicon.ico", favicon);
+
+        let sizes: Vec<_> = FAVICONS.iter().map(|&(_, size)| size).collect();
+        for (frame, (name, _)) in icon.render(&sizes)?.iter().zip(FAVICONS) {
+            files.add(name, encode_png(frame)?);
+        }
+
+        let artwork_sizes: Vec<_> = TILES.iter().map(|&(.., height)| height / 2).collect();
+        for (artwork, &(name, _, width, height)) in icon.render(&artwork_sizes)?.iter().zip(TILES) {
+            let tile = center_on_canvas(artwork, width, height);
+            files.add(name, encode_png(&tile)?);
+        }
+
+        if let Some(background) = self.maskable_background {
+            let background = Background::solid(background);
+            // The largest square that fits into the safe zone.
+            let artwork_sizes: Vec<_> = MASKABLE_ICONS
+                .iter()
+                .map(|&(_, size)| (size as f32 * MASKABLE_SAFE_ZONE / SQRT_2) as u32)
+                .collect();
+            let artworks = icon.render(&artwork_sizes)?;
+            for (artwork, &(name, size)) in artworks.iter().zip(MASKABLE_ICONS) {
+                let mut maskable = center_on_canvas(artwork, size, size);
+                background.apply(&mut maskable);
+                files.add(name, encode_png(&maskable)?);
+            }
+        }
+
+        if self.mask_icon {
+            let frame = icon.render(&[MASK_ICON_SIZE])?.remove(0);
+            files.add(
+                "safari-pinned-tab.svg",
+                trace_mask_icon(&frame).into_bytes(),
+            );
+        }
+
+        let webmanifest = self.webmanifest(&files);
+        files.add("site.webmanifest", webmanifest.into_bytes());
+        let browserconfig = self.browserconfig(&files);
+        files.add("browserconfig.xml", browserconfig.into_bytes());
+        let head = self.head(&files);
+        files.add_unhashed("head.html", head.into_bytes());
+
+        let output_dir = output_dir.as_ref();
+        files
+            .files
+            .iter()
+            .map(|(_, file_name, contents)| {
+                let path = output_dir.join(file_name);
+                icon.write_file(&path, contents)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    fn webmanifest(&self, files: &WebFiles) -> String {
+        let mut icons: Vec<_> = MANIFEST_ICONS
+            .iter()
+            .map(|&(name, size)| self.manifest_icon(files.file_name(name), size, "any"))
+            .collect();
+        if self.maskable_background.is_some() {
+            icons.extend(
+                MASKABLE_ICONS.iter().map(|&(name, size)| {
+                    self.manifest_icon(files.file_name(name), size, "maskable")
+                }),
+            );
+        }
+        format!(
+            "{{\n  \
+               \"icons\": [\n{icons}\n  ]\n\
+             }}\n",
+            icons = icons.join(",\n")
+        )
+    }
+
+    fn manifest_icon(&self, name: &str, size: u32, purpose: &str) -> String {
+        format!(
+            "    {{\n      \
+               \"src\": \"{base}{name}\",\n      \
+               \"sizes\": \"{size}x{size}\",\n      \
+               \"type\": \"image/png\",\n      \
+               \"purpose\": \"{purpose}\"\n    \
+             }}",
+            base = self.base_path
+        )
+    }
+
+    /// The `<link>` and `<meta>` elements that reference the icons, for the `<head>` of web pages.
+    fn head(&self, files: &WebFiles) -> String {
+        let base = &self.base_path;
+        let url = |name: &str| format!("{base}{}", files.file_name(name));
+        let color = hex_color(self.tile_color);
+        let mut head = format!(
+            "<link rel=\"icon\" href=\"{favicon}\" sizes=\"any\">\n\
+             <link rel=\"icon\" type=\"image/png\" sizes=\"32x32\" href=\"{favicon_32}\">\n\
+             <link rel=\"icon\" type=\"image/png\" sizes=\"16x16\" href=\"{favicon_16}\">\n\
+             <link rel=\"apple-touch-icon\" sizes=\"180x180\" href=\"{apple_touch_icon}\">\n\
+             <link rel=\"manifest\" href=\"{webmanifest}\">\n",
+            favicon = url("favicon.ico"),
+            favicon_32 = url("favicon-32x32.png"),
+            favicon_16 = url("favicon-16x16.png"),
+            apple_touch_icon = url("apple-touch-icon.png"),
+            webmanifest = url("site.webmanifest"),
+        );
+        if self.mask_icon {
+            head.push_str(&format!(
+                "<link rel=\"mask-icon\" href=\"{}\" color=\"{color}\">\n",
+                url("safari-pinned-tab.svg")
+            ));
+        }
+        head.push_str(&format!(
+            "<meta name=\"msapplication-config\" content=\"{}\">\n\
+             <meta name=\"msapplication-TileColor\" content=\"{color}\">\n",
+            url("browserconfig.xml")
+        ));
+        head
+    }
+
+    fn browserconfig(&self, files: &WebFiles) -> String {
+        let logos: String = TILES
+            .iter()
+            .filter_map(|&(name, element, ..)| {
+                let element = element?;
+                Some(format!(
+                    "      <{element} src=\"{base}{name}\"/>\n",
+                    base = self.base_path,
+                    name = files.file_name(name)
+                ))
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <browserconfig>\n  \
+               <msapplication>\n    \
+                 <tile>\n\
+                   {logos}      \
+                   <TileColor>{color}</TileColor>\n    \
+                 </tile>\n  \
+               </msapplication>\n\
+             </browserconfig>\n",
+            color = hex_color(self.tile_color)
+        )
+    }
+}
+
+/// The contents of the exported files, in the order they are written.
+struct WebFiles {
+    hashed_file_names: bool,
+    /// The files as `(name, file name, contents)`, where the file name may contain a hash.
+    files: Vec<(&'static str, String, Vec<u8>)>,
+}
+
+impl WebFiles {
+    fn new(hashed_file_names: bool) -> WebFiles {
+        WebFiles {
+            hashed_file_names,
+            files: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, name: &'static str, contents: Vec<u8>) {
+        let file_name = if self.hashed_file_names {
+            hashed_file_name(name, &contents)
+        } else {
+            name.to_owned()
+        };
+        self.files.push((name, file_name, contents));
+    }
+
+    fn add_unhashed(&mut self, name: &'static str, contents: Vec<u8>) {
+        self.files.push((name, name.to_owned(), contents));
+    }
+
+    /// The file name of a previously added file.
+    fn file_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.files
+            .iter()
+            .find(|(existing, ..)| *existing == name)
+            .map_or(name, |(_, file_name, _)| file_name)
+    }
+}
+
+/// Inserts a hash of the contents before the extension, e.g. `favicon.3fa9c2d1.ico`.
+fn hashed_file_name(name: &str, contents: &[u8]) -> String {
+    let hash = Fnv1a::default().write(contents).finish() >> 32;
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}.{hash:08x}.{extension}"),
+        None => format!("{name}.{hash:08x}"),
+    }
+}
+
+/// Traces the opaque pixels of the frame as an SVG path of one rectangle per horizontal run.
+fn trace_mask_icon(frame: &RgbaImage) -> String {
+    let mut path = String::new();
+    for (y, row) in frame.rows().enumerate() {
+        let mut x = 0;
+        let row: Vec<_> = row.map(|pixel| pixel[3] >= 0x80).collect();
+        while x < row.len() {
+            if !row[x] {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < row.len() && row[x] {
+                x += 1;
+            }
+            let width = x - start;
+            path.push_str(&format!("M{start} {y}h{width}v1h-{width}z"));
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\">\n  \
+           <path d=\"{path}\"/>\n\
+         </svg>\n",
+        width = frame.width(),
+        height = frame.height()
+    )
+}
+
+fn hex_color(Rgba([r, g, b, _]): Rgba<u8>) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{Fs, MemoryFs};
+    use std::sync::Arc;
+
+    #[test]
+    fn browserconfig_lists_the_tiles() {
+        let browserconfig = WebExport::default()
+            .base_path("/static")
+            .tile_color(Rgba([0, 0x80, 0xff, 0xff]))
+            .browserconfig(&WebFiles::new(false));
+        assert!(browserconfig.contains("<square70x70logo src=\"/static/mstile-70x70.png\"/>"));
+        assert!(browserconfig.contains("<wide310x150logo src=\"/static/mstile-310x150.png\"/>"));
+        assert!(!browserconfig.contains("mstile-144x144.png"));
+        assert!(browserconfig.contains("<TileColor>#0080ff</TileColor>"));
+    }
+
+    #[test]
+    fn lists_maskable_icons_in_the_webmanifest() {
+        let webmanifest = WebExport::default()
+            .base_path("/static")
+            .webmanifest(&WebFiles::new(false));
+        assert!(webmanifest.contains("\"src\": \"/static/android-chrome-192x192.png\""));
+        assert!(!webmanifest.contains("maskable"));
+        let webmanifest = WebExport::default()
+            .maskable(Rgba([0, 0, 0xff, 0]))
+            .webmanifest(&WebFiles::new(false));
+        assert_eq!(webmanifest.matches("\"purpose\": \"maskable\"").count(), 2);
+        assert!(webmanifest.contains("\"src\": \"/maskable-icon-512x512.png\""));
+    }
+
+    #[test]
+    fn traces_opaque_runs() {
+        let frame = RgbaImage::from_fn(4, 2, |x, y| match (x, y) {
+            (1 | 2, 0) | (0, 1) | (3, 1) => Rgba([0, 0, 0, 0xff]),
+            _ => Rgba([0, 0, 0, 0x7f]),
+        });
+        let svg = trace_mask_icon(&frame);
+        assert!(svg.contains("viewBox=\"0 0 4 2\""));
+        assert!(svg.contains("<path d=\"M1 0h2v1h-2zM0 1h1v1h-1zM3 1h1v1h-1z\"/>"));
+    }
+
+    #[test]
+    fn hashes_go_before_the_extension() {
+        let hashed = hashed_file_name("favicon.ico", b"icon");
+        let (stem, rest) = hashed.split_once('.').unwrap();
+        let (hash, extension) = rest.split_once('.').unwrap();
+        assert_eq!((stem, extension), ("favicon", "ico"));
+        assert_eq!(hash.len(), 8);
+        assert_ne!(hashed, hashed_file_name("favicon.ico", b"other icon"));
+        assert_eq!(hashed_file_name("LICENSE", b"").matches('.').count(), 1);
+    }
+
+    #[test]
+    fn references_use_the_hashed_file_names() {
+        let fs = Arc::new(MemoryFs::default());
+        let mut icon = IcoBuilder::default();
+        icon.fs(fs.clone())
+            .allow_upscaling(true)
+            .add_source_image(RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255])));
+        let written = WebExport::default()
+            .base_path("/static")
+            .mask_icon(true)
+            .maskable(Rgba([0, 0, 0xff, 0]))
+            .hashed_file_names(true)
+            .build(&icon, "public")
+            .unwrap();
+        assert_eq!(written.last(), Some(&Path::new("public").join("head.html")));
+
+        let read = |path: &Path| String::from_utf8_lossy(&fs.read(path).unwrap()).into_owned();
+        let head = read(&Path::new("public").join("head.html"));
+        let webmanifest = written
+            .iter()
+            .find(|path| path.to_string_lossy().contains("site."))
+            .unwrap();
+        let webmanifest_name = webmanifest.file_name().unwrap().to_string_lossy();
+        assert_ne!(webmanifest_name, "site.webmanifest");
+        assert!(head.contains(&format!("href=\"/static/{webmanifest_name}\"")));
+        assert!(head.contains("color=\"#2b5797\""));
+
+        let webmanifest = read(webmanifest);
+        assert_eq!(webmanifest.matches("\"purpose\": \"maskable\"").count(), 2);
+        for path in &written {
+            let name = path.file_name().unwrap().to_string_lossy();
+            if name.starts_with("android-chrome") || name.starts_with("maskable") {
+                assert!(webmanifest.contains(&format!("/static/{name}")), "{name}");
+            }
+        }
+
+        let maskable = written
+            .iter()
+            .find(|path| path.to_string_lossy().contains("maskable-icon-192x192"))
+            .unwrap();
+        let maskable = image::load_from_memory(&fs.read(maskable).unwrap())
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(*maskable.get_pixel(0, 0), Rgba([0, 0, 0xff, 0xff]));
+        assert_eq!(*maskable.get_pixel(96, 96), Rgba([255, 0, 0, 255]));
+    }
+}